@@ -0,0 +1,441 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::format::Report;
+use crate::hours::QuietHours;
+use crate::notifier::{self, KeybaseApi, Severity};
+use crate::pace::Pace;
+use crate::subscription::Subscriber;
+
+/// ```toml
+/// [alerting]
+/// cooldown_secs = 1800
+/// escalate_below = 5
+/// quiet_hours = "22:00-07:00 America/Los_Angeles"
+/// table = true
+/// pace_over = 6
+/// close_after = 2
+/// closed = "alert"
+/// ```
+///
+/// An open date is alerted once, then again only after `cooldown_secs`, unless its remaining
+/// permits drop under `escalate_below`, which always goes out right away. A date that closes and
+/// reopens is new again, once it has been under its watch's `close_below` for `close_after`
+/// scrapes in a row. So a date hovering around a threshold doesn't alert on every climb back.
+/// Then a message says how long it was open for, to the watch's channel with `closed = "alert"`,
+/// to `pcta-logs` with `"info"` or only where `[[keybase.channels]]` take `"debug"`.
+///
+/// Alerts that come due during `quiet_hours` are held and go out together as a morning summary
+/// once the quiet hours end. The notifiers that ping people get one alert per watch then, with
+/// every date that came open overnight. Scraping carries on regardless, only the pings wait.
+///
+/// With `table` the open dates are laid out as a table with a bar per date instead of a list.
+///
+/// A date that has been going since it came open is listed with how many permits an hour were
+/// taken over the latest `pace_over` snapshots of its calendar, and when the rest will likely be
+/// gone at that rate. `0` leaves the pace out.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertPolicy {
+    /// `0` alerts on every scrape that finds the date open
+    pub cooldown_secs: u64,
+    pub escalate_below: u64,
+    pub quiet_hours: Option<QuietHours>,
+    pub table: bool,
+    pub pace_over: usize,
+    pub close_after: u32,
+    pub closed: Severity,
+}
+
+impl Default for AlertPolicy {
+    fn default() -> Self {
+        AlertPolicy {
+            cooldown_secs: 0,
+            escalate_below: 5,
+            quiet_hours: None,
+            table: false,
+            pace_over: 6,
+            close_after: 2,
+            closed: Severity::Info,
+        }
+    }
+}
+
+/// Remembers what each watch last alerted on, to apply an `AlertPolicy`
+pub struct Alerter {
+    policy: AlertPolicy,
+    /// `(watch, date)` to when it was last alerted and with how many permits left
+    sent: HashMap<(String, NaiveDate), (DateTime<Utc>, u64)>,
+    /// Scrapes in a row an alerted date has been closed for
+    closing: HashMap<(String, NaiveDate), u32>,
+    /// When each alerted date was first alerted, since it last came open
+    opened: HashMap<(String, NaiveDate), DateTime<Utc>>,
+    /// Dates that closed and haven't been said so yet
+    closed: Vec<(String, Closed)>,
+    /// Alerts waiting for the quiet hours to end
+    held: Vec<KeybaseApi>,
+    /// The same for the notifiers that ping people, one per watch
+    held_reports: Vec<Held>,
+}
+
+/// What a watch opened during the quiet hours, for the notifiers that wait them out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Held {
+    pub label: String,
+    /// Every date that came open, with what it had left when it last did
+    pub dates: Vec<(NaiveDate, u64)>,
+    pub subscribers: Vec<Subscriber>,
+    pub apply_url: String,
+    pub paces: Vec<(NaiveDate, Pace)>,
+}
+
+impl Held {
+    pub fn report(&self) -> Report<'_> {
+        Report::Open {
+            label: &self.label,
+            dates: &self.dates,
+            subscribers: &self.subscribers,
+            apply_url: &self.apply_url,
+            paces: &self.paces,
+        }
+    }
+}
+
+/// Sets what `date` has in `into`, adding it after the others when it isn't there
+fn upsert<T: Copy>(into: &mut Vec<(NaiveDate, T)>, date: NaiveDate, value: T) {
+    match into.iter_mut().find(|(d, _)| *d == date) {
+        Some((_, v)) => *v = value,
+        None => into.push((date, value)),
+    }
+}
+
+impl Alerter {
+    pub fn new(policy: AlertPolicy) -> Self {
+        Alerter {
+            policy,
+            sent: HashMap::new(),
+            closing: HashMap::new(),
+            opened: HashMap::new(),
+            closed: vec![],
+            held: vec![],
+            held_reports: vec![],
+        }
+    }
+
+    pub fn policy(&self) -> &AlertPolicy {
+        &self.policy
+    }
+
+    /// A reloaded policy, keeping what was already sent
+    pub fn reconfigure(&mut self, policy: AlertPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn is_quiet(&self, now: DateTime<Utc>) -> bool {
+        self.policy
+            .quiet_hours
+            .is_some_and(|quiet| quiet.contains(now))
+    }
+
+    /// Keeps `msg` for the morning summary instead of sending it
+    pub fn hold(&mut self, msg: KeybaseApi) {
+        self.held.push(msg);
+    }
+
+    /// Keeps an open `report` for the notifiers that ping people, with what its watch has held
+    pub fn hold_report(&mut self, report: &Report<'_>) {
+        let Report::Open {
+            label,
+            dates,
+            subscribers,
+            apply_url,
+            paces,
+        } = report
+        else {
+            return;
+        };
+        let held = match self.held_reports.iter().position(|h| h.label == *label) {
+            Some(i) => &mut self.held_reports[i],
+            None => {
+                self.held_reports.push(Held {
+                    label: label.to_string(),
+                    dates: vec![],
+                    subscribers: vec![],
+                    apply_url: String::new(),
+                    paces: vec![],
+                });
+                self.held_reports.last_mut().unwrap()
+            }
+        };
+        for (date, left) in dates.iter() {
+            upsert(&mut held.dates, *date, *left);
+        }
+        for (date, pace) in paces.iter() {
+            upsert(&mut held.paces, *date, *pace);
+        }
+        held.subscribers = subscribers.to_vec();
+        held.apply_url = apply_url.to_string();
+    }
+
+    /// What `hold_report` kept, once `now` is out of the quiet hours
+    pub fn release_reports(&mut self, now: DateTime<Utc>) -> Vec<Held> {
+        match self.is_quiet(now) {
+            true => vec![],
+            false => std::mem::take(&mut self.held_reports),
+        }
+    }
+
+    /// The morning summary of everything held, once `now` is out of the quiet hours
+    pub fn release(&mut self, now: DateTime<Utc>, display: &str) -> Vec<KeybaseApi> {
+        if self.held.is_empty() || self.is_quiet(now) {
+            return vec![];
+        }
+        notifier::morning_summary(&std::mem::take(&mut self.held), display)
+    }
+
+    /// Counts a scrape towards closing the dates of `watch` that were alerted and aren't
+    /// `holding` anymore. Closed dates are forgotten so a reopening alerts immediately.
+    pub fn track(&mut self, watch: &str, holding: &[(NaiveDate, u64)], now: DateTime<Utc>) {
+        let sent: Vec<(String, NaiveDate)> = self
+            .sent
+            .keys()
+            .filter(|(w, _)| w == watch)
+            .cloned()
+            .collect();
+        for key in sent {
+            if holding.iter().any(|(d, _)| *d == key.1) {
+                self.closing.remove(&key);
+                continue;
+            }
+            let closed = self.closing.entry(key.clone()).or_default();
+            *closed += 1;
+            if *closed >= self.policy.close_after {
+                self.closing.remove(&key);
+                self.sent.remove(&key);
+                let open_for = self
+                    .opened
+                    .remove(&key)
+                    .map_or(Duration::zero(), |at| now - at);
+                let closed = Closed {
+                    date: key.1,
+                    open_for,
+                };
+                self.closed.push((key.0, closed));
+            }
+        }
+    }
+
+    /// The dates of `watch` that closed since this was last asked
+    pub fn closed(&mut self, watch: &str) -> Vec<Closed> {
+        let (closed, others) = std::mem::take(&mut self.closed)
+            .into_iter()
+            .partition(|(w, _)| w == watch);
+        self.closed = others;
+        closed.into_iter().map(|(_, closed)| closed).collect()
+    }
+
+    /// The part of `open` that should be alerted on at `now`, which is then remembered as sent.
+    /// Dates alerted before stay open while they are `holding`, however few are left.
+    pub fn due(
+        &mut self,
+        watch: &str,
+        open: &[(NaiveDate, u64)],
+        holding: &[(NaiveDate, u64)],
+        now: DateTime<Utc>,
+    ) -> Vec<(NaiveDate, u64)> {
+        let held: Vec<(NaiveDate, u64)> = open.iter().chain(holding).copied().collect();
+        self.track(watch, &held, now);
+
+        let cooldown = Duration::seconds(self.policy.cooldown_secs as i64);
+        let due: Vec<(NaiveDate, u64)> = open
+            .iter()
+            .filter(
+                |(date, remaining)| match self.sent.get(&(watch.to_string(), *date)) {
+                    None => true,
+                    Some((at, last)) => {
+                        now - *at >= cooldown
+                            || (*remaining < self.policy.escalate_below && remaining < last)
+                    }
+                },
+            )
+            .copied()
+            .collect();
+        for (date, remaining) in &due {
+            let key = (watch.to_string(), *date);
+            self.opened.entry(key.clone()).or_insert(now);
+            self.sent.insert(key, (now, *remaining));
+        }
+        due
+    }
+}
+
+/// An alerted date that filled up again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Closed {
+    pub date: NaiveDate,
+    /// Since it was first alerted
+    pub open_for: Duration,
+}
+
+impl Closed {
+    pub fn message(&self, watch: &str, now: &str) -> String {
+        let minutes = self.open_for.num_minutes();
+        let open_for = match minutes {
+            1 => "1 minute".to_string(),
+            0..=59 => format!("{} minutes", minutes),
+            _ => format!("{}h {}m", minutes / 60, minutes % 60),
+        };
+        format!(
+            "`{}` - *{}* is now full at {}, it was open for {}",
+            now,
+            self.date.format("%B %-d"),
+            watch,
+            open_for
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        "2023-04-17T17:00:00Z".parse::<DateTime<Utc>>().unwrap() + Duration::minutes(minutes)
+    }
+
+    fn alerter() -> Alerter {
+        Alerter::new(AlertPolicy {
+            cooldown_secs: 30 * 60,
+            escalate_below: 5,
+            quiet_hours: None,
+            ..AlertPolicy::default()
+        })
+    }
+
+    #[test]
+    fn repeats_only_after_cooldown() {
+        let mut alerter = alerter();
+        let open = vec![(date("2023-04-14"), 12)];
+        assert_eq!(alerter.due("April", &open, &open, at(0)), open);
+        assert!(alerter.due("April", &open, &open, at(10)).is_empty());
+        assert!(alerter.due("April", &open, &open, at(29)).is_empty());
+        assert_eq!(alerter.due("April", &open, &open, at(31)), open);
+    }
+
+    #[test]
+    fn new_dates_and_other_watches_are_not_held_back() {
+        let mut alerter = alerter();
+        let open = vec![(date("2023-04-14"), 12)];
+        alerter.due("April", &open, &open, at(0));
+        let more = vec![(date("2023-04-14"), 12), (date("2023-04-15"), 30)];
+        assert_eq!(
+            alerter.due("April", &more, &more, at(1)),
+            vec![(date("2023-04-15"), 30)]
+        );
+        assert_eq!(alerter.due("May", &more, &more, at(1)), more);
+    }
+
+    #[test]
+    fn dropping_below_threshold_escalates() {
+        let mut alerter = alerter();
+        alerter.due("April", &[(date("2023-04-14"), 6)], &[], at(0));
+        assert!(alerter
+            .due("April", &[(date("2023-04-14"), 5)], &[], at(1))
+            .is_empty());
+        assert_eq!(
+            alerter.due("April", &[(date("2023-04-14"), 3)], &[], at(2)),
+            vec![(date("2023-04-14"), 3)]
+        );
+        // Same count again is not news
+        assert!(alerter
+            .due("April", &[(date("2023-04-14"), 3)], &[], at(3))
+            .is_empty());
+    }
+
+    #[test]
+    fn reopened_date_alerts_again() {
+        let mut alerter = alerter();
+        let open = vec![(date("2023-04-14"), 12)];
+        alerter.due("April", &open, &open, at(0));
+        assert!(alerter.due("April", &[], &[], at(4)).is_empty());
+        assert!(alerter.due("April", &[], &[], at(5)).is_empty());
+        assert_eq!(alerter.due("April", &open, &open, at(6)), open);
+    }
+
+    #[test]
+    fn hovering_dates_close_only_after_a_while() {
+        let mut alerter = Alerter::new(AlertPolicy {
+            cooldown_secs: 60 * 60,
+            close_after: 2,
+            ..AlertPolicy::default()
+        });
+        let day = date("2023-04-14");
+        let open = vec![(day, 50)];
+        let below = vec![(day, 49)];
+        assert_eq!(alerter.due("April", &open, &open, at(0)), open);
+        // Still holding at 49 though not open, then one scrape under isn't enough
+        assert!(alerter.due("April", &[], &below, at(1)).is_empty());
+        assert!(alerter.due("April", &open, &open, at(2)).is_empty());
+        assert!(alerter.due("April", &[], &[], at(3)).is_empty());
+        assert!(alerter.due("April", &open, &open, at(4)).is_empty());
+        // Two in a row closes it
+        alerter.due("April", &[], &[], at(5));
+        assert!(alerter.closed("April").is_empty());
+        alerter.due("April", &[], &[], at(22));
+        let closed = alerter.closed("April");
+        assert_eq!(
+            closed,
+            vec![Closed {
+                date: day,
+                open_for: Duration::minutes(22)
+            }]
+        );
+        assert_eq!(
+            closed[0].message("April", "now"),
+            "`now` - *April 14* is now full at April, it was open for 22 minutes"
+        );
+        assert!(alerter.closed("April").is_empty());
+        assert_eq!(alerter.due("April", &open, &open, at(23)), open);
+    }
+
+    #[test]
+    fn no_cooldown_alerts_every_time() {
+        let mut alerter = Alerter::new(AlertPolicy::default());
+        let open = vec![(date("2023-04-14"), 12)];
+        alerter.due("April", &open, &open, at(0));
+        assert_eq!(alerter.due("April", &open, &open, at(0)), open);
+    }
+
+    #[test]
+    fn quiet_hours_hold_alerts_for_the_morning() {
+        let mut alerter = Alerter::new(AlertPolicy {
+            // 10:00-17:30 UTC, so at(0) is quiet and at(31) is not
+            quiet_hours: Some("10:00-17:30 UTC".parse().unwrap()),
+            ..AlertPolicy::default()
+        });
+        assert!(alerter.is_quiet(at(0)));
+        alerter.hold(notifier::keybase_message(
+            "pcta-alerts",
+            "April open".into(),
+        ));
+        alerter.hold(notifier::keybase_message("pcta-may", "May open".into()));
+        alerter.hold(notifier::keybase_message(
+            "pcta-alerts",
+            "April again".into(),
+        ));
+        assert!(alerter.release(at(10), "now").is_empty());
+
+        let summary = alerter.release(at(31), "now");
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0].topic(), "pcta-alerts");
+        assert!(summary[0].body().contains("April open"));
+        assert!(summary[0].body().contains("April again"));
+        assert_eq!(summary[1].topic(), "pcta-may");
+        assert!(alerter.release(at(32), "now").is_empty());
+    }
+}
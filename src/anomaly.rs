@@ -0,0 +1,230 @@
+use anyhow::Context;
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// ```toml
+/// [anomaly]
+/// enabled = true
+/// min_fraction = 0.5
+/// season = "03-01..09-30"
+/// ```
+///
+/// A calendar that shrank below `min_fraction` of the last one, dropped to zero everywhere at
+/// once, or lists PCTA start dates outside `season` is reported as an error instead of being
+/// believed. The same result on the next scrape is taken as real.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnomalyConfig {
+    pub enabled: bool,
+    pub min_fraction: f64,
+    pub season: Season,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        AnomalyConfig {
+            enabled: true,
+            min_fraction: 0.5,
+            season: Season {
+                start: (3, 1),
+                end: (9, 30),
+            },
+        }
+    }
+}
+
+/// Start dates the portal issues permits for, as `MM-DD..MM-DD` in any year. A season that
+/// ends before it starts, `11-01..02-28`, runs over New Year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Season {
+    pub start: (u32, u32),
+    pub end: (u32, u32),
+}
+
+impl Season {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        let day = (date.month(), date.day());
+        match self.start <= self.end {
+            true => day >= self.start && day <= self.end,
+            false => day >= self.start || day <= self.end,
+        }
+    }
+}
+
+impl fmt::Display for Season {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (start, end) = (self.start, self.end);
+        write!(
+            f,
+            "{:02}-{:02}..{:02}-{:02}",
+            start.0, start.1, end.0, end.1
+        )
+    }
+}
+
+impl FromStr for Season {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let invalid = || format!("Invalid season '{}', expected e.g. '03-01..09-30'", s);
+        let (start, end) = s.split_once("..").with_context(invalid)?;
+        let month_day = |md: &str| -> anyhow::Result<(u32, u32)> {
+            let (month, day) = md.trim().split_once('-').with_context(invalid)?;
+            Ok((
+                month.parse().with_context(invalid)?,
+                day.parse().with_context(invalid)?,
+            ))
+        };
+        Ok(Season {
+            start: month_day(start)?,
+            end: month_day(end)?,
+        })
+    }
+}
+
+impl TryFrom<String> for Season {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+/// A calendar that doesn't look like the real thing, returned as the scrape's error
+#[derive(Debug)]
+pub struct Suspicious {
+    pub reason: String,
+}
+
+impl fmt::Display for Suspicious {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Suspicious calendar: {}", self.reason)
+    }
+}
+
+impl std::error::Error for Suspicious {}
+
+/// What the last believed calendar of one target looked like
+#[derive(Default)]
+struct Seen {
+    entries: usize,
+    open: usize,
+    /// The reason the last calendar was flagged, a repeat is accepted
+    flagged: Option<String>,
+}
+
+/// Remembers each target's last calendar to judge the next one against
+pub struct Detector {
+    config: AnomalyConfig,
+    seen: Mutex<HashMap<String, Seen>>,
+}
+
+impl Detector {
+    pub fn new(config: AnomalyConfig) -> Self {
+        Detector {
+            config,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A reloaded config, keeping what was seen so far
+    pub fn reconfigure(&mut self, config: AnomalyConfig) {
+        self.config = config;
+    }
+
+    /// Errors with `Suspicious` if `days`, the whole calendar of `label`, looks wrong.
+    /// `seasonal` applies the season check, which only PCTA calendars have.
+    pub fn check(
+        &self,
+        label: &str,
+        days: &[(NaiveDate, u64)],
+        seasonal: bool,
+    ) -> Result<(), Suspicious> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let mut seen = self.seen.lock().unwrap();
+        let last = seen.entry(label.to_string()).or_default();
+        let open = days.iter().filter(|(_, remaining)| *remaining > 0).count();
+
+        let reason = if (days.len() as f64) < last.entries as f64 * self.config.min_fraction {
+            Some(format!(
+                "{} calendar entries, down from {}",
+                days.len(),
+                last.entries
+            ))
+        } else if !days.is_empty() && open == 0 && last.open > 1 {
+            Some(format!(
+                "every date is at zero, {} were open on the last scrape",
+                last.open
+            ))
+        } else {
+            days.iter()
+                .find(|(date, _)| seasonal && !self.config.season.contains(*date))
+                .map(|(date, _)| format!("{} is outside the permit season", date))
+        };
+
+        match reason {
+            Some(reason) if last.flagged.as_ref() != Some(&reason) => {
+                last.flagged = Some(reason.clone());
+                Err(Suspicious { reason })
+            }
+            // Clean, or the same oddity twice in a row which makes it the new normal
+            _ => {
+                *last = Seen {
+                    entries: days.len(),
+                    open,
+                    flagged: None,
+                };
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn days(n: u32, remaining: u64) -> Vec<(NaiveDate, u64)> {
+        (1..=n)
+            .map(|d| (NaiveDate::from_ymd_opt(2023, 4, d).unwrap(), remaining))
+            .collect()
+    }
+
+    #[test]
+    fn shrunk_calendar_is_flagged_once() {
+        let detector = Detector::new(AnomalyConfig::default());
+        assert!(detector.check("x", &days(30, 5), true).is_ok());
+        assert!(detector.check("x", &days(10, 5), true).is_err());
+        // Still that small a tick later, so it is real
+        assert!(detector.check("x", &days(10, 5), true).is_ok());
+        // Other targets have their own baseline
+        assert!(detector.check("y", &days(10, 5), true).is_ok());
+    }
+
+    #[test]
+    fn everything_at_zero_at_once_is_flagged() {
+        let detector = Detector::new(AnomalyConfig::default());
+        assert!(detector.check("x", &days(30, 5), true).is_ok());
+        let err = detector.check("x", &days(30, 0), true).unwrap_err();
+        assert!(err.reason.contains("every date is at zero"));
+    }
+
+    #[test]
+    fn dates_out_of_season_are_flagged_for_pcta_only() {
+        let detector = Detector::new(AnomalyConfig::default());
+        let december = vec![(NaiveDate::from_ymd_opt(2023, 12, 1).unwrap(), 3)];
+        assert!(detector.check("x", &december, true).is_err());
+        assert!(detector.check("y", &december, false).is_ok());
+        assert_eq!(
+            "03-01..09-30".parse::<Season>().unwrap(),
+            AnomalyConfig::default().season
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Polls fast around a known release time, e.g. the second-round release at 10:30 AM PT
+///
+/// ```toml
+/// [[schedule.boost]]
+/// # sec min hour day-of-month month day-of-week, in the business hours' timezone
+/// release = "0 30 10 * * Mon-Fri"
+/// lead_secs = 300
+/// hold_secs = 1800
+/// ramp_secs = 1800
+/// interval_secs = 5
+/// ```
+///
+/// From `lead_secs` before each release until `hold_secs` after it the scraper polls every
+/// `interval_secs`, then eases back to its normal interval over `ramp_secs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Boost {
+    pub release: Cron,
+    #[serde(default = "Boost::default_lead")]
+    pub lead_secs: u64,
+    #[serde(default = "Boost::default_hold")]
+    pub hold_secs: u64,
+    #[serde(default = "Boost::default_ramp")]
+    pub ramp_secs: u64,
+    #[serde(default = "Boost::default_interval")]
+    pub interval_secs: u64,
+}
+
+/// A cron expression with seconds (`sec min hour day-of-month month day-of-week`), checked
+/// when the config is read
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cron(Schedule);
+
+impl Cron {
+    /// First time the expression fires after `now`, read as wall-clock time in `tz`
+    pub fn after(&self, now: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        self.0
+            .after(&now.with_timezone(&tz))
+            .next()
+            .map(|at| at.with_timezone(&Utc))
+    }
+}
+
+impl TryFrom<String> for Cron {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        Schedule::from_str(&s)
+            .map(Cron)
+            .with_context(|| format!("Invalid cron expression '{}'", s))
+    }
+}
+
+impl Boost {
+    fn default_lead() -> u64 {
+        300
+    }
+
+    fn default_hold() -> u64 {
+        1800
+    }
+
+    fn default_ramp() -> u64 {
+        1800
+    }
+
+    fn default_interval() -> u64 {
+        5
+    }
+
+    /// Seconds to wait before the next scrape at `now`, `None` when no release is near
+    pub fn interval(&self, now: DateTime<Utc>, tz: Tz, normal: u64) -> Option<u64> {
+        let secs = |s: u64| Duration::seconds(s as i64);
+        let lookback = now - secs(self.hold_secs + self.ramp_secs);
+        let release = self
+            .release
+            .0
+            .after(&lookback.with_timezone(&tz))
+            .take_while(|release| *release <= now + secs(self.lead_secs))
+            .last()?
+            .with_timezone(&Utc);
+
+        let since = (now - release).num_seconds();
+        if since <= self.hold_secs as i64 {
+            return Some(self.interval_secs);
+        }
+        // Linear from the boosted interval back up to the normal one
+        let ramped = (since - self.hold_secs as i64) as f64 / self.ramp_secs.max(1) as f64;
+        let fast = self.interval_secs.min(normal) as f64;
+        Some((fast + (normal as f64 - fast) * ramped).round() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn boosts_around_release_then_ramps_back() {
+        let boost: Boost = toml::from_str(r#"release = "0 30 10 * * *""#).unwrap();
+        let tz = chrono_tz::America::Los_Angeles;
+        // 10:30 PDT is 17:30 UTC
+        let interval = |t: &str| boost.interval(utc(t), tz, 35);
+        assert_eq!(interval("2023-04-17T17:20:00Z"), None);
+        assert_eq!(interval("2023-04-17T17:26:00Z"), Some(5));
+        assert_eq!(interval("2023-04-17T17:59:00Z"), Some(5));
+        assert_eq!(interval("2023-04-17T18:15:00Z"), Some(20));
+        assert_eq!(interval("2023-04-17T18:31:00Z"), None);
+    }
+}
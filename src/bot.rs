@@ -0,0 +1,440 @@
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::Notify;
+
+use crate::notifier::Keybase;
+use crate::state::State;
+use crate::subscription::Subscriber;
+
+/// How many failed scrapes `Control::errors` remembers
+const RECENT_ERRORS: usize = 20;
+
+/// A scrape that failed, for the web UI
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedScrape {
+    pub at: DateTime<Utc>,
+    pub label: String,
+    pub error: String,
+}
+
+/// What the chat commands, the web UI and `pcta ctl` can see and change while the scrape loop
+/// runs. With profiles every loop has its own, and the one given to those sees them combined
+/// and passes commands on to all of them.
+pub struct Control {
+    paused: AtomicBool,
+    /// An HA standby while its primary is alive
+    standby: AtomicBool,
+    subscribers: Mutex<Vec<Subscriber>>,
+    status: Mutex<String>,
+    last: Mutex<Option<State>>,
+    next: Mutex<Option<DateTime<Utc>>>,
+    errors: Mutex<VecDeque<FailedScrape>>,
+    scrape_now: Notify,
+    reload: Notify,
+    /// Each profile's own, by name
+    profiles: Vec<(String, Arc<Control>)>,
+}
+
+impl Control {
+    pub fn new(subscribers: Vec<Subscriber>) -> Arc<Control> {
+        Control::with_profiles(subscribers, &[])
+    }
+
+    /// One for each of `profiles`, all starting out with `subscribers`
+    pub fn with_profiles(subscribers: Vec<Subscriber>, profiles: &[String]) -> Arc<Control> {
+        let profiles = profiles
+            .iter()
+            .map(|name| (name.clone(), Control::new(subscribers.clone())))
+            .collect();
+        Arc::new(Control {
+            paused: AtomicBool::new(false),
+            standby: AtomicBool::new(false),
+            subscribers: Mutex::new(subscribers),
+            status: Mutex::new("No scrape yet".to_string()),
+            last: Mutex::new(None),
+            next: Mutex::new(None),
+            errors: Mutex::new(VecDeque::new()),
+            scrape_now: Notify::new(),
+            reload: Notify::new(),
+            profiles,
+        })
+    }
+
+    /// The loop of profile `name`'s own
+    pub fn profile(&self, name: &str) -> Option<Arc<Control>> {
+        self.profiles
+            .iter()
+            .find(|(profile, _)| profile == name)
+            .map(|(_, control)| control.clone())
+    }
+
+    /// This one and every profile's, for commands
+    fn each(&self) -> impl Iterator<Item = &Control> {
+        std::iter::once(self).chain(self.profiles.iter().map(|(_, control)| control.as_ref()))
+    }
+
+    /// Where the loops report to, to read from
+    fn loops(&self) -> Vec<&Control> {
+        match self.profiles.is_empty() {
+            true => vec![self],
+            false => self.profiles.iter().map(|(_, c)| c.as_ref()).collect(),
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn pause(&self) {
+        for control in self.each() {
+            control.paused.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn resume(&self) {
+        for control in self.each() {
+            control.paused.store(false, Ordering::Relaxed);
+        }
+    }
+
+    pub fn standing_by(&self) -> bool {
+        self.standby.load(Ordering::Relaxed)
+    }
+
+    /// Holds scraping off for as long as another instance is doing it, see `ha`
+    pub fn stand_by(&self, standby: bool) {
+        for control in self.each() {
+            control.standby.store(standby, Ordering::Relaxed);
+        }
+    }
+
+    /// Cuts the scheduler's current wait short
+    pub fn scrape_now(&self) {
+        for control in self.each() {
+            control.scrape_now.notify_one();
+        }
+    }
+
+    /// Resolves once `scrape_now` was called, also if that happened before waiting
+    pub async fn scrape_requested(&self) {
+        self.scrape_now.notified().await;
+    }
+
+    /// Asks the scheduler to read the config file again
+    pub fn reload(&self) {
+        for control in self.each() {
+            control.reload.notify_one();
+        }
+    }
+
+    /// Resolves once `reload` was called, also if that happened before waiting
+    pub async fn reload_requested(&self) {
+        self.reload.notified().await;
+    }
+
+    /// The last pass, with its failures added to the recent errors
+    pub fn scraped(&self, state: State) {
+        let mut errors = self.errors.lock().unwrap();
+        for target in &state.targets {
+            if let Some(error) = &target.error {
+                if errors.len() == RECENT_ERRORS {
+                    errors.pop_front();
+                }
+                errors.push_back(FailedScrape {
+                    at: state.scraped_at.utc,
+                    label: target.label.clone(),
+                    error: error.clone(),
+                });
+            }
+        }
+        *self.last.lock().unwrap() = Some(state);
+    }
+
+    /// Every loop's last pass as one, stamped with the latest
+    pub fn last(&self) -> Option<State> {
+        let mut states = self
+            .loops()
+            .into_iter()
+            .filter_map(|c| c.last.lock().unwrap().clone());
+        let mut last = states.next()?;
+        for state in states {
+            if state.scraped_at.utc > last.scraped_at.utc {
+                last.scraped_at = state.scraped_at;
+            }
+            last.targets.extend(state.targets);
+        }
+        Some(last)
+    }
+
+    /// Newest first
+    pub fn errors(&self) -> Vec<FailedScrape> {
+        let mut errors: Vec<FailedScrape> = self
+            .loops()
+            .into_iter()
+            .flat_map(|c| c.errors.lock().unwrap().clone())
+            .collect();
+        errors.sort_by_key(|e| std::cmp::Reverse(e.at));
+        errors.truncate(RECENT_ERRORS);
+        errors
+    }
+
+    pub fn set_next(&self, at: DateTime<Utc>) {
+        *self.next.lock().unwrap() = Some(at);
+    }
+
+    /// The soonest of any loop
+    pub fn next(&self) -> Option<DateTime<Utc>> {
+        self.loops()
+            .into_iter()
+            .filter_map(|c| *c.next.lock().unwrap())
+            .min()
+    }
+
+    pub fn subscribers(&self) -> Vec<Subscriber> {
+        self.subscribers.lock().unwrap().clone()
+    }
+
+    /// One line per profile when there are any
+    pub fn status(&self) -> String {
+        if self.profiles.is_empty() {
+            return self.status.lock().unwrap().clone();
+        }
+        self.profiles
+            .iter()
+            .map(|(name, control)| format!("`{}`: {}", name, control.status()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Replaces what `!status` reports, called by the scheduler after every tick
+    pub fn set_status(&self, status: String) {
+        *self.status.lock().unwrap() = status;
+    }
+
+    /// Runs one command from `user` and returns the reply
+    pub fn apply(&self, user: &str, command: BotCommand) -> String {
+        match command {
+            BotCommand::Status => {
+                let state = match (self.paused(), self.standing_by()) {
+                    (true, _) => "paused",
+                    (false, true) => "standing by",
+                    (false, false) => "running",
+                };
+                let sep = match self.profiles.is_empty() {
+                    true => " ",
+                    false => "\n",
+                };
+                format!("Scraper is {}.{}{}", state, sep, self.status())
+            }
+            BotCommand::Watch(start, end) => {
+                for control in self.each() {
+                    control.subscribers.lock().unwrap().push(Subscriber {
+                        user: user.to_string(),
+                        start: Some(start),
+                        end: Some(end),
+                    });
+                }
+                format!("@{} is now watching `{}` to `{}`", user, start, end)
+            }
+            BotCommand::Unwatch => {
+                for control in self.each() {
+                    control
+                        .subscribers
+                        .lock()
+                        .unwrap()
+                        .retain(|s| s.user != user);
+                }
+                format!("@{} is no longer watching any dates", user)
+            }
+            BotCommand::Pause => {
+                self.pause();
+                "Paused, `!resume` to start scraping again".to_string()
+            }
+            BotCommand::Resume => {
+                self.resume();
+                "Resumed scraping".to_string()
+            }
+            BotCommand::Help => HELP.to_string(),
+        }
+    }
+}
+
+const HELP: &str = "`!status`, `!watch 2024-04-12..2024-04-20`, `!unwatch`, `!pause`, `!resume`";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BotCommand {
+    Status,
+    /// Subscribe the sender to an inclusive range
+    Watch(NaiveDate, NaiveDate),
+    /// Drop all of the sender's subscriptions
+    Unwatch,
+    Pause,
+    Resume,
+    Help,
+}
+
+impl BotCommand {
+    /// `None` for chat that isn't meant for us, an error for a malformed command
+    pub fn parse(text: &str) -> Option<anyhow::Result<BotCommand>> {
+        let text = text.trim().strip_prefix('!')?;
+        let (name, arg) = text.split_once(' ').unwrap_or((text, ""));
+        let command = match name {
+            "status" => Ok(BotCommand::Status),
+            "watch" => parse_range(arg.trim()).map(|(start, end)| BotCommand::Watch(start, end)),
+            "unwatch" => Ok(BotCommand::Unwatch),
+            "pause" => Ok(BotCommand::Pause),
+            "resume" => Ok(BotCommand::Resume),
+            "help" => Ok(BotCommand::Help),
+            _ => Err(anyhow::anyhow!("Unknown command `!{}`, try {}", name, HELP)),
+        };
+        Some(command)
+    }
+}
+
+fn parse_range(arg: &str) -> anyhow::Result<(NaiveDate, NaiveDate)> {
+    let (start, end) = arg
+        .split_once("..")
+        .context("Expected a range like `2024-04-12..2024-04-20`")?;
+    let parse = |s: &str| {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").with_context(|| format!("Invalid date `{}`", s))
+    };
+    let (start, end) = (parse(start)?, parse(end)?);
+    if start > end {
+        anyhow::bail!("`{}` is after `{}`", start, end);
+    }
+    Ok((start, end))
+}
+
+/// One line of `keybase chat api-listen`, only the parts we read
+#[derive(Deserialize)]
+struct Event {
+    #[serde(rename = "type")]
+    kind: String,
+    msg: Option<EventMessage>,
+}
+
+#[derive(Deserialize)]
+struct EventMessage {
+    channel: EventChannel,
+    sender: Sender,
+    content: Content,
+}
+
+#[derive(Deserialize)]
+struct EventChannel {
+    name: String,
+    #[serde(default)]
+    topic_name: String,
+}
+
+#[derive(Deserialize)]
+struct Sender {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct Content {
+    text: Option<Text>,
+}
+
+#[derive(Deserialize)]
+struct Text {
+    body: String,
+}
+
+/// Listens on the team's channels and answers `!commands` in the topic they were sent to.
+/// Returns when the listener process exits.
+pub async fn listen(team: &str, keybase: Keybase, control: Arc<Control>) -> anyhow::Result<()> {
+    let mut child = crate::exec::command("keybase")
+        .arg("chat")
+        .arg("api-listen")
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to start `keybase chat api-listen`")?;
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let Ok(event) = serde_json::from_str::<Event>(&line) else {
+            continue;
+        };
+        let Some(msg) = event.msg.filter(|_| event.kind == "chat") else {
+            continue;
+        };
+        let Some(text) = msg.content.text else {
+            continue;
+        };
+        if msg.channel.name != team {
+            continue;
+        }
+        let reply = match BotCommand::parse(&text.body) {
+            None => continue,
+            Some(Ok(command)) => control.apply(&msg.sender.username, command),
+            Some(Err(e)) => format!("{:#}", e),
+        };
+        crate::info!("Bot: {} -> {}", text.body, reply);
+        keybase.send(&msg.channel.topic_name, reply).await;
+    }
+
+    let status = child.wait().await?;
+    anyhow::bail!("`keybase chat api-listen` exited with {}", status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            BotCommand::parse("!watch 2024-04-12..2024-04-20")
+                .unwrap()
+                .unwrap(),
+            BotCommand::Watch(date("2024-04-12"), date("2024-04-20"))
+        );
+        assert_eq!(
+            BotCommand::parse(" !pause ").unwrap().unwrap(),
+            BotCommand::Pause
+        );
+        assert!(BotCommand::parse("!watch 2024-04-20..2024-04-12")
+            .unwrap()
+            .is_err());
+        assert!(BotCommand::parse("!launch").unwrap().is_err());
+        assert!(BotCommand::parse("anyone got a permit?").is_none());
+    }
+
+    #[test]
+    fn watch_subscribes_the_sender() {
+        let control = Control::new(vec![]);
+        control.apply(
+            "hiker",
+            BotCommand::Watch(date("2024-04-12"), date("2024-04-20")),
+        );
+        assert!(control.subscribers()[0].wants(date("2024-04-20")));
+        control.apply("hiker", BotCommand::Unwatch);
+        assert!(control.subscribers().is_empty());
+    }
+
+    #[test]
+    fn commands_reach_every_profile() {
+        let control = Control::with_profiles(vec![], &["sobo".to_string(), "nobo".to_string()]);
+        let sobo = control.profile("sobo").unwrap();
+        sobo.set_status("Last scrape `now`".to_string());
+        control.apply("ctl", BotCommand::Pause);
+        assert!(sobo.paused() && control.profile("nobo").unwrap().paused());
+        assert_eq!(
+            control.apply("ctl", BotCommand::Status),
+            "Scraper is paused.\n`sobo`: Last scrape `now`\n`nobo`: No scrape yet"
+        );
+    }
+}
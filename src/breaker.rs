@@ -0,0 +1,163 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+/// ```toml
+/// [breaker]
+/// enabled = true
+/// threshold = 3
+/// cooldown_secs = 3600
+/// ```
+///
+/// After `threshold` passes in a row that got blocked (403s, CAPTCHA pages) scraping stops for
+/// `cooldown_secs`. Then the identity is rotated and a single target is scraped as a probe, and
+/// only if that gets through does the full cadence resume.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BreakerConfig {
+    pub enabled: bool,
+    pub threshold: u32,
+    pub cooldown_secs: u64,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        BreakerConfig {
+            enabled: true,
+            threshold: 3,
+            cooldown_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Scraping as usual, counting blocked passes in a row
+    Closed { blocked: u32 },
+    /// Cooling down, nothing is scraped until then
+    Open { until: DateTime<Utc> },
+    /// The cool-down is over, the next pass is a probe
+    HalfOpen,
+}
+
+/// How much of the next pass may go ahead
+#[derive(Debug, PartialEq, Eq)]
+pub enum Admit {
+    All,
+    /// One request to see whether the block has lifted, on a fresh identity
+    Probe,
+    /// Cooling down until then
+    No(DateTime<Utc>),
+}
+
+/// Closed/Open/Half-open state machine around the scrape, see `BreakerConfig`
+pub struct Breaker {
+    config: BreakerConfig,
+    state: State,
+}
+
+impl Breaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Breaker {
+            config,
+            state: State::Closed { blocked: 0 },
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn admit(&mut self, now: DateTime<Utc>) -> Admit {
+        match self.state {
+            State::Closed { .. } => Admit::All,
+            State::Open { until } if now < until => Admit::No(until),
+            State::Open { .. } | State::HalfOpen => {
+                self.state = State::HalfOpen;
+                Admit::Probe
+            }
+        }
+    }
+
+    /// Records how a pass went, with a message to post when the breaker trips or resets
+    pub fn record(&mut self, blocked: bool, now: DateTime<Utc>) -> Option<String> {
+        if !self.config.enabled {
+            return None;
+        }
+        let cooldown = Duration::seconds(self.config.cooldown_secs as i64);
+        match (self.state, blocked) {
+            (State::Closed { blocked: n }, true) if n + 1 >= self.config.threshold => {
+                self.state = State::Open {
+                    until: now + cooldown,
+                };
+                Some(format!(
+                    "*Blocked {} passes in a row*, cooling down for {}m",
+                    n + 1,
+                    cooldown.num_minutes()
+                ))
+            }
+            (State::Closed { blocked: n }, true) => {
+                self.state = State::Closed { blocked: n + 1 };
+                None
+            }
+            (State::Closed { .. }, false) => {
+                self.state = State::Closed { blocked: 0 };
+                None
+            }
+            (State::HalfOpen, true) => {
+                self.state = State::Open {
+                    until: now + cooldown,
+                };
+                Some(format!(
+                    "*Probe was blocked too*, cooling down for another {}m",
+                    cooldown.num_minutes()
+                ))
+            }
+            (State::HalfOpen, false) => {
+                self.state = State::Closed { blocked: 0 };
+                Some("*Probe got through*, back to scraping everything".to_string())
+            }
+            // Nothing was scraped while open
+            (State::Open { .. }, _) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        "2023-04-17T17:00:00Z".parse::<DateTime<Utc>>().unwrap() + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn trips_after_blocks_in_a_row_and_probes_after_the_cooldown() {
+        let mut breaker = Breaker::new(BreakerConfig::default());
+        assert!(breaker.record(true, at(0)).is_none());
+        assert!(breaker.record(false, at(1)).is_none());
+        assert!(breaker.record(true, at(2)).is_none());
+        assert!(breaker.record(true, at(3)).is_none());
+        assert!(breaker.record(true, at(4)).is_some());
+        assert_eq!(breaker.admit(at(30)), Admit::No(at(64)));
+
+        assert_eq!(breaker.admit(at(64)), Admit::Probe);
+        assert!(breaker.record(true, at(65)).is_some());
+        assert_eq!(breaker.state(), State::Open { until: at(125) });
+
+        assert_eq!(breaker.admit(at(130)), Admit::Probe);
+        assert!(breaker.record(false, at(131)).is_some());
+        assert_eq!(breaker.admit(at(132)), Admit::All);
+    }
+
+    #[test]
+    fn disabled_never_trips() {
+        let mut breaker = Breaker::new(BreakerConfig {
+            enabled: false,
+            ..BreakerConfig::default()
+        });
+        for minute in 0..10 {
+            assert!(breaker.record(true, at(minute)).is_none());
+        }
+        assert_eq!(breaker.admit(at(10)), Admit::All);
+    }
+}
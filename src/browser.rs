@@ -0,0 +1,100 @@
+use anyhow::bail;
+#[cfg(feature = "browser")]
+use anyhow::Context;
+#[cfg(feature = "browser")]
+use std::{io, time::Duration};
+
+/// Chromium gets this long to load and settle the page before we give up on it
+#[cfg(feature = "browser")]
+const PAGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Virtual time the page's scripts and requests get to run before the DOM is dumped. Chromium
+/// dumps earlier once the page is idle.
+#[cfg(feature = "browser")]
+const SCRIPT_BUDGET: Duration = Duration::from_secs(10);
+
+/// Loads `url` in headless Chromium and returns the DOM after the page's scripts ran. The
+/// `var data` <script> survives serialization, so the result parses like a plain HTTP body.
+///
+/// This runs the `chromium --headless --dump-dom` CLI rather than driving the browser over
+/// DevTools (chromiumoxide) or WebDriver (fantoccini). One page load per scrape needs no
+/// session, and those crates would pull in a second async HTTP/WebSocket stack. The costs:
+/// scripts get a fixed budget instead of waiting on a selector, and a hung Chromium is only
+/// killed once twice `PAGE_TIMEOUT` has passed.
+#[cfg(feature = "browser")]
+pub async fn fetch(
+    binary: &str,
+    url: &str,
+    user_agent: &str,
+    proxy: Option<&str>,
+) -> anyhow::Result<String> {
+    let mut chromium = crate::exec::command(binary);
+    chromium
+        .arg("--headless")
+        .arg("--disable-gpu")
+        .arg("--no-first-run")
+        .arg(format!("--user-agent={}", user_agent))
+        .arg(format!("--timeout={}", PAGE_TIMEOUT.as_millis()))
+        .arg(format!(
+            "--virtual-time-budget={}",
+            SCRIPT_BUDGET.as_millis()
+        ));
+    if let Some(proxy) = proxy {
+        chromium.arg(format!("--proxy-server={}", proxy));
+    }
+    chromium.arg("--dump-dom").arg(url).kill_on_drop(true);
+
+    let output = match tokio::time::timeout(PAGE_TIMEOUT * 2, chromium.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) if e.kind() == io::ErrorKind::NotFound => {
+            bail!(
+                "`{}` not found on PATH. Install Chromium or set `browser.binary`",
+                binary
+            )
+        }
+        Ok(Err(e)) => return Err(e).with_context(|| format!("Failed to call {} (err)", binary)),
+        Err(_) => bail!("Headless {} did not finish loading {} in time", binary, url),
+    };
+
+    if !output.status.success() {
+        bail!(
+            "Headless {} failed with {}, stderr = '{}'",
+            binary,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Built without the `browser` feature only plain HTTP scrapes
+#[cfg(not(feature = "browser"))]
+pub async fn fetch(_: &str, url: &str, _: &str, _: Option<&str>) -> anyhow::Result<String> {
+    bail!(
+        "Can't load {} in a browser, pcta was built without the `browser` feature. \
+         Use `engine = \"http\"` or rebuild with `--features browser`.",
+        url
+    )
+}
+
+#[cfg(all(test, feature = "browser"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn a_missing_chromium_says_so() {
+        let err = fetch(
+            "pcta-test-chromium-not-installed",
+            "http://localhost/",
+            "pcta",
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`pcta-test-chromium-not-installed` not found on PATH. Install Chromium or set \
+             `browser.binary`"
+        );
+    }
+}
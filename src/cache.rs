@@ -0,0 +1,55 @@
+use chrono::NaiveDate;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+/// The last good response per URL: its validators for conditional requests and what was parsed
+/// out of it, so a 304 or a byte-identical body needs no parsing at all
+#[derive(Default)]
+pub struct ResponseCache {
+    entries: Mutex<HashMap<String, Entry>>,
+    /// Keeps nothing, for `[memory] low`
+    off: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body_hash: u64,
+    /// Of the calendar JSON alone, which survives the rest of the page changing
+    pub data_hash: u64,
+    pub days: Vec<(NaiveDate, u64)>,
+}
+
+impl ResponseCache {
+    pub fn off() -> Self {
+        ResponseCache {
+            off: true,
+            ..ResponseCache::default()
+        }
+    }
+
+    pub fn get(&self, url: &str) -> Option<Entry> {
+        self.entries.lock().unwrap().get(url).cloned()
+    }
+
+    pub fn put(&self, url: &str, entry: Entry) {
+        if self.off {
+            return;
+        }
+        self.entries.lock().unwrap().insert(url.to_string(), entry);
+    }
+
+    /// Validators identify us as much as cookies do, so they go together
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+pub fn hash(body: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,165 @@
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use pcta::doctor::Severity;
+use pcta::export::{self, Format};
+use pcta::scraper::{Engine, Simulated, Source};
+use pcta::target::Terminus;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(about = "Watches the PCTA permit portal for open start dates")]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// How the availability page is fetched
+    #[arg(long, global = true, value_enum, default_value_t = Engine::Http)]
+    pub engine: Engine,
+
+    /// Where the calendar data comes from
+    #[arg(long, global = true, value_enum, default_value_t = Source::Html)]
+    pub source: Source,
+
+    /// Only watch this terminus, repeat for several. Defaults to every target in the config.
+    #[arg(long = "target", global = true, value_enum)]
+    pub targets: Vec<Terminus>,
+
+    /// Scrape and parse, but only print the Keybase messages and VPN changes
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Start even if another instance is running the same profiles
+    #[arg(long)]
+    pub force: bool,
+
+    /// Log more, `-vv` for the raw calendar payloads too. Secrets and channel names are
+    /// redacted either way.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Keep every fetched response body in this directory, for `pcta replay`
+    #[arg(long, global = true, value_name = "DIR")]
+    pub save_bodies: Option<PathBuf>,
+
+    /// Read every target's calendar as these days instead of fetching it, `2024-04-15=3` for
+    /// three permits left, and alert as if it had. Nothing is kept in the state or history files.
+    #[arg(long, global = true, value_name = "DAYS", value_parser = pcta::scraper::parse_simulated)]
+    pub simulate: Option<Simulated>,
+
+    /// Serve a dashboard and JSON API on this address while scraping, `:8080` for localhost only
+    #[cfg(feature = "web")]
+    #[arg(long, global = true, value_name = "ADDR", value_parser = pcta::web::parse_addr)]
+    pub web: Option<std::net::SocketAddr>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Scrape every target once and exit: 0 when nothing is open, 10 when a watch alerted, 1 on
+    /// errors. For cron or systemd timers.
+    Once,
+    /// Dump the per-date counts of every calendar change in the history file
+    Export {
+        #[arg(long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+        /// Only changes from then on, `2023-04-17` or RFC 3339
+        #[arg(long, value_parser = export::parse_time)]
+        from: Option<DateTime<Utc>>,
+        /// Only changes before then
+        #[arg(long, value_parser = export::parse_time)]
+        to: Option<DateTime<Utc>>,
+        /// Defaults to stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Run bodies saved with `--save-bodies` through parsing and alerting again, printing what
+    /// would have been sent
+    Replay { dir: PathBuf },
+    /// Check the config, VPN, keybase, every target's page and every notifier, sending each a
+    /// test message, and report what passed. Exits 1 when anything failed.
+    Doctor,
+    /// Send a made-up message through the templates to Keybase and every notifier, to check
+    /// where each kind of message lands and how it looks
+    NotifyTest {
+        #[arg(long, value_enum, default_value_t = Severity::Alert)]
+        severity: Severity,
+    },
+    /// Control the running scraper over its socket
+    Ctl {
+        #[command(subcommand)]
+        command: CtlCommand,
+    },
+    /// Write a systemd user unit running the scraper with these options from this directory
+    InstallSystemd {
+        /// Defaults to `~/.config/systemd/user/pcta.service`
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Replace an existing unit file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Subcommand)]
+pub enum CtlCommand {
+    /// Stop scraping until resumed
+    Pause,
+    Resume,
+    /// Cut the wait for the next scrape short
+    ScrapeNow,
+    /// Whether it is paused and how the last scrape went
+    Status,
+}
+
+impl CtlCommand {
+    /// What the socket expects
+    pub fn request(&self) -> &'static str {
+        match self {
+            CtlCommand::Pause => "pause",
+            CtlCommand::Resume => "resume",
+            CtlCommand::ScrapeNow => "scrape-now",
+            CtlCommand::Status => "status",
+        }
+    }
+}
+
+impl Args {
+    /// The scraping options as flags again, for the unit's `ExecStart`
+    pub fn flags(&self) -> String {
+        let mut flags = format!(
+            "--engine {} --source {}",
+            name(&self.engine),
+            name(&self.source)
+        );
+        for target in &self.targets {
+            flags += &format!(" --target {}", name(target));
+        }
+        if self.dry_run {
+            flags += " --dry-run";
+        }
+        if self.verbose > 0 {
+            flags += &format!(" -{}", "v".repeat(self.verbose as usize));
+        }
+        if let Some(dir) = &self.save_bodies {
+            flags += &format!(" --save-bodies {}", dir.display());
+        }
+        if let Some(Simulated(days)) = &self.simulate {
+            let days: Vec<String> = days
+                .iter()
+                .map(|(date, left)| format!("{}={}", date, left))
+                .collect();
+            flags += &format!(" --simulate {}", days.join(","));
+        }
+        #[cfg(feature = "web")]
+        if let Some(addr) = self.web {
+            flags += &format!(" --web {}", addr);
+        }
+        flags
+    }
+}
+
+fn name<T: ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default()
+}
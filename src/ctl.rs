@@ -0,0 +1,162 @@
+use anyhow::Context;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::bot::{BotCommand, Control};
+
+/// ```toml
+/// [control]
+/// socket = "pcta.sock"
+/// ```
+///
+/// `pcta ctl` talks to the running scraper over this Unix socket, `""` to not listen. Only
+/// the owner may connect. Windows has no such socket, so it doesn't listen by default there.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlConfig {
+    pub socket: PathBuf,
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        ControlConfig {
+            socket: PathBuf::from(if cfg!(unix) { "pcta.sock" } else { "" }),
+        }
+    }
+}
+
+impl ControlConfig {
+    pub fn socket(&self) -> Option<&Path> {
+        Some(self.socket.as_path()).filter(|p| !p.as_os_str().is_empty())
+    }
+}
+
+/// Runs one request line from `pcta ctl` and returns the reply
+#[cfg_attr(not(unix), allow(dead_code))]
+fn apply(control: &Control, line: &str) -> String {
+    match line.trim() {
+        "pause" => {
+            control.pause();
+            "Paused, `pcta ctl resume` to start scraping again".to_string()
+        }
+        "resume" => {
+            control.resume();
+            "Resumed scraping".to_string()
+        }
+        "status" => control.apply("ctl", BotCommand::Status),
+        "scrape-now" => {
+            control.scrape_now();
+            "Scraping now".to_string()
+        }
+        other => format!("Unknown command `{}`", other),
+    }
+}
+
+/// Answers `pcta ctl` on `path` until listening fails. A socket file left behind by an
+/// earlier run is replaced.
+#[cfg(unix)]
+pub async fn serve(path: &Path, control: Arc<Control>) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::UnixListener;
+
+    if path.exists() {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket '{}'", path.display()))?;
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to listen on '{}'", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let control = control.clone();
+        tokio::spawn(async move {
+            if let Err(e) = answer(stream, &control).await {
+                crate::info!("Control connection failed: {:#}", e);
+            }
+        });
+    }
+}
+
+#[cfg(unix)]
+async fn answer(stream: tokio::net::UnixStream, control: &Control) -> anyhow::Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (read, mut write) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(read).read_line(&mut line).await?;
+    let reply = apply(control, &line);
+    crate::info!("Control: {} -> {}", line.trim(), reply);
+    write.write_all(reply.as_bytes()).await?;
+    write.write_all(b"\n").await?;
+    Ok(())
+}
+
+/// Sends `command` to the scraper listening on `path`, returning its reply
+#[cfg(unix)]
+pub async fn send(path: &Path, command: &str) -> anyhow::Result<String> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::UnixStream::connect(path)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to connect to '{}', is the scraper running?",
+                path.display()
+            )
+        })?;
+    stream
+        .write_all(format!("{}\n", command).as_bytes())
+        .await?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).await?;
+    Ok(reply.trim_end().to_string())
+}
+
+#[cfg(not(unix))]
+pub async fn serve(path: &Path, _: Arc<Control>) -> anyhow::Result<()> {
+    anyhow::bail!(
+        "Can't listen on '{}', the control socket needs Unix",
+        path.display()
+    )
+}
+
+#[cfg(not(unix))]
+pub async fn send(path: &Path, _: &str) -> anyhow::Result<String> {
+    anyhow::bail!(
+        "Can't connect to '{}', the control socket needs Unix. Use the bot or the web UI.",
+        path.display()
+    )
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn ctl_pauses_the_running_scraper() {
+        let path = std::env::temp_dir().join(format!("pcta-ctl-{}.sock", std::process::id()));
+        let control = Control::new(vec![]);
+        let server = {
+            let (path, control) = (path.clone(), control.clone());
+            tokio::spawn(async move { serve(&path, control).await })
+        };
+        // Wait for the socket to show up
+        while !path.exists() {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(
+            send(&path, "pause").await.unwrap(),
+            "Paused, `pcta ctl resume` to start scraping again"
+        );
+        assert!(control.paused());
+        assert!(send(&path, "status")
+            .await
+            .unwrap()
+            .starts_with("Scraper is paused"));
+        assert!(send(&path, "launch").await.unwrap().starts_with("Unknown"));
+        server.abort();
+        let _ = std::fs::remove_file(&path);
+    }
+}
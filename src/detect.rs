@@ -0,0 +1,95 @@
+use reqwest::StatusCode;
+use std::fmt;
+
+/// Markers found in Cloudflare interstitials. Any of these means we got the challenge page
+/// instead of the availability calendar.
+const CLOUDFLARE_MARKERS: &[&str] = &[
+    "cf-chl-",
+    "challenge-platform",
+    "cf-browser-verification",
+    "<title>just a moment...</title>",
+    "attention required! | cloudflare",
+];
+
+/// Markers left behind by the usual CAPTCHA widgets.
+const CAPTCHA_MARKERS: &[&str] = &[
+    "g-recaptcha",
+    "h-captcha",
+    "hcaptcha.com",
+    "recaptcha/api.js",
+];
+
+/// Why we think the portal is refusing to serve us
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    /// HTTP 429
+    RateLimited,
+    /// HTTP 403 without any more specific marker in the body
+    Forbidden,
+    /// Cloudflare challenge / interstitial page
+    Cloudflare,
+    /// A CAPTCHA widget on the page
+    Captcha,
+    /// The page came back but the calendar <script> was missing and nothing else matched
+    Unrecognized,
+    /// A body bigger than any calendar, a block page or a tarpit
+    Oversized,
+    /// Something other than the HTML or JSON asked for
+    ContentType,
+}
+
+impl fmt::Display for BlockKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            BlockKind::RateLimited => "rate limited (429)",
+            BlockKind::Forbidden => "forbidden (403)",
+            BlockKind::Cloudflare => "Cloudflare challenge",
+            BlockKind::Captcha => "CAPTCHA",
+            BlockKind::Unrecognized => "unrecognized page",
+            BlockKind::Oversized => "oversized response",
+            BlockKind::ContentType => "unexpected content type",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The portal answered, but not with the availability page. Usually an IP block or a CAPTCHA.
+#[derive(Debug)]
+pub struct Blocked {
+    pub kind: BlockKind,
+    pub detail: String,
+}
+
+impl Blocked {
+    pub fn new(kind: BlockKind, detail: impl Into<String>) -> Self {
+        Blocked {
+            kind,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Blocked ({}): {}", self.kind, self.detail)
+    }
+}
+
+impl std::error::Error for Blocked {}
+
+/// Inspects a response before we try to parse it. Body markers win over the status code since
+/// Cloudflare serves its challenge with 403 and 503 alike.
+pub fn detect(status: StatusCode, body: &str) -> Option<BlockKind> {
+    let lower = body.to_lowercase();
+    if CLOUDFLARE_MARKERS.iter().any(|m| lower.contains(m)) {
+        return Some(BlockKind::Cloudflare);
+    }
+    if CAPTCHA_MARKERS.iter().any(|m| lower.contains(m)) {
+        return Some(BlockKind::Captcha);
+    }
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => Some(BlockKind::RateLimited),
+        StatusCode::FORBIDDEN => Some(BlockKind::Forbidden),
+        _ => None,
+    }
+}
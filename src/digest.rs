@@ -0,0 +1,194 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::boost::Cron;
+use crate::timing::Timings;
+
+/// ```toml
+/// [digest]
+/// # sec min hour day-of-month month day-of-week, in the business hours' timezone
+/// at = "0 0 8 * * *"
+/// channel = "pcta-logs"
+/// ```
+///
+/// Once a day, everything the per-tick logs would have said in one message: scrapes, errors,
+/// availability events, what every watch has open right now and the uptime. Off unless `at` is
+/// set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DigestConfig {
+    pub at: Option<Cron>,
+    pub channel: String,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        DigestConfig {
+            at: None,
+            channel: "pcta-logs".to_string(),
+        }
+    }
+}
+
+/// Tallies since the last digest, and when the next one is due
+pub struct Digest {
+    at: Option<Cron>,
+    tz: Tz,
+    next: Option<DateTime<Utc>>,
+    scrapes: u64,
+    errors: u64,
+    /// Open dates that were alerted on
+    events: u64,
+    /// Requests, their summed and their slowest time to first byte
+    requests: u64,
+    ttfb_ms: u64,
+    slowest_ms: u64,
+    /// Each watch's open dates as of its last scrape
+    open: BTreeMap<String, Vec<(NaiveDate, u64)>>,
+}
+
+impl Digest {
+    pub fn new(config: &DigestConfig, tz: Tz, now: DateTime<Utc>) -> Self {
+        Digest {
+            next: config.at.as_ref().and_then(|at| at.after(now, tz)),
+            at: config.at.clone(),
+            tz,
+            scrapes: 0,
+            errors: 0,
+            events: 0,
+            requests: 0,
+            ttfb_ms: 0,
+            slowest_ms: 0,
+            open: BTreeMap::new(),
+        }
+    }
+
+    pub fn scraped(&mut self, failed: bool) {
+        self.scrapes += 1;
+        self.errors += failed as u64;
+    }
+
+    pub fn timed(&mut self, timings: &Timings) {
+        self.requests += timings.requests as u64;
+        self.ttfb_ms += timings.ttfb_ms;
+        self.slowest_ms = self.slowest_ms.max(timings.ttfb_avg_ms());
+    }
+
+    pub fn alerted(&mut self, dates: usize) {
+        self.events += dates as u64;
+    }
+
+    pub fn watch(&mut self, name: &str, open: Vec<(NaiveDate, u64)>) {
+        self.open.insert(name.to_string(), open);
+    }
+
+    /// The digest text once `now` reaches the configured time, which starts the tallies over.
+    /// The open dates carry on, they are the current state rather than a tally.
+    pub fn take(&mut self, now: DateTime<Utc>, display: &str, uptime: Duration) -> Option<String> {
+        if self.next.is_none_or(|next| now < next) {
+            return None;
+        }
+        self.next = self.at.as_ref().and_then(|at| at.after(now, self.tz));
+
+        let secs = uptime.as_secs();
+        let mut lines = vec![
+            format!("*Daily digest* `{}`\n", display),
+            format!("* Scrapes: {} ({} failed)", self.scrapes, self.errors),
+            format!("* Availability events: {}", self.events),
+            format!(
+                "* Time to first byte, connecting included: {}ms average, {}ms slowest scrape",
+                self.ttfb_ms / self.requests.max(1),
+                self.slowest_ms
+            ),
+            format!(
+                "* Uptime: {}d {}h {}m",
+                secs / 86400,
+                secs / 3600 % 24,
+                secs / 60 % 60
+            ),
+        ];
+        for (watch, open) in &self.open {
+            match open.is_empty() {
+                true => lines.push(format!("\n*{}*: nothing open", watch)),
+                false => {
+                    lines.push(format!("\n*{}*", watch));
+                    lines.extend(
+                        open.iter()
+                            .map(|(date, remaining)| format!("* `{}`: {}", date, remaining)),
+                    );
+                }
+            }
+        }
+        self.scrapes = 0;
+        self.errors = 0;
+        self.events = 0;
+        self.requests = 0;
+        self.ttfb_ms = 0;
+        self.slowest_ms = 0;
+        Some(lines.join("\n") + "\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn goes_out_daily_and_starts_over() {
+        let config: DigestConfig = toml::from_str(r#"at = "0 0 8 * * *""#).unwrap();
+        let mut digest = Digest::new(&config, chrono_tz::UTC, utc("2023-04-17T07:00:00Z"));
+        digest.scraped(false);
+        digest.scraped(true);
+        digest.alerted(2);
+        digest.timed(&Timings {
+            requests: 2,
+            ttfb_ms: 900,
+            ..Timings::default()
+        });
+        let date = NaiveDate::from_ymd_opt(2023, 4, 14).unwrap();
+        digest.watch("April", vec![(date, 12)]);
+        digest.watch("May", vec![]);
+        let uptime = Duration::from_secs(90_000);
+        assert!(digest
+            .take(utc("2023-04-17T07:59:59Z"), "now", uptime)
+            .is_none());
+
+        let msg = digest
+            .take(utc("2023-04-17T08:00:10Z"), "now", uptime)
+            .unwrap();
+        assert!(msg.contains("Scrapes: 2 (1 failed)"));
+        assert!(msg.contains("Availability events: 2"));
+        assert!(msg.contains("connecting included: 450ms average, 450ms slowest"));
+        assert!(msg.contains("Uptime: 1d 1h 0m"));
+        assert!(msg.contains("*April*\n* `2023-04-14`: 12"));
+        assert!(msg.contains("*May*: nothing open"));
+
+        assert!(digest
+            .take(utc("2023-04-18T07:00:00Z"), "now", uptime)
+            .is_none());
+        let msg = digest
+            .take(utc("2023-04-18T08:00:00Z"), "now", uptime)
+            .unwrap();
+        assert!(msg.contains("Scrapes: 0 (0 failed)"));
+        assert!(msg.contains("`2023-04-14`: 12"));
+    }
+
+    #[test]
+    fn off_without_a_time() {
+        let mut digest = Digest::new(
+            &DigestConfig::default(),
+            chrono_tz::UTC,
+            utc("2023-04-17T07:00:00Z"),
+        );
+        assert!(digest
+            .take(utc("2023-05-17T07:00:00Z"), "now", Duration::ZERO)
+            .is_none());
+    }
+}
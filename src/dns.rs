@@ -0,0 +1,282 @@
+use anyhow::Context;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::config::Config;
+use crate::target::{SourceConfig, Target};
+use crate::timing;
+
+/// ```toml
+/// [dns]
+/// resolver = "doh:https://1.1.1.1/dns-query"
+///
+/// [[targets]]
+/// terminus = "mexican-border"
+/// dns = "pin:104.21.3.7"
+/// ```
+///
+/// How the permit sites' hostnames are looked up: `system`, over HTTPS with `doh:<url>`, or
+/// pinned to fixed addresses with `pin:<ip>,<ip>`, so an ISP resolver tampering with the answers
+/// doesn't matter. A target's `dns` applies to its site's hosts. SOCKS5 proxies resolve on
+/// their end, this only changes direct and HTTP proxy connections.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DnsConfig {
+    pub resolver: Resolver,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Resolver {
+    #[default]
+    System,
+    /// A DNS-over-HTTPS server speaking the `application/dns-json` flavor
+    Doh(Url),
+    Pinned(Vec<IpAddr>),
+}
+
+impl FromStr for Resolver {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if s == "system" {
+            return Ok(Resolver::System);
+        }
+        if let Some(url) = s.strip_prefix("doh:") {
+            let url = Url::parse(url).with_context(|| format!("Invalid DoH URL '{}'", url))?;
+            return Ok(Resolver::Doh(url));
+        }
+        if let Some(ips) = s.strip_prefix("pin:") {
+            let ips = ips
+                .split(',')
+                .map(|ip| {
+                    ip.trim()
+                        .parse()
+                        .with_context(|| format!("Invalid pinned address '{}'", ip))
+                })
+                .collect::<anyhow::Result<_>>()?;
+            return Ok(Resolver::Pinned(ips));
+        }
+        anyhow::bail!(
+            "Invalid resolver '{}', expected 'system', 'doh:<url>' or 'pin:<ip>,<ip>'",
+            s
+        )
+    }
+}
+
+impl TryFrom<String> for Resolver {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resolver::System => write!(f, "system"),
+            Resolver::Doh(url) => write!(f, "doh:{}", url),
+            Resolver::Pinned(ips) => {
+                let ips: Vec<String> = ips.iter().map(|ip| ip.to_string()).collect();
+                write!(f, "pin:{}", ips.join(","))
+            }
+        }
+    }
+}
+
+/// One answer in a `application/dns-json` response
+#[derive(Deserialize)]
+struct Answer {
+    #[serde(rename = "type")]
+    kind: u16,
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Answer", default)]
+    answer: Vec<Answer>,
+}
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+/// Asks `server` for the A and AAAA records of `host`
+pub async fn doh(client: &Client, server: &Url, host: &str) -> anyhow::Result<Vec<IpAddr>> {
+    let mut ips = vec![];
+    for kind in ["A", "AAAA"] {
+        let res: DohResponse = client
+            .get(server.clone())
+            .query(&[("name", host), ("type", kind)])
+            .header("Accept", "application/dns-json")
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .with_context(|| format!("DoH lookup of {} at {} failed", host, server))?
+            .json()
+            .await
+            .with_context(|| format!("Invalid DoH answer from {}", server))?;
+        // CNAMEs come back in the chain too, the addresses are what matters
+        ips.extend(
+            res.answer
+                .iter()
+                .filter(|a| a.kind == TYPE_A || a.kind == TYPE_AAAA)
+                .filter_map(|a| a.data.parse::<IpAddr>().ok()),
+        );
+    }
+    if ips.is_empty() {
+        anyhow::bail!("DoH server {} has no address for {}", server, host);
+    }
+    Ok(ips)
+}
+
+/// Resolves each host the way its target asked for, everything else with the `[dns]` default
+#[derive(Clone)]
+pub struct Router {
+    default: Resolver,
+    hosts: HashMap<String, Resolver>,
+    client: Client,
+}
+
+impl Router {
+    pub fn new(default: Resolver, client: Client) -> Self {
+        Router {
+            default,
+            hosts: HashMap::new(),
+            client,
+        }
+    }
+
+    pub fn host(mut self, host: &str, resolver: Resolver) -> Self {
+        self.hosts.insert(host.to_lowercase(), resolver);
+        self
+    }
+
+    /// Also when everything goes through the system resolver, so every lookup is timed
+    pub fn from_config(config: &Config, client: Client) -> anyhow::Result<Arc<Self>> {
+        let mut router = Router::new(config.dns.resolver.clone(), client);
+        for target in &config.targets {
+            if let Some(resolver) = &target.dns {
+                for host in hosts(target, config)? {
+                    router = router.host(&host, resolver.clone());
+                }
+            }
+        }
+        Ok(Arc::new(router))
+    }
+
+    async fn lookup(&self, host: &str) -> anyhow::Result<Vec<IpAddr>> {
+        let resolver = self
+            .hosts
+            .get(&host.to_lowercase())
+            .unwrap_or(&self.default);
+        match resolver {
+            Resolver::System => Ok(tokio::net::lookup_host((host, 0))
+                .await
+                .with_context(|| format!("Lookup of {} failed", host))?
+                .map(|addr| addr.ip())
+                .collect()),
+            Resolver::Doh(server) => doh(&self.client, server, host).await,
+            Resolver::Pinned(ips) => Ok(ips.clone()),
+        }
+    }
+}
+
+impl Resolve for Router {
+    fn resolve(&self, name: Name) -> Resolving {
+        let router = self.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let ips = router.lookup(name.as_str()).await?;
+            timing::dns(started);
+            // The connector fills the port in
+            let addrs: Addrs = Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// The hosts a target's requests go to
+fn hosts(target: &Target, config: &Config) -> anyhow::Result<Vec<String>> {
+    let urls = match &target.source {
+        SourceConfig::Pcta { api_url, .. } => {
+            vec![config.portal.base_url.as_str(), api_url.as_str()]
+        }
+        SourceConfig::RecreationGov { .. } => vec![config.recreation_gov.base_url.as_str()],
+    };
+    urls.into_iter()
+        .filter(|url| !url.is_empty())
+        .map(|url| {
+            let url = Url::parse(url).with_context(|| format!("Invalid URL '{}'", url))?;
+            url.host_str()
+                .map(str::to_string)
+                .with_context(|| format!("No host in '{}'", url))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn parses_resolvers() {
+        assert_eq!("system".parse::<Resolver>().unwrap(), Resolver::System);
+        let pinned: Resolver = "pin:104.21.3.7, ::1".parse().unwrap();
+        assert_eq!(pinned.to_string(), "pin:104.21.3.7,::1");
+        assert!(matches!(
+            "doh:https://1.1.1.1/dns-query".parse::<Resolver>().unwrap(),
+            Resolver::Doh(_)
+        ));
+        assert!("8.8.8.8".parse::<Resolver>().is_err());
+    }
+
+    #[tokio::test]
+    async fn doh_answers_skip_cnames() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("type", "A"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "Status": 0,
+                "Answer": [
+                    {"name": "portal.permit.pcta.org", "type": 5, "data": "pcta.example."},
+                    {"name": "pcta.example", "type": 1, "data": "104.21.3.7"},
+                ],
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(query_param("type", "AAAA"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"Status": 0})))
+            .mount(&server)
+            .await;
+        let url = Url::parse(&server.uri()).unwrap();
+        let ips = doh(&Client::new(), &url, "portal.permit.pcta.org")
+            .await
+            .unwrap();
+        assert_eq!(ips, vec!["104.21.3.7".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn targets_route_their_own_hosts() {
+        let mut config = Config::default();
+        let system = Router::from_config(&config, Client::new()).unwrap();
+        assert!(system.default == Resolver::System && system.hosts.is_empty());
+        config.targets[0].dns = Some("pin:10.0.0.1".parse().unwrap());
+        let router = Router::from_config(&config, Client::new()).unwrap();
+        let ips = router.lookup("portal.permit.pcta.org").await.unwrap();
+        assert_eq!(ips, vec!["10.0.0.1".parse::<IpAddr>().unwrap()]);
+    }
+}
@@ -0,0 +1,304 @@
+use anyhow::Context;
+use chrono::Local;
+use clap::ValueEnum;
+use std::fmt;
+
+use crate::config::{Config, VpnProviderConfig};
+use crate::format::{Markdown, Report, Templated};
+use crate::http;
+use crate::notifier::{self, Keybase};
+use crate::scraper::Scraper;
+use crate::session::Session;
+use crate::timekeeping::Clock;
+use crate::vpn;
+
+/// What one of `pcta doctor`'s checks found: what was seen when it passed, why when it didn't
+pub struct Check {
+    pub name: String,
+    pub outcome: anyhow::Result<String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, outcome: anyhow::Result<String>) -> Self {
+        Check {
+            name: name.into(),
+            outcome,
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.outcome.is_ok()
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            Ok(seen) => write!(f, "PASS  {:<24} {}", self.name, seen),
+            Err(e) => write!(f, "FAIL  {:<24} {:#}", self.name, e),
+        }
+    }
+}
+
+/// Checks everything the scraper needs before it is left to run: the config as `loaded`, the
+/// clock's zone data, the VPN daemon, the keybase CLI, every target's page over the host's
+/// current route, and Keybase and every notifier by sending each a test message. A config that didn't load
+/// is reported and the defaults checked in its place. `scraper` builds the scraper from the
+/// config with the command line's options.
+pub async fn run(
+    loaded: anyhow::Result<Config>,
+    scraper: impl FnOnce(Config) -> Scraper,
+) -> Vec<Check> {
+    let (config, loaded) = match loaded {
+        Ok(config) => {
+            let seen = format!(
+                "'{}', {} targets, {} profiles",
+                Config::path(),
+                config.targets.len(),
+                config.profiles.len()
+            );
+            (config, Ok(seen))
+        }
+        Err(e) => (
+            Config::default(),
+            Err(e.context("Checking the defaults instead")),
+        ),
+    };
+    let mut checks = vec![Check::new("config", loaded)];
+    checks.push(Check::new("timezone", timezone(&config)));
+    checks.push(Check::new("vpn", vpn_status(&config).await));
+    checks.push(Check::new("keybase", keybase_status().await));
+
+    let scraper = scraper(config);
+    for target in &scraper.config.targets {
+        let name = format!("portal {}", target.label());
+        checks.push(Check::new(name, portal(&scraper, target).await));
+    }
+
+    let tested = notify_test(&scraper.config, Severity::Log, scraper.dry_run).await;
+    checks.extend(tested);
+    checks
+}
+
+/// Which kind of message `pcta notify-test` sends, and so which Keybase topics it lands in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Severity {
+    /// Dates coming open, to every watch's channel
+    Alert,
+    /// Nothing open, to `pcta-logs`
+    Log,
+    /// A failed scrape, to `pcta-errors`
+    Error,
+}
+
+/// Sends a made-up report of `severity` the way a real one goes out: through the templates to
+/// Keybase, to the topics it would reach, and to every notifier. With `dry_run` the Keybase
+/// messages are printed and the notifiers left alone.
+pub async fn notify_test(config: &Config, severity: Severity, dry_run: bool) -> Vec<Check> {
+    let clock = Clock::new(config.display.timezone);
+    let now = clock.now();
+    let stamp = clock.format(now);
+    let label = "pcta notify-test";
+    let dates = [(clock.today(now), 3)];
+    let error = anyhow::anyhow!("Test error, nothing failed");
+    let topics = match severity {
+        Severity::Alert => {
+            let mut topics: Vec<String> = vec![];
+            for watch in config.targets.iter().flat_map(|target| target.watches()) {
+                if !topics.contains(&watch.channel) {
+                    topics.push(watch.channel);
+                }
+            }
+            topics
+        }
+        // `handle_result` routes these itself
+        Severity::Log | Severity::Error => vec![String::new()],
+    };
+    let report = match severity {
+        Severity::Alert => Report::Open {
+            label,
+            dates: &dates,
+            subscribers: &[],
+            apply_url: "",
+            paces: &[],
+        },
+        Severity::Log => Report::Nothing { label },
+        Severity::Error => Report::Failed {
+            label,
+            error: &error,
+        },
+    };
+    let formatter = Templated {
+        templates: &config.templates,
+        fallback: Markdown {
+            table: config.alerting.table,
+        },
+    };
+    let keybase = Keybase { dry_run };
+    let mut checks = vec![];
+    for topic in &topics {
+        let sent = notifier::handle_result(&report, topic, &stamp, &formatter);
+        let sent = match sent {
+            Ok(msg) => keybase
+                .deliver(&msg)
+                .await
+                .map(|_| format!("#{}", msg.topic()))
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        let sent = sent.map(|topic| match dry_run {
+            true => format!("not sent to {}, --dry-run", topic),
+            false => format!("sent to {}", topic),
+        });
+        checks.push(Check::new("keybase message", sent));
+    }
+
+    let notifiers = http::client(&config.http)
+        .and_then(|client| notifier::from_config(&config.notifiers, client));
+    match notifiers {
+        Ok(notifiers) => {
+            for notifier in notifiers {
+                let sent = match dry_run {
+                    true => Ok("not sent, --dry-run".to_string()),
+                    false => notifier
+                        .notify(&report, &stamp)
+                        .await
+                        .map(|_| "test message sent".to_string()),
+                };
+                checks.push(Check::new(format!("notifier {}", notifier.name()), sent));
+            }
+        }
+        Err(e) => checks.push(Check::new("notifiers", Err(e))),
+    }
+    checks
+}
+
+/// One line per check and a count, `false` when any failed
+pub fn report(checks: &[Check]) -> (String, bool) {
+    let mut text: String = checks.iter().map(|check| format!("{}\n", check)).collect();
+    let failed = checks.iter().filter(|check| !check.passed()).count();
+    text += &format!("\n{} checks, {} failed\n", checks.len(), failed);
+    (text, failed == 0)
+}
+
+/// The display zone is built in, only the machine's own zone comes from the system
+fn timezone(config: &Config) -> anyhow::Result<String> {
+    if let Some(tz) = config.display.timezone {
+        return Ok(format!("{} from [display], built in", tz.name()));
+    }
+    let offset = Local::now().offset().to_string();
+    // Windows always has a zone of its own
+    let system = cfg!(windows) || std::path::Path::new("/etc/localtime").exists();
+    match std::env::var("TZ").is_ok() || system {
+        true => Ok(format!("local zone, UTC{}", offset)),
+        false => anyhow::bail!(
+            "No TZ and no /etc/localtime, times will show in UTC. Set `timezone` in [display]."
+        ),
+    }
+}
+
+async fn vpn_status(config: &Config) -> anyhow::Result<String> {
+    if matches!(config.vpn.provider, VpnProviderConfig::None) {
+        return Ok("no VPN configured".to_string());
+    }
+    let provider = vpn::from_config(&config.vpn);
+    let status = provider.status().await?;
+    Ok(format!("{} is {:?}", provider.name(), status))
+}
+
+/// Installed and logged in, which `keybase chat api` needs to post anything
+async fn keybase_status() -> anyhow::Result<String> {
+    let out = crate::exec::command("keybase")
+        .args(["status", "--json"])
+        .output()
+        .await
+        .context("Failed to run `keybase`, is it installed?")?;
+    let status: serde_json::Value = serde_json::from_slice(&out.stdout).with_context(|| {
+        format!(
+            "`keybase status` failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        )
+    })?;
+    let username = status["Username"].as_str().unwrap_or_default();
+    match status["LoggedIn"].as_bool() {
+        Some(true) => Ok(format!("logged in as {}", username)),
+        _ => anyhow::bail!("keybase isn't logged in, run `keybase login`"),
+    }
+}
+
+/// One fetch and parse of `target`'s calendar
+async fn portal(scraper: &Scraper, target: &crate::target::Target) -> anyhow::Result<String> {
+    let proxies = scraper.proxies()?;
+    let session =
+        Session::new(proxies.builder()?, None)?.max_body(scraper.config.http.max_body_bytes);
+    let scraped = scraper.scrape(target, &session, proxies.current()).await?;
+    Ok(format!(
+        "calendar read, {} dates open in range",
+        scraped.days.len()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_counts_the_failures() {
+        let checks = vec![
+            Check::new(
+                "config",
+                Ok("'pcta.toml', 1 targets, 0 profiles".to_string()),
+            ),
+            Check::new("keybase", Err(anyhow::anyhow!("keybase isn't logged in"))),
+        ];
+        let (text, ok) = report(&checks);
+        assert!(!ok);
+        assert!(text.contains("PASS  config "));
+        assert!(text.contains("FAIL  keybase                  keybase isn't logged in\n"));
+        assert!(text.ends_with("2 checks, 1 failed\n"));
+    }
+
+    #[cfg(feature = "ntfy")]
+    #[tokio::test]
+    async fn test_alerts_reach_every_watch_channel() {
+        let config: Config = toml::from_str(
+            r#"
+            [[targets]]
+            terminus = "canadian-border"
+            start = "2023-06-20"
+            end = "2023-07-10"
+
+            [[targets.watches]]
+            name = "Late June"
+            channel = "pcta-sobo"
+
+            [[targets.watches]]
+            name = "July"
+
+            [[targets.watches]]
+            name = "Also July"
+            channel = "pcta-sobo"
+
+            [[notifiers]]
+            kind = "ntfy"
+            topic = "pcta-1234"
+            "#,
+        )
+        .unwrap();
+        let seen = |checks: Vec<Check>| -> Vec<String> {
+            checks.into_iter().map(|c| c.outcome.unwrap()).collect()
+        };
+        assert_eq!(
+            seen(notify_test(&config, Severity::Alert, true).await),
+            vec![
+                "not sent to #pcta-sobo, --dry-run",
+                "not sent to #pcta-alerts, --dry-run",
+                "not sent, --dry-run",
+            ]
+        );
+        assert_eq!(
+            seen(notify_test(&config, Severity::Error, true).await)[0],
+            "not sent to #pcta-errors, --dry-run"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use crate::detect::{BlockKind, Blocked};
+
+/// What went wrong fetching or reading a calendar, for the retry policy, the VPN rotation and
+/// the alert wording to tell apart. Sources still return `anyhow::Error`, with one of these
+/// somewhere in its chain; `find` digs it out from under any context added on the way up. Not
+/// to be mixed up with `errors`, which decides when failures get posted.
+#[derive(Debug, thiserror::Error)]
+pub enum ScrapeError {
+    /// The request never got an answer, or got an error status
+    #[error(transparent)]
+    Network(#[from] reqwest::Error),
+    /// The portal served something other than the calendar on purpose
+    #[error(transparent)]
+    Blocked(Blocked),
+    /// A CAPTCHA widget instead of the calendar, the block a new IP is most likely to lift
+    #[error(transparent)]
+    CaptchaDetected(Blocked),
+    /// The page assigns the calendar but its object couldn't be cut out of the script
+    #[error("{0}")]
+    ParseHtml(String),
+    /// The calendar's JSON is there but doesn't read as one, `what` saying whose
+    #[error("{what}")]
+    ParseJson {
+        what: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// One date on the calendar has a `field` that makes no sense
+    #[error("Invalid '{field}' from {from} on '{date}': '{value}'")]
+    InvalidEntry {
+        date: String,
+        field: &'static str,
+        value: String,
+        from: &'static str,
+    },
+}
+
+impl From<Blocked> for ScrapeError {
+    fn from(blocked: Blocked) -> Self {
+        match blocked.kind {
+            BlockKind::Captcha => ScrapeError::CaptchaDetected(blocked),
+            _ => ScrapeError::Blocked(blocked),
+        }
+    }
+}
+
+impl ScrapeError {
+    /// The first one in `err`'s chain
+    pub fn find(err: &anyhow::Error) -> Option<&ScrapeError> {
+        err.chain().find_map(|e| e.downcast_ref::<ScrapeError>())
+    }
+
+    pub fn blocked(&self) -> Option<&Blocked> {
+        match self {
+            ScrapeError::Blocked(blocked) | ScrapeError::CaptchaDetected(blocked) => Some(blocked),
+            _ => None,
+        }
+    }
+
+    pub fn json(what: impl Into<String>) -> impl FnOnce(serde_json::Error) -> ScrapeError {
+        let what = what.into();
+        move |source| ScrapeError::ParseJson { what, source }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn found_under_context() {
+        let blocked = Blocked::new(BlockKind::Captcha, "Saved page");
+        let err = Err::<(), _>(ScrapeError::from(blocked))
+            .context("Response kept in 'pcta-debug/x.txt'")
+            .unwrap_err();
+        assert!(matches!(
+            ScrapeError::find(&err),
+            Some(ScrapeError::CaptchaDetected(_))
+        ));
+        assert!(ScrapeError::find(&anyhow::anyhow!("robots.txt")).is_none());
+    }
+}
@@ -0,0 +1,188 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// ```toml
+/// [errors]
+/// repeat_secs = 900
+/// pause_after = 10
+/// pause_secs = 1800
+/// ```
+///
+/// A target failing the same way tick after tick is posted once, then summed up as
+/// "same error ×12 over 45m" every `repeat_secs` until it changes or recovers. After
+/// `pause_after` passes in a row where every target failed, scraping stops for `pause_secs`;
+/// `0` never pauses.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorConfig {
+    pub repeat_secs: u64,
+    pub pause_after: u32,
+    pub pause_secs: u64,
+}
+
+impl Default for ErrorConfig {
+    fn default() -> Self {
+        ErrorConfig {
+            repeat_secs: 900,
+            pause_after: 10,
+            pause_secs: 1800,
+        }
+    }
+}
+
+/// What to do about one failure
+#[derive(Debug, PartialEq, Eq)]
+pub enum Post {
+    /// Something new, post the error in full
+    First,
+    /// The same error is still happening, post this summary instead
+    Repeat(String),
+    /// Already said recently
+    Quiet,
+}
+
+/// The same error over and over on one target
+struct Streak {
+    error: String,
+    since: DateTime<Utc>,
+    count: u64,
+    posted: DateTime<Utc>,
+}
+
+/// Collapses repeated errors and counts failing passes, see `ErrorConfig`
+pub struct Aggregator {
+    config: ErrorConfig,
+    streaks: HashMap<String, Streak>,
+    failed_passes: u32,
+}
+
+/// `1h 5m` or `45m`
+fn span(d: Duration) -> String {
+    match d.num_hours() {
+        0 => format!("{}m", d.num_minutes()),
+        hours => format!("{}h {}m", hours, d.num_minutes() % 60),
+    }
+}
+
+impl Aggregator {
+    pub fn new(config: ErrorConfig) -> Self {
+        Aggregator {
+            config,
+            streaks: HashMap::new(),
+            failed_passes: 0,
+        }
+    }
+
+    /// A reloaded config, keeping the running streaks
+    pub fn reconfigure(&mut self, config: ErrorConfig) {
+        self.config = config;
+    }
+
+    pub fn failed(&mut self, label: &str, error: &str, now: DateTime<Utc>) -> Post {
+        let repeat = Duration::seconds(self.config.repeat_secs as i64);
+        match self.streaks.get_mut(label) {
+            Some(streak) if streak.error == error => {
+                streak.count += 1;
+                if now - streak.posted < repeat {
+                    return Post::Quiet;
+                }
+                streak.posted = now;
+                Post::Repeat(format!(
+                    "{} - same error ×{} over {}:\n\n```\n{}\n```\n",
+                    label,
+                    streak.count,
+                    span(now - streak.since),
+                    error
+                ))
+            }
+            _ => {
+                self.streaks.insert(
+                    label.to_string(),
+                    Streak {
+                        error: error.to_string(),
+                        since: now,
+                        count: 1,
+                        posted: now,
+                    },
+                );
+                Post::First
+            }
+        }
+    }
+
+    /// Ends `label`'s streak, with a note to post if it had been failing more than once
+    pub fn recovered(&mut self, label: &str, now: DateTime<Utc>) -> Option<String> {
+        let streak = self.streaks.remove(label)?;
+        (streak.count > 1).then(|| {
+            format!(
+                "{} - recovered after the same error ×{} over {}",
+                label,
+                streak.count,
+                span(now - streak.since)
+            )
+        })
+    }
+
+    /// Counts a pass, `Some` pause once `pause_after` passes in a row failed throughout
+    pub fn pass(&mut self, all_failed: bool) -> Option<Duration> {
+        self.failed_passes = match all_failed {
+            true => self.failed_passes + 1,
+            false => 0,
+        };
+        if self.config.pause_after == 0 || self.failed_passes < self.config.pause_after {
+            return None;
+        }
+        self.failed_passes = 0;
+        Some(Duration::seconds(self.config.pause_secs as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        "2023-04-17T17:00:00Z".parse::<DateTime<Utc>>().unwrap() + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn repeats_are_summed_up() {
+        let mut errors = Aggregator::new(ErrorConfig::default());
+        assert_eq!(errors.failed("April", "503", at(0)), Post::First);
+        for minute in 1..15 {
+            assert_eq!(errors.failed("April", "503", at(minute)), Post::Quiet);
+        }
+        assert_eq!(
+            errors.failed("April", "503", at(45)),
+            Post::Repeat("April - same error ×16 over 45m:\n\n```\n503\n```\n".to_string())
+        );
+        // A different error is news
+        assert_eq!(errors.failed("April", "403", at(46)), Post::First);
+        assert_eq!(errors.recovered("April", at(47)), None);
+        errors.failed("April", "403", at(47));
+        errors.failed("April", "403", at(48));
+        assert_eq!(
+            errors.recovered("May", at(48)),
+            None,
+            "never failed, nothing to say"
+        );
+        assert!(errors
+            .recovered("April", at(108))
+            .unwrap()
+            .contains("×2 over 1h 1m"));
+    }
+
+    #[test]
+    fn pauses_after_failed_passes_in_a_row() {
+        let mut errors = Aggregator::new(ErrorConfig {
+            pause_after: 2,
+            ..ErrorConfig::default()
+        });
+        assert_eq!(errors.pass(true), None);
+        assert_eq!(errors.pass(false), None);
+        assert_eq!(errors.pass(true), None);
+        assert_eq!(errors.pass(true), Some(Duration::seconds(1800)));
+        assert_eq!(errors.pass(true), None);
+    }
+}
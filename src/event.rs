@@ -0,0 +1,193 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+use crate::format::{self, Report};
+use crate::journal::Journal;
+use crate::retry;
+
+/// Events a slow listener can fall behind by before it misses some
+const BACKLOG: usize = 64;
+
+/// Something the scraper did, as every output that isn't a chat message sees it: the webhook
+/// posts it, `/api/events` streams it. Serialized with `event` naming the kind.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Dates in a watch that just came open
+    #[serde(rename = "availability")]
+    AvailabilityFound {
+        label: String,
+        scraped_at: String,
+        dates: Vec<OpenDate>,
+    },
+    /// The scrape worked and nothing in the range is open
+    #[serde(rename = "nothing_open")]
+    NoAvailability { label: String, scraped_at: String },
+    #[serde(rename = "failure")]
+    ScrapeFailed {
+        label: String,
+        scraped_at: String,
+        /// `retry::Failure`, how it was handled
+        failure: String,
+        error: String,
+    },
+    /// A new VPN exit after blocks or failures
+    VpnRotated { at: String, exit: String },
+    /// Requests go out through the next proxy
+    ProxyRotated { at: String, proxy: String },
+    /// Scraping stopped for `minutes` after passes where everything failed
+    SchedulerPaused { at: String, minutes: i64 },
+    /// One target scraped in a pass, however it went: `days` with permits left on its
+    /// calendar, or the `error` it failed with
+    Scraped {
+        label: String,
+        scraped_at: String,
+        changed: bool,
+        days: usize,
+        error: Option<String>,
+    },
+    /// An alerted date of watch `label` that filled up after `open_minutes`
+    DateClosed {
+        label: String,
+        closed_at: String,
+        date: chrono::NaiveDate,
+        open_minutes: i64,
+    },
+}
+
+/// One open date of an `AvailabilityFound`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OpenDate {
+    pub date: chrono::NaiveDate,
+    pub remaining: u64,
+    pub apply_url: Option<String>,
+    /// The users who want this date
+    pub subscribers: Vec<String>,
+    /// When the last permit will likely be gone, at the pace it has been going
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gone_within_minutes: Option<i64>,
+}
+
+impl Event {
+    pub fn from_report(report: &Report, now: &str) -> Event {
+        let (label, scraped_at) = (report.label().to_string(), now.to_string());
+        match report {
+            Report::Open {
+                dates,
+                subscribers,
+                apply_url,
+                ..
+            } => Event::AvailabilityFound {
+                label,
+                scraped_at,
+                dates: dates
+                    .iter()
+                    .map(|(date, remaining)| OpenDate {
+                        date: *date,
+                        remaining: *remaining,
+                        apply_url: format::apply_link(apply_url, *date),
+                        subscribers: subscribers
+                            .iter()
+                            .filter(|s| s.wants(*date))
+                            .map(|s| s.user.clone())
+                            .collect(),
+                        gone_within_minutes: report
+                            .pace(*date)
+                            .map(|pace| pace.gone_within().num_minutes()),
+                    })
+                    .collect(),
+            },
+            Report::Nothing { .. } => Event::NoAvailability { label, scraped_at },
+            Report::Failed { error, .. } => Event::ScrapeFailed {
+                label,
+                scraped_at,
+                failure: format!("{:?}", retry::classify(error)),
+                error: format!("{:#}", error),
+            },
+        }
+    }
+
+    /// What `event` says in the JSON, the SSE event name
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::AvailabilityFound { .. } => "availability",
+            Event::NoAvailability { .. } => "nothing_open",
+            Event::ScrapeFailed { .. } => "failure",
+            Event::VpnRotated { .. } => "vpn_rotated",
+            Event::ProxyRotated { .. } => "proxy_rotated",
+            Event::SchedulerPaused { .. } => "scheduler_paused",
+            Event::Scraped { .. } => "scraped",
+            Event::DateClosed { .. } => "date_closed",
+        }
+    }
+}
+
+/// Where the scraper's events go out to whoever listens, shared by all profiles, and into the
+/// journal when there is one
+pub struct Events {
+    tx: broadcast::Sender<Event>,
+    journal: Option<Mutex<Journal>>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Events {
+            tx: broadcast::channel(BACKLOG).0,
+            journal: None,
+        }
+    }
+}
+
+impl Events {
+    pub fn journal(mut self, journal: Option<Journal>) -> Self {
+        self.journal = journal.map(Mutex::new);
+        self
+    }
+
+    pub fn emit(&self, event: Event) {
+        if let Some(journal) = &self.journal {
+            // Losing a line is no reason to stop scraping
+            if let Err(e) = journal.lock().unwrap().write(&event, chrono::Utc::now()) {
+                crate::info!("{:#}", e);
+            }
+        }
+        // Nobody listening is fine
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subscription::Subscriber;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn events_name_their_kind() {
+        let date = NaiveDate::from_ymd_opt(2023, 4, 14).unwrap();
+        let report = Report::Open {
+            label: "Mexican Border",
+            dates: &[(date, 13)],
+            subscribers: &Subscriber::defaults(),
+            apply_url: "https://apply/?d={date}",
+            paces: &[],
+        };
+        let event = Event::from_report(&report, "now");
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], event.kind());
+        assert_eq!(json["dates"][0]["apply_url"], "https://apply/?d=2023-04-14");
+
+        let paused = Event::SchedulerPaused {
+            at: "now".to_string(),
+            minutes: 30,
+        };
+        let json = serde_json::to_value(&paused).unwrap();
+        assert_eq!(json["event"], paused.kind());
+        assert_eq!(json["minutes"], 30);
+    }
+}
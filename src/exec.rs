@@ -0,0 +1,79 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Where the CLIs we call install themselves on Windows when they don't add themselves to
+/// `PATH`: the environment variable holding the base directory, and the directory under it
+const WINDOWS_INSTALLS: [(&str, &str, &str); 5] = [
+    ("keybase", "LOCALAPPDATA", "Keybase"),
+    ("mullvad", "ProgramFiles", "Mullvad VPN\\resources"),
+    ("wg", "ProgramFiles", "WireGuard"),
+    ("wireguard", "ProgramFiles", "WireGuard"),
+    ("chromium", "ProgramFiles", "Chromium\\Application"),
+];
+
+/// What to run for `program`. Unix finds it on `PATH` by name. On Windows the name needs its
+/// `.exe`, and keybase and the VPN clients are usually not on `PATH` at all, so their install
+/// directories are searched after it.
+pub fn program(program: &str) -> PathBuf {
+    if !cfg!(windows) || Path::new(program).extension().is_some() {
+        return PathBuf::from(program);
+    }
+    let env = |name: &str| std::env::var_os(name);
+    find_exe(program, env("PATH"), env).unwrap_or_else(|| PathBuf::from(format!("{}.exe", program)))
+}
+
+/// `program` as a command, resolved like `program` says
+pub fn command(program: &str) -> tokio::process::Command {
+    tokio::process::Command::new(self::program(program))
+}
+
+/// `command` for the few callers that block
+pub fn blocking_command(program: &str) -> std::process::Command {
+    std::process::Command::new(self::program(program))
+}
+
+/// The first `{program}.exe` on `path`, then in the install directory it is known by
+fn find_exe(
+    program: &str,
+    path: Option<OsString>,
+    env: impl Fn(&str) -> Option<OsString>,
+) -> Option<PathBuf> {
+    let exe = format!("{}.exe", program);
+    let installed = WINDOWS_INSTALLS
+        .iter()
+        .filter(|(name, _, _)| *name == program)
+        .filter_map(|(_, base, dir)| Some(PathBuf::from(env(base)?).join(dir)));
+    path.iter()
+        .flat_map(std::env::split_paths)
+        .chain(installed)
+        .map(|dir| dir.join(&exe))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exes_are_found_on_path_then_where_they_install() {
+        let root = std::env::temp_dir().join(format!("pcta-exec-{}", std::process::id()));
+        let (bin, local) = (root.join("bin"), root.join("local"));
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::create_dir_all(local.join("Keybase")).unwrap();
+        std::fs::write(local.join("Keybase").join("keybase.exe"), "").unwrap();
+        let env = |name: &str| (name == "LOCALAPPDATA").then(|| local.clone().into_os_string());
+        let path = Some(bin.clone().into_os_string());
+
+        assert_eq!(
+            find_exe("keybase", path.clone(), env),
+            Some(local.join("Keybase").join("keybase.exe"))
+        );
+        std::fs::write(bin.join("keybase.exe"), "").unwrap();
+        assert_eq!(
+            find_exe("keybase", path.clone(), env),
+            Some(bin.join("keybase.exe"))
+        );
+        assert_eq!(find_exe("mullvad", path, env), None);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
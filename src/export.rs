@@ -0,0 +1,115 @@
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::history::History;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// A header row, then `scraped_at,label,date,remaining`
+    Csv,
+    /// An array of `{scraped_at, label, date, remaining}`
+    Json,
+}
+
+/// One date's remaining permits as one scrape saw them
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Row {
+    pub scraped_at: DateTime<Utc>,
+    pub label: String,
+    pub date: NaiveDate,
+    pub remaining: u64,
+}
+
+/// `--from` and `--to`: RFC 3339, or a bare date for midnight UTC
+pub fn parse_time(s: &str) -> anyhow::Result<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    s.parse()
+        .with_context(|| format!("Invalid time '{}', expected 2023-04-17 or RFC 3339", s))
+}
+
+/// The per-date counts of every change in `from..to`. History only keeps the dates with
+/// permits left, a date running out becomes a row with `remaining` 0 so fill rates add up.
+pub fn rows(history: &History, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Vec<Row> {
+    let mut rows = vec![];
+    for change in history.changes(from, to) {
+        let closed = change.closed.iter().map(|date| (*date, 0));
+        for (date, remaining) in change.days.iter().copied().chain(closed) {
+            rows.push(Row {
+                scraped_at: change.at,
+                label: change.label.clone(),
+                date,
+                remaining,
+            });
+        }
+    }
+    rows
+}
+
+fn csv_field(s: &str) -> String {
+    match s.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.to_string(),
+    }
+}
+
+pub fn write(rows: &[Row], format: Format, out: &mut impl Write) -> anyhow::Result<()> {
+    match format {
+        Format::Csv => {
+            writeln!(out, "scraped_at,label,date,remaining")?;
+            for row in rows {
+                writeln!(
+                    out,
+                    "{},{},{},{}",
+                    row.scraped_at.to_rfc3339(),
+                    csv_field(&row.label),
+                    row.date,
+                    row.remaining
+                )?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, rows)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Snapshot;
+
+    #[test]
+    fn dates_running_out_are_exported_as_zero() {
+        let day = |d| NaiveDate::from_ymd_opt(2023, 4, d).unwrap();
+        let history = History::open(None).unwrap();
+        for (at, days) in [
+            ("2023-04-16T17:00:00Z", vec![(day(20), 2)]),
+            ("2023-04-17T17:00:00Z", vec![(day(21), 1)]),
+        ] {
+            history
+                .record(Snapshot {
+                    at: at.parse().unwrap(),
+                    label: "Mexican border, Campo".to_string(),
+                    days,
+                })
+                .unwrap();
+        }
+        let rows = rows(&history, Some(parse_time("2023-04-17").unwrap()), None);
+        let mut csv = vec![];
+        write(&rows, Format::Csv, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "scraped_at,label,date,remaining
+2023-04-17T17:00:00+00:00,\"Mexican border, Campo\",2023-04-21,1
+2023-04-17T17:00:00+00:00,\"Mexican border, Campo\",2023-04-20,0
+"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+/// Assignments the calendar object has been (or plausibly will be) published under, tried in
+/// order. The site has shipped `var data = {...};` so far.
+const CANDIDATES: &[&str] = &[
+    "var data =",
+    "var data=",
+    "let data =",
+    "let data=",
+    "const data =",
+    "const data=",
+    "window.data =",
+    "window.data=",
+];
+
+/// Finds the object literal assigned by one of the `CANDIDATES` in a chunk of JavaScript and
+/// returns its exact source text, braces included. Braces inside strings and comments don't
+/// count, so reformatting or a `}` in some label can't cut the object short the way
+/// `\{.*\}` would.
+pub fn object_literal(script: &str) -> Option<&str> {
+    CANDIDATES.iter().find_map(|marker| {
+        let mut from = 0;
+        while let Some(at) = script[from..].find(marker) {
+            let start = from + at + marker.len();
+            if let Some(object) = balanced_object(&script[start..]) {
+                return Some(object);
+            }
+            from = start;
+        }
+        None
+    })
+}
+
+/// Whether `script` assigns one of the `CANDIDATES` at all, literal readable or not
+pub fn assigned(script: &str) -> bool {
+    CANDIDATES.iter().any(|marker| script.contains(marker))
+}
+
+/// The text of every <script> in `html`, in page order, found by scanning for its tags instead
+/// of building a DOM. The DOM keeps a script's text as is too, so both read the same.
+pub fn scripts(html: &str) -> impl Iterator<Item = &str> {
+    let mut rest = html;
+    std::iter::from_fn(move || {
+        let open = find_tag(rest, "<script")?;
+        let tag = &rest[open..];
+        let start = tag.find('>')? + 1;
+        let end = start + find_tag(&tag[start..], "</script")?;
+        rest = &tag[end..];
+        Some(&tag[start..end])
+    })
+}
+
+/// Where `tag` starts in `html`, matched like HTML does regardless of case
+fn find_tag(html: &str, tag: &str) -> Option<usize> {
+    html.as_bytes()
+        .windows(tag.len())
+        .position(|window| window.eq_ignore_ascii_case(tag.as_bytes()))
+}
+
+/// Takes `{ ... }` off the front of `src`, skipping leading whitespace
+fn balanced_object(src: &str) -> Option<&str> {
+    let trimmed = src.trim_start();
+    let offset = src.len() - trimmed.len();
+    let bytes = trimmed.as_bytes();
+    if bytes.first() != Some(&b'{') {
+        return None;
+    }
+
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' | b'`' => i = skip_string(bytes, i)?,
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = bytes[i..].iter().position(|b| *b == b'\n').map(|p| i + p)?;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = trimmed[i + 2..].find("*/").map(|p| i + 2 + p + 1)?;
+            }
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&src[offset..offset + i + 1]);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Index of the quote closing the string opened at `start`
+fn skip_string(bytes: &[u8], start: usize) -> Option<usize> {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b if b == quote => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = include_str!("../fixtures/mexican-border.html");
+    const REFORMATTED: &str = include_str!("../fixtures/mexican-border-reformatted.html");
+
+    #[test]
+    fn finds_object_on_the_live_layout() {
+        let object = object_literal(PAGE).unwrap();
+        assert!(object.starts_with("{\"limit\":50"));
+        assert!(object.ends_with("]}"));
+        serde_json::from_str::<serde_json::Value>(object).unwrap();
+    }
+
+    #[test]
+    fn survives_reformatting_and_braces_in_strings() {
+        let object = object_literal(REFORMATTED).unwrap();
+        let value = serde_json::from_str::<serde_json::Value>(object).unwrap();
+        assert_eq!(value["calendar"].as_array().unwrap().len(), 3);
+        assert_eq!(value["note"], "closed {for now}");
+    }
+
+    #[test]
+    fn ignores_braces_in_comments() {
+        let script = "var data = {\"limit\": 1 /* } */, // }\n\"calendar\": []};";
+        assert_eq!(
+            object_literal(script),
+            Some("{\"limit\": 1 /* } */, // }\n\"calendar\": []}")
+        );
+    }
+
+    #[test]
+    fn unbalanced_or_missing_object() {
+        assert_eq!(object_literal("var data = {\"limit\": 50"), None);
+        assert_eq!(object_literal("var other = {}"), None);
+        assert_eq!(object_literal("<html>Just a moment...</html>"), None);
+    }
+}
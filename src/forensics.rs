@@ -0,0 +1,110 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// ```toml
+/// [debug]
+/// dir = "pcta-debug"
+/// ```
+///
+/// A response that fails to parse is written to `dir` whole, status and headers included, and
+/// the error that gets posted names the file. `""` to only keep the error.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DebugConfig {
+    pub dir: PathBuf,
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        DebugConfig {
+            dir: PathBuf::from("pcta-debug"),
+        }
+    }
+}
+
+impl DebugConfig {
+    pub fn dir(&self) -> Option<&Path> {
+        Some(self.dir.as_path()).filter(|p| !p.as_os_str().is_empty())
+    }
+}
+
+/// What came back along with a body
+#[derive(Debug, Clone, Default)]
+pub struct Head {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+}
+
+/// The context a parse error gets once its response is kept, naming the file
+#[derive(Debug)]
+pub struct Kept(pub PathBuf);
+
+impl std::fmt::Display for Kept {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Response kept in '{}'", self.0.display())
+    }
+}
+
+/// `err` as `{:#}` shows it, less the file it was `Kept` in. Every failure keeps a new one,
+/// which must not tell the same error apart from the last.
+pub fn unkept(err: &anyhow::Error) -> String {
+    let skip = err.downcast_ref::<Kept>().is_some() as usize;
+    let causes: Vec<String> = err.chain().skip(skip).map(|e| e.to_string()).collect();
+    causes.join(": ")
+}
+
+/// Writes the response `key`'s source got at `at` into `dir`, returning where
+pub fn dump(
+    dir: &Path,
+    at: DateTime<Utc>,
+    key: &str,
+    head: &Head,
+    body: &str,
+) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    let path = dir.join(format!("{}-{}.txt", at.format("%Y%m%dT%H%M%S%.3fZ"), key));
+    let mut text = format!("HTTP {}\n", head.status);
+    for (name, value) in &head.headers {
+        writeln!(text, "{}: {}", name, value.to_str().unwrap_or("<binary>"))?;
+    }
+    text += "\n";
+    text += body;
+    std::fs::write(&path, text).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_keeps_status_headers_and_body() {
+        let dir = std::env::temp_dir().join(format!("pcta-debug-{}", std::process::id()));
+        let mut head = Head {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+        };
+        head.headers
+            .insert("content-type", "text/html".parse().unwrap());
+        let at = "2024-03-01T17:00:00Z".parse().unwrap();
+        let path = dump(&dir, at, "pcta-mexican-border", &head, "<html>").unwrap();
+        assert!(path.ends_with("20240301T170000.000Z-pcta-mexican-border.txt"));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "HTTP 200 OK\ncontent-type: text/html\n\n<html>"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let err = anyhow::anyhow!("EOF")
+            .context("Invalid JSON")
+            .context(Kept(path));
+        assert!(format!("{:#}", err).starts_with("Response kept in '"));
+        assert_eq!(unkept(&err), "Invalid JSON: EOF");
+    }
+}
@@ -0,0 +1,442 @@
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::anomaly::Suspicious;
+use crate::error::ScrapeError;
+use crate::event::Event;
+use crate::pace::Pace;
+use crate::retry;
+use crate::subscription::{self, Subscriber};
+
+/// What a message is about, before any notifier decides how it looks
+pub enum Report<'a> {
+    /// Dates in a watch that just came open
+    Open {
+        label: &'a str,
+        dates: &'a [(NaiveDate, u64)],
+        subscribers: &'a [Subscriber],
+        /// Where to apply, with `{date}` standing in for each start date. Empty for no link.
+        apply_url: &'a str,
+        /// How fast the dates that have been going are
+        paces: &'a [(NaiveDate, Pace)],
+    },
+    /// The scrape worked and nothing in the range is open
+    Nothing { label: &'a str },
+    Failed {
+        label: &'a str,
+        error: &'a anyhow::Error,
+    },
+}
+
+impl Report<'_> {
+    /// The pace of `date` when it is open and has one
+    pub fn pace(&self, date: NaiveDate) -> Option<&Pace> {
+        match self {
+            Report::Open { paces, .. } => paces.iter().find(|(d, _)| *d == date).map(|(_, p)| p),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            Report::Open { label, .. }
+            | Report::Nothing { label }
+            | Report::Failed { label, .. } => label,
+        }
+    }
+}
+
+/// Turns a `Report` into the payload one kind of notifier expects
+pub trait MessageFormatter {
+    fn format(&self, report: &Report, now: &str) -> String;
+}
+
+/// Chat markup with @-mentions, for Keybase and Discord
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Markdown {
+    /// Lay the open dates out as a table instead of a list
+    pub table: bool,
+}
+
+/// One line without markup, for SMS and email subjects
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlainText;
+
+/// A JSON object, for webhooks
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+/// Replaces a notifier's built-in text, per kind of report. Placeholders are `{label}`,
+/// `{scrape_time}` and for open dates `{mentions}`, `{count}` and `{date_list}`, for failures
+/// `{headline}` and `{error}`. Anything else in braces is left alone. With a per-date
+/// `apply_url` each line of `{date_list}` ends in its link.
+///
+/// ```toml
+/// [templates]
+/// open = "{mentions} {count} dates at {label}\n{date_list}"
+/// failed = "{label}: {error}"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Templates {
+    pub open: Option<String>,
+    pub nothing: Option<String>,
+    pub failed: Option<String>,
+}
+
+impl Templates {
+    /// The filled-in template for `report`, `None` if it has none
+    pub fn render(&self, report: &Report, now: &str) -> Option<String> {
+        let (template, vars) = match report {
+            Report::Open {
+                label,
+                dates,
+                subscribers,
+                apply_url,
+                ..
+            } => {
+                let days: Vec<NaiveDate> = dates.iter().map(|(date, _)| *date).collect();
+                let list: Vec<String> = dates
+                    .iter()
+                    .map(|(date, remaining)| {
+                        let mut line =
+                            format!("* `{}` ({}): {} left", date, date.format("%a"), remaining);
+                        if let Some(pace) = report.pace(*date) {
+                            line += &format!(", {}", pace);
+                        }
+                        match apply_url.contains("{date}") {
+                            true => format!(
+                                "{} {}",
+                                line,
+                                apply_link(apply_url, *date).unwrap_or_default()
+                            ),
+                            false => line,
+                        }
+                    })
+                    .collect();
+                (
+                    self.open.as_ref()?,
+                    vec![
+                        ("label", label.to_string()),
+                        ("mentions", subscription::mentions_any(subscribers, &days)),
+                        ("count", dates.len().to_string()),
+                        ("date_list", list.join("\n")),
+                    ],
+                )
+            }
+            Report::Nothing { label } => {
+                (self.nothing.as_ref()?, vec![("label", label.to_string())])
+            }
+            Report::Failed { label, error } => (
+                self.failed.as_ref()?,
+                vec![
+                    ("label", label.to_string()),
+                    ("headline", headline(error).to_string()),
+                    ("error", format!("{:#}", error)),
+                ],
+            ),
+        };
+        Some(
+            vars.iter()
+                .chain([("scrape_time", now.to_string())].iter())
+                .fold(template.clone(), |text, (name, value)| {
+                    text.replace(&format!("{{{}}}", name), value)
+                }),
+        )
+    }
+}
+
+/// `templates` where they cover the report, `fallback` everywhere else
+pub struct Templated<'a, F> {
+    pub templates: &'a Templates,
+    pub fallback: F,
+}
+
+impl<F: MessageFormatter> MessageFormatter for Templated<'_, F> {
+    fn format(&self, report: &Report, now: &str) -> String {
+        self.templates
+            .render(report, now)
+            .unwrap_or_else(|| self.fallback.format(report, now))
+    }
+}
+
+fn headline(error: &anyhow::Error) -> &'static str {
+    if error.downcast_ref::<Suspicious>().is_some() {
+        return "Calendar looks wrong, not trusting it";
+    }
+    match ScrapeError::find(error) {
+        Some(ScrapeError::CaptchaDetected(_)) => {
+            return "Portal wants a CAPTCHA solved, rotating the VPN"
+        }
+        Some(ScrapeError::ParseHtml(_) | ScrapeError::ParseJson { .. }) => {
+            return "Page changed, the calendar can't be read"
+        }
+        Some(ScrapeError::InvalidEntry { .. }) => return "Calendar has a date we can't read",
+        _ => {}
+    }
+    match retry::classify(error) {
+        retry::Failure::Blocked => "Portal is blocking us, rotating the VPN",
+        retry::Failure::Transient => "Failed to reach the permit page after retrying",
+        retry::Failure::Fatal => "Failed to scrape the permit page",
+    }
+}
+
+impl MessageFormatter for Markdown {
+    fn format(&self, report: &Report, now: &str) -> String {
+        match report {
+            Report::Open {
+                label,
+                dates,
+                subscribers,
+                apply_url,
+                ..
+            } => {
+                let per_date = apply_url.contains("{date}");
+                let mut msg = String::new();
+                let days: Vec<NaiveDate> = dates.iter().map(|(date, _)| *date).collect();
+                let everyone = subscription::mentions_any(subscribers, &days);
+                if !everyone.is_empty() {
+                    msg += &format!("{} - ", everyone);
+                }
+                msg += &format!(
+                    "*There are {} NEW starting dates open at the {}!*\n\n",
+                    dates.len(),
+                    label
+                );
+                match self.table {
+                    true => {
+                        msg += &calendar_table(dates, subscribers);
+                        for (date, _) in dates.iter().filter(|_| per_date) {
+                            msg += &format!(
+                                "Apply for `{}`: {}\n",
+                                date,
+                                apply_link(apply_url, *date).unwrap_or_default()
+                            );
+                        }
+                    }
+                    false => {
+                        for (date, remaining) in *dates {
+                            msg += &format!(
+                                "* `{}` ({}): {} left",
+                                date,
+                                date.format("%a"),
+                                remaining
+                            );
+                            if let Some(pace) = report.pace(*date) {
+                                msg += &format!(", {}", pace);
+                            }
+                            // With several subscribers, show whose window each date falls in
+                            let who = subscription::mentions(subscribers, *date);
+                            if subscribers.len() > 1 && !who.is_empty() {
+                                msg += &format!(" {}", who);
+                            }
+                            if per_date {
+                                msg += &format!(
+                                    " - {}",
+                                    apply_link(apply_url, *date).unwrap_or_default()
+                                );
+                            }
+                            msg += "\n";
+                        }
+                    }
+                }
+                if !per_date && !apply_url.is_empty() {
+                    msg += &format!("Apply at {}\n", apply_url);
+                }
+                msg + &format!("\n`{}` - Scrape time\n", now)
+            }
+            Report::Nothing { label } => format!(
+                "`{}` @ {} - There are zero available permits in the date range",
+                now, label
+            ),
+            Report::Failed { label, error } => format!(
+                "{} ({}) with error = \n\n```\n{:#}\n```\n",
+                headline(error),
+                label,
+                error
+            ),
+        }
+    }
+}
+
+impl MessageFormatter for PlainText {
+    fn format(&self, report: &Report, _now: &str) -> String {
+        match report {
+            Report::Open { label, dates, .. } => {
+                let list: Vec<String> = dates
+                    .iter()
+                    .map(|(date, remaining)| match report.pace(*date) {
+                        Some(pace) => format!(
+                            "{} ({}, {} left, gone within ~{} min)",
+                            date,
+                            date.format("%a"),
+                            remaining,
+                            pace.gone_within().num_minutes()
+                        ),
+                        None => format!("{} ({}, {} left)", date, date.format("%a"), remaining),
+                    })
+                    .collect();
+                format!(
+                    "{} new start dates open at {}: {}",
+                    dates.len(),
+                    label,
+                    list.join(", ")
+                )
+            }
+            Report::Nothing { label } => format!("No permits open at {}", label),
+            Report::Failed { label, error } => format!("{} ({})", headline(error), label),
+        }
+    }
+}
+
+impl MessageFormatter for Json {
+    fn format(&self, report: &Report, now: &str) -> String {
+        serde_json::to_string(&Event::from_report(report, now)).unwrap_or_default()
+    }
+}
+
+/// The link to apply for `date` with, the same page for every date unless `apply_url` has a
+/// `{date}` in it
+pub fn apply_link(apply_url: &str, date: NaiveDate) -> Option<String> {
+    (!apply_url.is_empty()).then(|| apply_url.replace("{date}", &date.to_string()))
+}
+
+/// Open dates as a fixed-width table in a code block, with a bar per date scaled to the most
+/// permits left so the busy days stand out at a glance
+pub fn calendar_table(open_dates: &[(NaiveDate, u64)], subscribers: &[Subscriber]) -> String {
+    const BAR: u64 = 20;
+    let most = open_dates.iter().map(|(_, left)| *left).max().unwrap_or(0);
+    let width = most.to_string().len().max(4);
+    let who = subscribers.len() > 1;
+    let mut table = format!("```\nDate        Day  {:>width$}\n", "Left");
+    for (date, remaining) in open_dates {
+        let bar = match most {
+            0 => 0,
+            _ => (remaining * BAR).div_ceil(most),
+        };
+        table += &format!(
+            "{}  {}  {:>width$}  {}",
+            date,
+            date.format("%a"),
+            remaining,
+            "#".repeat(bar as usize)
+        );
+        if who {
+            let mentions = subscription::mentions(subscribers, *date);
+            if !mentions.is_empty() {
+                table += &format!(" {}", mentions);
+            }
+        }
+        table += "\n";
+    }
+    table + "```\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 4, d).unwrap()
+    }
+
+    #[test]
+    fn table_scales_bars_to_the_busiest_day() {
+        let table = calendar_table(&[(date(2), 2), (date(14), 40)], &Subscriber::defaults());
+        assert_eq!(
+            table,
+            "```\nDate        Day  Left\n\
+             2023-04-02  Sun     2  #\n\
+             2023-04-14  Fri    40  ####################\n```\n"
+        );
+    }
+
+    #[test]
+    fn each_formatter_fits_its_notifier() {
+        let subscribers = Subscriber::defaults();
+        let report = Report::Open {
+            label: "Mexican Border",
+            dates: &[(date(2), 2), (date(14), 13)],
+            subscribers: &subscribers,
+            apply_url: "",
+            paces: &[],
+        };
+        let markdown = Markdown::default().format(&report, "now");
+        assert!(markdown.starts_with("@jacobyoung - *There are 2 NEW"));
+        assert!(markdown.contains("* `2023-04-14` (Fri): 13 left\n"));
+        assert_eq!(
+            PlainText.format(&report, "now"),
+            "2 new start dates open at Mexican Border: 2023-04-02 (Sun, 2 left), 2023-04-14 (Fri, 13 left)"
+        );
+        let json: serde_json::Value = serde_json::from_str(&Json.format(&report, "now")).unwrap();
+        assert_eq!(json["event"], "availability");
+        assert_eq!(json["dates"][1]["remaining"], 13);
+        assert_eq!(json["dates"][0]["subscribers"][0], "jacobyoung");
+        assert!(json["dates"][1]["gone_within_minutes"].is_null());
+
+        let pace = Pace {
+            taken: 26,
+            over: chrono::Duration::hours(1),
+            left: 13,
+        };
+        let report = Report::Open {
+            label: "Mexican Border",
+            dates: &[(date(14), 13)],
+            subscribers: &subscribers,
+            apply_url: "",
+            paces: &[(date(14), pace)],
+        };
+        let markdown = Markdown::default().format(&report, "now");
+        assert!(markdown
+            .contains("* `2023-04-14` (Fri): 13 left, ~26.0 taken/h, likely gone within 30 min\n"));
+        let json: serde_json::Value = serde_json::from_str(&Json.format(&report, "now")).unwrap();
+        assert_eq!(json["dates"][0]["gone_within_minutes"], 30);
+    }
+
+    #[test]
+    fn open_dates_link_to_the_application() {
+        let dates = [(date(14), 13)];
+        let report = |apply_url| Report::Open {
+            label: "Mexican Border",
+            dates: &dates,
+            subscribers: &[],
+            apply_url,
+            paces: &[],
+        };
+        let markdown = Markdown::default().format(&report("https://apply/?d={date}"), "now");
+        assert!(markdown.contains("* `2023-04-14` (Fri): 13 left - https://apply/?d=2023-04-14\n"));
+        let markdown = Markdown::default().format(&report("https://page"), "now");
+        assert!(markdown.contains("* `2023-04-14` (Fri): 13 left\nApply at https://page\n"));
+        let json: serde_json::Value =
+            serde_json::from_str(&Json.format(&report(""), "now")).unwrap();
+        assert!(json["dates"][0]["apply_url"].is_null());
+    }
+
+    #[test]
+    fn templates_fill_in_their_placeholders() {
+        let templates: Templates = toml::from_str(
+            r#"
+            open = "{mentions} {count} at {label} ({scrape_time}):\n{date_list} {unknown}"
+            "#,
+        )
+        .unwrap();
+        let subscribers = Subscriber::defaults();
+        let report = Report::Open {
+            label: "Mexican Border",
+            dates: &[(date(2), 2), (date(14), 13)],
+            subscribers: &subscribers,
+            apply_url: "",
+            paces: &[],
+        };
+        let formatter = Templated {
+            templates: &templates,
+            fallback: PlainText,
+        };
+        assert_eq!(
+            formatter.format(&report, "now"),
+            "@jacobyoung 2 at Mexican Border (now):\n* `2023-04-02` (Sun): 2 left\n* `2023-04-14` (Fri): 13 left {unknown}"
+        );
+        let nothing = Report::Nothing { label: "May" };
+        assert_eq!(formatter.format(&nothing, "now"), "No permits open at May");
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::bot::Control;
+use crate::notifier::Keybase;
+use crate::timekeeping::Clock;
+
+/// ```toml
+/// [ha]
+/// role = "standby"
+/// heartbeat = "/mnt/shared/pcta-heartbeat.json"
+/// interval_secs = 15
+/// stale_after_secs = 90
+/// ```
+///
+/// Two machines watching the same permits, one alerting at a time. The primary writes its
+/// heartbeat every `interval_secs` to a file both can reach, or to `[redis]` when set. The
+/// standby stays idle and only scrapes while that heartbeat is older than `stale_after_secs`,
+/// handing back once it is fresh again.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HaConfig {
+    pub role: Role,
+    pub heartbeat: PathBuf,
+    pub interval_secs: u64,
+    pub stale_after_secs: u64,
+}
+
+impl Default for HaConfig {
+    fn default() -> Self {
+        HaConfig {
+            role: Role::Off,
+            heartbeat: PathBuf::from("pcta-heartbeat.json"),
+            interval_secs: 15,
+            stale_after_secs: 90,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// The only instance, no heartbeat
+    Off,
+    Primary,
+    Standby,
+}
+
+/// What the primary last published
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Beat {
+    pub host: String,
+    pub pid: u32,
+    pub at: DateTime<Utc>,
+}
+
+/// Where the heartbeat is shared between the machines
+#[async_trait]
+pub trait Heartbeat: Send + Sync {
+    async fn beat(&self, beat: &Beat) -> anyhow::Result<()>;
+
+    /// `None` before the primary ever beat
+    async fn last(&self) -> anyhow::Result<Option<Beat>>;
+}
+
+/// A JSON file, on a mount both machines share
+pub struct FileHeartbeat {
+    path: PathBuf,
+}
+
+#[async_trait]
+impl Heartbeat for FileHeartbeat {
+    async fn beat(&self, beat: &Beat) -> anyhow::Result<()> {
+        // Written aside and moved over, the standby never reads half a beat
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(beat)?).await?;
+        tokio::fs::rename(&tmp, &self.path)
+            .await
+            .with_context(|| format!("Failed to write heartbeat '{}'", self.path.display()))
+    }
+
+    async fn last(&self) -> anyhow::Result<Option<Beat>> {
+        match tokio::fs::read(&self.path).await {
+            Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e)
+                .with_context(|| format!("Failed to read heartbeat '{}'", self.path.display())),
+        }
+    }
+}
+
+impl HaConfig {
+    pub fn heartbeat(&self) -> Box<dyn Heartbeat> {
+        Box::new(FileHeartbeat {
+            path: self.heartbeat.clone(),
+        })
+    }
+
+    /// Whether the primary has gone quiet at `now`. One that can't be read counts as gone.
+    fn stale(&self, last: Option<&Beat>, now: DateTime<Utc>) -> bool {
+        last.is_none_or(|beat| now - beat.at > Duration::seconds(self.stale_after_secs as i64))
+    }
+}
+
+fn host() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|host| host.trim().to_string())
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Beats as the primary forever, or as the standby keeps `control` standing by while the
+/// primary is alive and says so on the logs channel when that changes
+pub async fn run(
+    config: HaConfig,
+    heartbeat: Arc<dyn Heartbeat>,
+    control: Arc<Control>,
+    keybase: Keybase,
+    clock: Clock,
+) -> anyhow::Result<()> {
+    let interval = std::time::Duration::from_secs(config.interval_secs.max(1));
+    let host = host();
+    if config.role == Role::Standby {
+        control.stand_by(true);
+    }
+    loop {
+        let now = clock.now();
+        match config.role {
+            Role::Off => return Ok(()),
+            Role::Primary => {
+                let beat = Beat {
+                    host: host.clone(),
+                    pid: std::process::id(),
+                    at: now,
+                };
+                if let Err(e) = heartbeat.beat(&beat).await {
+                    crate::info!("{} - Heartbeat failed: {:#}", clock.format(now), e);
+                }
+            }
+            Role::Standby => {
+                let last = heartbeat.last().await.unwrap_or_else(|e| {
+                    crate::info!(
+                        "{} - Reading the heartbeat failed: {:#}",
+                        clock.format(now),
+                        e
+                    );
+                    None
+                });
+                let stale = config.stale(last.as_ref(), now);
+                if stale == control.standing_by() {
+                    let msg = match (stale, &last) {
+                        (true, Some(beat)) => format!(
+                            "`{}` - *Primary on {} went quiet* at `{}`, the standby takes over",
+                            clock.format(now),
+                            beat.host,
+                            clock.format(beat.at)
+                        ),
+                        (true, None) => format!(
+                            "`{}` - *No primary heartbeat*, the standby takes over",
+                            clock.format(now)
+                        ),
+                        (false, _) => format!(
+                            "`{}` - Primary is back, the standby stands by",
+                            clock.format(now)
+                        ),
+                    };
+                    crate::info!("{}", msg);
+                    control.stand_by(!stale);
+                    keybase.send("pcta-logs", msg).await;
+                }
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn standby_takes_over_once_the_beat_is_stale() {
+        let name = format!("pcta-heartbeat-{}.json", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let config = HaConfig {
+            role: Role::Standby,
+            heartbeat: path.clone(),
+            ..HaConfig::default()
+        };
+        let heartbeat = config.heartbeat();
+        assert_eq!(heartbeat.last().await.unwrap(), None);
+        assert!(config.stale(None, utc("2024-03-01T17:00:00Z")));
+
+        let beat = Beat {
+            host: "trailhead".to_string(),
+            pid: 1,
+            at: utc("2024-03-01T17:00:00Z"),
+        };
+        heartbeat.beat(&beat).await.unwrap();
+        let last = heartbeat.last().await.unwrap();
+        assert_eq!(last.as_ref(), Some(&beat));
+        assert!(!config.stale(last.as_ref(), utc("2024-03-01T17:01:30Z")));
+        assert!(config.stale(last.as_ref(), utc("2024-03-01T17:01:31Z")));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
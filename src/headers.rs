@@ -0,0 +1,113 @@
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE,
+    UPGRADE_INSECURE_REQUESTS, USER_AGENT,
+};
+
+/// Chrome started sending Sec-Fetch-* in 76, Firefox in 90. Older versions sending them would be
+/// as odd as newer ones leaving them out.
+const CHROME_SEC_FETCH_SINCE: u32 = 76;
+const FIREFOX_SEC_FETCH_SINCE: u32 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    Chrome,
+    Firefox,
+    Safari,
+    InternetExplorer,
+}
+
+impl Family {
+    /// Works out the browser family and major version from a user agent string
+    pub fn detect(user_agent: &str) -> (Family, u32) {
+        // Chrome UAs also carry "Safari/", so look for it first
+        if let Some(version) = major_version(user_agent, "Chrome/") {
+            (Family::Chrome, version)
+        } else if let Some(version) = major_version(user_agent, "Firefox/") {
+            (Family::Firefox, version)
+        } else if let Some(version) = major_version(user_agent, "Version/") {
+            (Family::Safari, version)
+        } else {
+            (
+                Family::InternetExplorer,
+                major_version(user_agent, "rv:").unwrap_or(11),
+            )
+        }
+    }
+}
+
+/// The full set of headers the browser behind `user_agent` sends on a top-level navigation.
+/// Built once per session so every request from one identity looks identical.
+pub fn profile(user_agent: &str) -> HeaderMap {
+    let (family, version) = Family::detect(user_agent);
+    let mut headers = HeaderMap::new();
+    let mut set = |name, value: &'static str| {
+        headers.insert(name, HeaderValue::from_static(value));
+    };
+
+    match family {
+        Family::Chrome => {
+            set(
+                ACCEPT,
+                "text/html,application/xhtml+xml,application/xml;q=0.9,image/webp,*/*;q=0.8",
+            );
+            set(ACCEPT_LANGUAGE, "en-US,en;q=0.9");
+            set(ACCEPT_ENCODING, "gzip, deflate, br");
+            set(UPGRADE_INSECURE_REQUESTS, "1");
+        }
+        Family::Firefox => {
+            set(
+                ACCEPT,
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+            );
+            set(ACCEPT_LANGUAGE, "en-US,en;q=0.5");
+            set(ACCEPT_ENCODING, "gzip, deflate, br");
+            set(UPGRADE_INSECURE_REQUESTS, "1");
+        }
+        Family::Safari => {
+            set(
+                ACCEPT,
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+            );
+            set(ACCEPT_LANGUAGE, "en-US,en;q=0.9");
+            set(ACCEPT_ENCODING, "gzip, deflate, br");
+        }
+        Family::InternetExplorer => {
+            set(ACCEPT, "text/html, application/xhtml+xml, */*");
+            set(ACCEPT_LANGUAGE, "en-US");
+            set(ACCEPT_ENCODING, "gzip, deflate");
+        }
+    }
+
+    let sec_fetch = match family {
+        Family::Chrome => version >= CHROME_SEC_FETCH_SINCE,
+        Family::Firefox => version >= FIREFOX_SEC_FETCH_SINCE,
+        Family::Safari | Family::InternetExplorer => false,
+    };
+    if sec_fetch {
+        set(HeaderName::from_static("sec-fetch-dest"), "document");
+        set(HeaderName::from_static("sec-fetch-mode"), "navigate");
+        set(HeaderName::from_static("sec-fetch-site"), "none");
+        set(HeaderName::from_static("sec-fetch-user"), "?1");
+    }
+
+    if let Ok(ua) = HeaderValue::from_str(user_agent) {
+        headers.insert(USER_AGENT, ua);
+    }
+    headers
+}
+
+/// What a client that doesn't pretend to be a browser sends, for the politeness mode
+pub fn identified(user_agent: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+    if let Ok(ua) = HeaderValue::from_str(user_agent) {
+        headers.insert(USER_AGENT, ua);
+    }
+    headers
+}
+
+fn major_version(user_agent: &str, marker: &str) -> Option<u32> {
+    let rest = &user_agent[user_agent.find(marker)? + marker.len()..];
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
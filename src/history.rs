@@ -0,0 +1,283 @@
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::store::Store;
+
+/// Changes a slow subscriber may fall behind by before it misses some
+const BACKLOG: usize = 64;
+
+/// One target's calendar as a scrape saw it: the dates in range with permits left, any other
+/// date in range had none
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub at: DateTime<Utc>,
+    pub label: String,
+    pub days: Vec<(NaiveDate, u64)>,
+}
+
+/// How a target's calendar differs from the snapshot before, what the web UI streams
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    pub at: DateTime<Utc>,
+    pub label: String,
+    /// Dates that had no permits left before, with what they have now
+    pub opened: Vec<(NaiveDate, u64)>,
+    /// Dates that ran out
+    pub closed: Vec<NaiveDate>,
+    /// The whole calendar as of now
+    pub days: Vec<(NaiveDate, u64)>,
+}
+
+impl Change {
+    fn between(before: Option<&Snapshot>, after: &Snapshot) -> Self {
+        let had = |day: &NaiveDate| before.is_some_and(|b| b.days.iter().any(|(d, _)| d == day));
+        let closed = before
+            .map(|b| &b.days[..])
+            .unwrap_or_default()
+            .iter()
+            .map(|(day, _)| *day)
+            .filter(|day| !after.days.iter().any(|(d, _)| d == day))
+            .collect();
+        Change {
+            at: after.at,
+            label: after.label.clone(),
+            opened: after
+                .days
+                .iter()
+                .filter(|(day, _)| !had(day))
+                .copied()
+                .collect(),
+            closed,
+            days: after.days.clone(),
+        }
+    }
+}
+
+/// Every calendar that changed, appended to a JSON-lines file or Redis and kept in memory for the
+/// web UI. A season of changes is small enough for that, unless `keep` says otherwise.
+pub struct History {
+    file: Option<PathBuf>,
+    store: Option<Arc<Store>>,
+    snapshots: Mutex<Vec<Snapshot>>,
+    /// The most snapshots held in memory, the oldest go first
+    limit: Option<usize>,
+    changes: broadcast::Sender<Change>,
+}
+
+impl History {
+    /// Picks up what earlier runs wrote to `file`, `None` keeps the history in memory only
+    pub fn open(file: Option<&Path>) -> anyhow::Result<Self> {
+        let mut snapshots = vec![];
+        if let Some(path) = file.filter(|path| path.exists()) {
+            let reader = BufReader::new(
+                File::open(path)
+                    .with_context(|| format!("Failed to open history '{}'", path.display()))?,
+            );
+            for (n, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let snapshot = serde_json::from_str(&line).with_context(|| {
+                    format!("Invalid history line {} in '{}'", n + 1, path.display())
+                })?;
+                snapshots.push(snapshot);
+            }
+        }
+        Ok(History {
+            file: file.map(Path::to_path_buf),
+            store: None,
+            snapshots: Mutex::new(snapshots),
+            limit: None,
+            changes: broadcast::channel(BACKLOG).0,
+        })
+    }
+
+    /// Shared with other instances through `store`, picking up what all of them recorded
+    pub async fn shared(store: Arc<Store>) -> anyhow::Result<Self> {
+        Ok(History {
+            file: None,
+            snapshots: Mutex::new(store.snapshots().await?),
+            store: Some(store),
+            limit: None,
+            changes: broadcast::channel(BACKLOG).0,
+        })
+    }
+
+    /// Holds only the latest `limit` snapshots in memory and in Redis, `None` for all. The file
+    /// still gets every one, the web UI and export only see what is held.
+    pub fn keep(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        trim(self.snapshots.get_mut().unwrap(), limit);
+        self
+    }
+
+    /// Whatever the other instances recorded since, from Redis. Without it this is the history
+    /// already held.
+    pub async fn refresh(&self) -> anyhow::Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let mut shared = store.snapshots().await?;
+        trim(&mut shared, self.limit);
+        *self.snapshots.lock().unwrap() = shared;
+        Ok(())
+    }
+
+    /// Shared in the background, a Redis that can't be reached only costs the other instances
+    /// this snapshot
+    pub fn record(&self, snapshot: Snapshot) -> anyhow::Result<()> {
+        if let Some(store) = &self.store {
+            let (store, shared, limit) = (store.clone(), snapshot.clone(), self.limit);
+            tokio::spawn(async move {
+                if let Err(e) = store.append(&shared, limit).await {
+                    crate::info!("Failed to share the {} snapshot: {:#}", shared.label, e);
+                }
+            });
+        }
+        if let Some(path) = &self.file {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open history '{}'", path.display()))?;
+            writeln!(file, "{}", serde_json::to_string(&snapshot)?)
+                .with_context(|| format!("Failed to append to history '{}'", path.display()))?;
+        }
+        let mut snapshots = self.snapshots.lock().unwrap();
+        let before = snapshots.iter().rev().find(|s| s.label == snapshot.label);
+        // Nobody listening is fine
+        let _ = self.changes.send(Change::between(before, &snapshot));
+        snapshots.push(snapshot);
+        trim(&mut snapshots, self.limit);
+        Ok(())
+    }
+
+    /// The changes the snapshots taken in `from..to` made, the first of each target measured
+    /// against the snapshot before the range
+    pub fn changes(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Vec<Change> {
+        let snapshots = self.snapshots.lock().unwrap();
+        let mut last: HashMap<&str, &Snapshot> = HashMap::new();
+        let mut changes = vec![];
+        for snapshot in snapshots.iter() {
+            let before = last.insert(&snapshot.label, snapshot);
+            if from.is_none_or(|from| snapshot.at >= from) && to.is_none_or(|to| snapshot.at < to) {
+                changes.push(Change::between(before, snapshot));
+            }
+        }
+        changes
+    }
+
+    /// The latest `n` snapshots of `label` held, oldest first
+    pub fn latest(&self, label: &str, n: usize) -> Vec<Snapshot> {
+        let snapshots = self.snapshots.lock().unwrap();
+        let mut latest: Vec<Snapshot> = snapshots
+            .iter()
+            .rev()
+            .filter(|s| s.label == label)
+            .take(n)
+            .cloned()
+            .collect();
+        latest.reverse();
+        latest
+    }
+
+    /// The latest snapshot of `label` taken by `at`
+    pub fn as_of(&self, label: &str, at: DateTime<Utc>) -> Option<Snapshot> {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|s| s.label == label && s.at <= at)
+            .cloned()
+    }
+
+    /// Every change recorded from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Change> {
+        self.changes.subscribe()
+    }
+
+    /// Snapshots taken in `from..to`, either end open when `None`, oldest first
+    pub fn between(&self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Vec<Snapshot> {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|s| from.is_none_or(|from| s.at >= from) && to.is_none_or(|to| s.at < to))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Drops the oldest snapshots over `limit`
+fn trim(snapshots: &mut Vec<Snapshot>, limit: Option<usize>) {
+    if let Some(over) = limit.and_then(|limit| snapshots.len().checked_sub(limit)) {
+        snapshots.drain(..over);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn survives_a_restart() {
+        let path = std::env::temp_dir().join(format!("pcta-history-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let snapshot = |when: &str, remaining| Snapshot {
+            at: at(when),
+            label: "Mexican border".to_string(),
+            days: vec![(NaiveDate::from_ymd_opt(2023, 4, 14).unwrap(), remaining)],
+        };
+
+        let history = History::open(Some(&path)).unwrap();
+        history.record(snapshot("2023-04-17T17:00:00Z", 3)).unwrap();
+        history.record(snapshot("2023-04-17T18:00:00Z", 1)).unwrap();
+
+        let history = History::open(Some(&path)).unwrap();
+        let later = history.between(Some(at("2023-04-17T17:30:00Z")), None);
+        assert_eq!(later, vec![snapshot("2023-04-17T18:00:00Z", 1)]);
+        assert_eq!(history.between(None, None).len(), 2);
+
+        let history = History::open(Some(&path)).unwrap().keep(Some(1));
+        assert_eq!(history.between(None, None), later);
+        history.record(snapshot("2023-04-17T19:00:00Z", 0)).unwrap();
+        assert_eq!(history.between(None, None).len(), 1);
+        assert_eq!(
+            History::open(Some(&path))
+                .unwrap()
+                .between(None, None)
+                .len(),
+            3
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn changes_say_what_opened_and_closed() {
+        let day = |d| NaiveDate::from_ymd_opt(2023, 4, d).unwrap();
+        let snapshot = |days: Vec<(NaiveDate, u64)>| Snapshot {
+            at: "2023-04-17T17:00:00Z".parse().unwrap(),
+            label: "Mexican border".to_string(),
+            days,
+        };
+        let history = History::open(None).unwrap();
+        history.record(snapshot(vec![(day(14), 1)])).unwrap();
+        let mut changes = history.subscribe();
+        history
+            .record(snapshot(vec![(day(15), 2), (day(16), 1)]))
+            .unwrap();
+        let change = changes.try_recv().unwrap();
+        assert_eq!(change.opened, vec![(day(15), 2), (day(16), 1)]);
+        assert_eq!(change.closed, vec![day(14)]);
+    }
+}
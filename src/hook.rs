@@ -0,0 +1,134 @@
+use anyhow::Context;
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::process::ExitStatus;
+use tokio::process::Command;
+
+use crate::{exec, format};
+
+/// ```toml
+/// [hook]
+/// command = ["/home/me/bin/apply.sh", "--fast"]
+/// open_browser = true
+/// ```
+///
+/// Acting on an alert within seconds: `command` runs once per newly alerted date with the date
+/// and its remaining permits appended as arguments, and `PCTA_WATCH`, `PCTA_TARGET`,
+/// `PCTA_DATE`, `PCTA_REMAINING` and `PCTA_URL`, the date's application link, set.
+/// `open_browser` opens the first date's link with `xdg-open`, `open` on macOS or `explorer` on
+/// Windows. Neither is held for quiet hours or waited on.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HookConfig {
+    /// Program and its leading arguments, nothing runs when empty
+    pub command: Vec<String>,
+    pub open_browser: bool,
+}
+
+/// What the hook is told about one alert
+pub struct Opened<'a> {
+    pub watch: &'a str,
+    pub target: &'a str,
+    /// `{date}` stands in for each date
+    pub apply_url: &'a str,
+    pub dates: &'a [(NaiveDate, u64)],
+}
+
+#[cfg(target_os = "macos")]
+const OPENER: &str = "open";
+#[cfg(windows)]
+const OPENER: &str = "explorer";
+#[cfg(not(any(target_os = "macos", windows)))]
+const OPENER: &str = "xdg-open";
+
+impl HookConfig {
+    pub fn enabled(&self) -> bool {
+        !self.command.is_empty() || self.open_browser
+    }
+
+    /// Starts the hooks for `opened` in the background, they only log how they went
+    pub fn fire(&self, opened: &Opened<'_>, dry_run: bool) {
+        for (name, command) in self.commands(opened) {
+            if dry_run {
+                crate::info!("[dry run] Would run hook `{}`", name);
+                continue;
+            }
+            tokio::spawn(async move {
+                match run(command).await {
+                    Ok(status) if status.success() => crate::info!("Hook `{}` done", name),
+                    Ok(status) => crate::info!("Hook `{}` exited with {}", name, status),
+                    Err(e) => crate::info!("Hook `{}` failed: {:#}", name, e),
+                }
+            });
+        }
+    }
+
+    /// What to run for `opened`, each named for the logs
+    fn commands(&self, opened: &Opened<'_>) -> Vec<(String, Command)> {
+        let mut commands = vec![];
+        if opened.dates.is_empty() {
+            return commands;
+        }
+        if let Some((program, args)) = self.command.split_first() {
+            for (date, remaining) in opened.dates {
+                let url = format::apply_link(opened.apply_url, *date).unwrap_or_default();
+                let mut command = exec::command(program);
+                command
+                    .args(args)
+                    .arg(date.to_string())
+                    .arg(remaining.to_string())
+                    .env("PCTA_WATCH", opened.watch)
+                    .env("PCTA_TARGET", opened.target)
+                    .env("PCTA_DATE", date.to_string())
+                    .env("PCTA_REMAINING", remaining.to_string())
+                    .env("PCTA_URL", url);
+                commands.push((format!("{} {}", program, date), command));
+            }
+        }
+        let first = opened.dates[0].0;
+        match format::apply_link(opened.apply_url, first) {
+            Some(url) if self.open_browser => {
+                let mut command = exec::command(OPENER);
+                command.arg(&url);
+                commands.push((format!("{} {}", OPENER, url), command));
+            }
+            _ => {}
+        }
+        commands
+    }
+}
+
+async fn run(mut command: Command) -> anyhow::Result<ExitStatus> {
+    command.status().await.context("Failed to start")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn commands_get_the_date_as_arguments_and_env() {
+        let out = std::env::temp_dir().join(format!("pcta-hook-{}", std::process::id()));
+        let script = format!("echo \"$1 $2 $PCTA_WATCH $PCTA_URL\" > {}", out.display());
+        let hook = HookConfig {
+            command: vec!["sh".into(), "-c".into(), script, "hook".into()],
+            open_browser: false,
+        };
+        let dates = [(NaiveDate::from_ymd_opt(2023, 4, 20).unwrap(), 3)];
+        let opened = Opened {
+            watch: "Early",
+            target: "Mexican border",
+            apply_url: "https://permits.example/apply?date={date}",
+            dates: &dates,
+        };
+        let mut commands = hook.commands(&opened);
+        assert_eq!(commands.len(), 1);
+        assert!(run(commands.remove(0).1).await.unwrap().success());
+        let written = std::fs::read_to_string(&out).unwrap();
+        assert_eq!(
+            written,
+            "2023-04-20 3 Early https://permits.example/apply?date=2023-04-20\n"
+        );
+        std::fs::remove_file(&out).unwrap();
+    }
+}
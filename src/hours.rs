@@ -0,0 +1,230 @@
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// When the PCTA office is working, written `"09:00-17:00 America/Los_Angeles"`. The window is
+/// wall-clock time in that zone, so it follows DST no matter where the scraper runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct BusinessHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub tz: Tz,
+}
+
+impl Default for BusinessHours {
+    fn default() -> Self {
+        BusinessHours {
+            start: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            tz: chrono_tz::America::Los_Angeles,
+        }
+    }
+}
+
+impl BusinessHours {
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let time = now.with_timezone(&self.tz).time();
+        time >= self.start && time <= self.end
+    }
+
+    /// The day `now` falls on in the office's zone
+    pub fn local_date(&self, now: DateTime<Utc>) -> NaiveDate {
+        now.with_timezone(&self.tz).date_naive()
+    }
+
+    /// `now` if the window is open, otherwise when it next opens on a day that isn't `closed`.
+    /// `None` if every day for the next year is closed.
+    pub fn next_open(
+        &self,
+        now: DateTime<Utc>,
+        closed: impl Fn(NaiveDate) -> bool,
+    ) -> Option<DateTime<Utc>> {
+        let today = self.local_date(now);
+        if !closed(today) && self.contains(now) {
+            return Some(now);
+        }
+        today
+            .iter_days()
+            .take(366)
+            .filter(|day| !closed(*day))
+            .filter_map(|day| {
+                // A start inside a DST gap opens at the first instant that does exist
+                let start = day.and_time(self.start);
+                self.tz.from_local_datetime(&start).earliest().or_else(|| {
+                    self.tz
+                        .from_local_datetime(&(start + chrono::Duration::hours(1)))
+                        .earliest()
+                })
+            })
+            .map(|start| start.with_timezone(&Utc))
+            .find(|start| *start > now)
+    }
+}
+
+/// Parses `"HH:MM-HH:MM Zone"`, `what` names the setting in errors
+fn window(s: &str, what: &str) -> anyhow::Result<(NaiveTime, NaiveTime, Tz)> {
+    let invalid = || {
+        format!(
+            "Invalid {} '{}', expected e.g. '09:00-17:00 America/Los_Angeles'",
+            what, s
+        )
+    };
+    let (range, tz) = s.trim().split_once(' ').with_context(invalid)?;
+    let (start, end) = range.split_once('-').with_context(invalid)?;
+    let time = |t: &str| NaiveTime::parse_from_str(t, "%H:%M").with_context(invalid);
+    let tz = tz
+        .trim()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .with_context(invalid)?;
+    Ok((time(start)?, time(end)?, tz))
+}
+
+impl FromStr for BusinessHours {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (start, end, tz) = window(s, "business hours")?;
+        if start >= end {
+            anyhow::bail!("Invalid business hours '{}': start must be before end", s);
+        }
+        Ok(BusinessHours { start, end, tz })
+    }
+}
+
+impl TryFrom<String> for BusinessHours {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for BusinessHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{} {}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+            self.tz
+        )
+    }
+}
+
+/// When nobody wants to be pinged, written `"22:00-07:00 America/Los_Angeles"`. Unlike
+/// business hours the window may run past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub tz: Tz,
+}
+
+impl QuietHours {
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let time = now.with_timezone(&self.tz).time();
+        match self.start <= self.end {
+            true => time >= self.start && time < self.end,
+            false => time >= self.start || time < self.end,
+        }
+    }
+}
+
+impl FromStr for QuietHours {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (start, end, tz) = window(s, "quiet hours")?;
+        if start == end {
+            anyhow::bail!("Invalid quiet hours '{}': start and end are the same", s);
+        }
+        Ok(QuietHours { start, end, tz })
+    }
+}
+
+impl TryFrom<String> for QuietHours {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{} {}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+            self.tz
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Datelike;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn window_follows_dst() {
+        let hours: BusinessHours = "09:00-17:00 America/Los_Angeles".parse().unwrap();
+        // PST is UTC-8 in winter, PDT UTC-7 in summer
+        assert!(hours.contains(utc("2023-01-10T17:00:00Z")));
+        assert!(!hours.contains(utc("2023-01-10T16:59:00Z")));
+        assert!(hours.contains(utc("2023-07-10T16:00:00Z")));
+        assert!(!hours.contains(utc("2023-07-11T00:30:00Z")));
+    }
+
+    #[test]
+    fn next_open_skips_closed_days() {
+        let hours = BusinessHours::default();
+        let weekend = |d: NaiveDate| d.weekday().number_from_monday() > 5;
+        // Friday 18:00 PDT opens again Monday 09:00 PDT
+        assert_eq!(
+            hours.next_open(utc("2023-04-15T01:00:00Z"), weekend),
+            Some(utc("2023-04-17T16:00:00Z"))
+        );
+        // Early morning opens the same day
+        assert_eq!(
+            hours.next_open(utc("2023-04-17T14:00:00Z"), weekend),
+            Some(utc("2023-04-17T16:00:00Z"))
+        );
+        let now = utc("2023-04-17T18:00:00Z");
+        assert_eq!(hours.next_open(now, weekend), Some(now));
+        assert_eq!(hours.next_open(now, |_| true), None);
+    }
+
+    #[test]
+    fn rejects_bad_windows() {
+        assert!("17:00-09:00 America/Los_Angeles"
+            .parse::<BusinessHours>()
+            .is_err());
+        assert!("09:00-17:00 Pacific".parse::<BusinessHours>().is_err());
+        assert!("9-5".parse::<BusinessHours>().is_err());
+    }
+
+    #[test]
+    fn quiet_hours_wrap_midnight() {
+        let quiet: QuietHours = "22:00-07:00 America/Los_Angeles".parse().unwrap();
+        // 23:30 and 06:59 PDT are quiet, 07:00 and 21:59 are not
+        assert!(quiet.contains(utc("2023-04-18T06:30:00Z")));
+        assert!(quiet.contains(utc("2023-04-18T13:59:00Z")));
+        assert!(!quiet.contains(utc("2023-04-18T14:00:00Z")));
+        assert!(!quiet.contains(utc("2023-04-18T04:59:00Z")));
+        assert!("07:00-07:00 America/Los_Angeles"
+            .parse::<QuietHours>()
+            .is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use anyhow::Context;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, ClientBuilder, Response};
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use crate::detect::{BlockKind, Blocked};
+use crate::error::ScrapeError;
+
+/// Content types an availability page may come as
+pub const HTML: &[&str] = &["text/html", "application/xhtml+xml"];
+/// Content types an API answer may come as
+pub const JSON: &[&str] = &["application/json", "text/json", "text/javascript"];
+
+/// ```toml
+/// [http]
+/// connect_timeout_secs = 10
+/// timeout_secs = 30
+/// tcp_keepalive_secs = 60
+/// http2 = true
+/// compression = true
+/// interface = "wg0-mullvad"
+/// ip_version = "v4"
+/// max_body_bytes = 5000000
+/// ```
+///
+/// How every client is built, scraping or not. A timeout of `0` waits forever, which is what
+/// stalled the loop on hung connections before there were timeouts. `http2 = false` sticks to
+/// HTTP/1.1, `compression = false` asks for and accepts uncompressed responses only.
+///
+/// With an `interface` the scraping, DNS and exit-IP requests go out from that interface's
+/// address, so when the VPN drops they fail instead of leaking out the default route.
+/// `ip_version` picks which of its addresses, or without an interface which family to connect
+/// over at all.
+///
+/// A scraped body over `max_body_bytes` once decompressed is a block page or tarpit rather than
+/// a calendar, it is cut off there. `0` reads any size.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    pub connect_timeout_secs: u64,
+    pub timeout_secs: u64,
+    pub tcp_keepalive_secs: u64,
+    pub http2: bool,
+    pub compression: bool,
+    pub interface: String,
+    pub ip_version: IpVersion,
+    pub max_body_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpVersion {
+    Any,
+    V4,
+    V6,
+}
+
+impl IpVersion {
+    fn accepts(self, ip: &IpAddr) -> bool {
+        match self {
+            IpVersion::Any => true,
+            IpVersion::V4 => ip.is_ipv4(),
+            IpVersion::V6 => ip.is_ipv6(),
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            connect_timeout_secs: 10,
+            timeout_secs: 30,
+            tcp_keepalive_secs: 60,
+            http2: true,
+            compression: true,
+            interface: String::new(),
+            ip_version: IpVersion::Any,
+            max_body_bytes: 5_000_000,
+        }
+    }
+}
+
+fn secs(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// A client builder with `config` applied, for the caller to add proxies and cookies to
+pub fn builder(config: &HttpConfig) -> ClientBuilder {
+    let mut builder = Client::builder()
+        .tcp_keepalive(secs(config.tcp_keepalive_secs))
+        .gzip(config.compression)
+        .brotli(config.compression)
+        .deflate(config.compression);
+    if let Some(timeout) = secs(config.connect_timeout_secs) {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(timeout) = secs(config.timeout_secs) {
+        builder = builder.timeout(timeout);
+    }
+    if !config.http2 {
+        builder = builder.http1_only();
+    }
+    builder
+}
+
+/// Binds `builder` to the configured interface's address, or the IP family's unspecified one.
+/// Errors if the interface is gone or has no such address, which is the point.
+pub fn bind(builder: ClientBuilder, config: &HttpConfig) -> anyhow::Result<ClientBuilder> {
+    if !config.interface.is_empty() {
+        if cfg!(windows) {
+            anyhow::bail!("Binding to an interface needs `ip`, set only ip_version on Windows");
+        }
+        let output = crate::exec::blocking_command("ip")
+            .args(["-o", "addr", "show", "dev", &config.interface])
+            .output()
+            .context("Failed to run `ip addr`, is iproute2 installed?")?;
+        let listing = String::from_utf8_lossy(&output.stdout);
+        let addr = interface_address(&listing, config.ip_version).with_context(|| {
+            format!(
+                "Interface {} has no {:?} address, is the VPN up?",
+                config.interface, config.ip_version
+            )
+        })?;
+        return Ok(builder.local_address(addr));
+    }
+    Ok(match config.ip_version {
+        IpVersion::Any => builder,
+        IpVersion::V4 => builder.local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        IpVersion::V6 => builder.local_address(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+    })
+}
+
+/// The first address of `version` in `ip -o addr show` output
+fn interface_address(listing: &str, version: IpVersion) -> Option<IpAddr> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            words.find(|w| *w == "inet" || *w == "inet6")?;
+            let (addr, _prefix) = words.next()?.split_once('/')?;
+            addr.parse::<IpAddr>().ok()
+        })
+        .find(|ip| version.accepts(ip))
+}
+
+/// Reads a scraped `response` from `url`. A successful one has to be one of `types` when it
+/// says, and no body may go over `max_bytes`, `0` for any size. Either is a `Blocked`, read
+/// no further than needed to tell.
+pub async fn body(
+    mut response: Response,
+    types: &[&str],
+    max_bytes: u64,
+    url: &str,
+) -> Result<String, ScrapeError> {
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase()
+        });
+    match content_type {
+        Some(found) if response.status().is_success() && !types.contains(&found.as_str()) => {
+            let detail = format!("{} from {}, expected {}", found, url, types.join(" or "));
+            return Err(Blocked::new(BlockKind::ContentType, detail).into());
+        }
+        _ => {}
+    }
+    let too_large = || {
+        let detail = format!("More than {} bytes from {}", max_bytes, url);
+        ScrapeError::from(Blocked::new(BlockKind::Oversized, detail))
+    };
+    let over = |len: u64| max_bytes > 0 && len > max_bytes;
+    // Only what came over the wire, decompressed it is counted as it is read
+    if response.content_length().is_some_and(over) {
+        return Err(too_large());
+    }
+    let mut body = vec![];
+    while let Some(chunk) = response.chunk().await.map_err(ScrapeError::Network)? {
+        body.extend_from_slice(&chunk);
+        if over(body.len() as u64) {
+            return Err(too_large());
+        }
+    }
+    Ok(String::from_utf8(body)
+        .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()))
+}
+
+/// A plain client, for notifiers which don't need to go through the tunnel
+pub fn client(config: &HttpConfig) -> anyhow::Result<Client> {
+    builder(config)
+        .build()
+        .context("Reqwest client build failed")
+}
+
+/// A client bound like the scraping ones, for DNS and exit-IP checks
+pub fn bound_client(config: &HttpConfig) -> anyhow::Result<Client> {
+    bind(builder(config), config)?
+        .build()
+        .context("Reqwest client build failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn picks_the_interface_address_of_the_family() {
+        let listing = "\
+5: wg0    inet 10.64.1.2/32 scope global wg0\\       valid_lft forever preferred_lft forever
+5: wg0    inet6 fc00:bbbb:bbbb:bb01::1:102/128 scope global \\       valid_lft forever
+";
+        assert_eq!(
+            interface_address(listing, IpVersion::Any),
+            Some("10.64.1.2".parse().unwrap())
+        );
+        assert_eq!(
+            interface_address(listing, IpVersion::V6),
+            Some("fc00:bbbb:bbbb:bb01::1:102".parse().unwrap())
+        );
+        assert_eq!(interface_address("", IpVersion::V4), None);
+    }
+
+    #[tokio::test]
+    async fn hung_responses_time_out() {
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+        let client = builder(&HttpConfig {
+            timeout_secs: 1,
+            ..HttpConfig::default()
+        })
+        .build()
+        .unwrap();
+        let err = client.get(server.uri()).send().await.unwrap_err();
+        assert!(err.is_timeout());
+    }
+
+    #[tokio::test]
+    async fn oversized_and_mistyped_bodies_are_blocks() {
+        let server = MockServer::start().await;
+        for (route, body, mime) in [
+            ("/page", "<html></html>", "text/html; charset=utf-8"),
+            ("/image", "GIF89a", "image/gif"),
+            ("/tarpit", &"x".repeat(2048)[..], "text/html"),
+        ] {
+            Mock::given(wiremock::matchers::path(route))
+                .respond_with(ResponseTemplate::new(200).set_body_raw(body, mime))
+                .mount(&server)
+                .await;
+        }
+        let read = |route: &str| {
+            let url = format!("{}{}", server.uri(), route);
+            async move {
+                let response = Client::new().get(&url).send().await.unwrap();
+                body(response, HTML, 1024, &url).await
+            }
+        };
+        assert_eq!(read("/page").await.unwrap(), "<html></html>");
+        for (route, kind) in [
+            ("/image", BlockKind::ContentType),
+            ("/tarpit", BlockKind::Oversized),
+        ] {
+            let err = read(route).await.unwrap_err();
+            assert_eq!(err.blocked().unwrap().kind, kind);
+        }
+    }
+}
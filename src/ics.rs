@@ -0,0 +1,109 @@
+use chrono::{Duration, NaiveDate};
+
+use crate::state::State;
+
+/// Lines are folded after this many octets, as RFC 5545 asks
+const FOLD_AT: usize = 75;
+
+/// Escapes TEXT values
+fn text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Continues lines longer than `FOLD_AT` on the next with a leading space
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > FOLD_AT {
+            out += "\r\n ";
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out
+}
+
+fn day(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+/// An all-day event per open start date of every target in `state`, with the permits left
+/// in its description, for subscribing to from a calendar app. Targets that failed have none.
+pub fn calendar(state: Option<&State>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//jryio//pcta//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:PCTA permits".to_string(),
+    ];
+    if let Some(state) = state {
+        let stamp = state.scraped_at.utc.format("%Y%m%dT%H%M%SZ");
+        for target in &state.targets {
+            let uid: String = target
+                .label
+                .chars()
+                .map(|c| match c.is_ascii_alphanumeric() {
+                    true => c.to_ascii_lowercase(),
+                    false => '-',
+                })
+                .collect();
+            for (date, left) in &target.open {
+                lines.extend([
+                    "BEGIN:VEVENT".to_string(),
+                    format!("UID:{}-{}@pcta", day(*date), uid),
+                    format!("DTSTAMP:{}", stamp),
+                    format!("DTSTART;VALUE=DATE:{}", day(*date)),
+                    format!("DTEND;VALUE=DATE:{}", day(*date + Duration::days(1))),
+                    format!(
+                        "SUMMARY:{}",
+                        text(&format!("{}: {} left", target.label, left))
+                    ),
+                    format!(
+                        "DESCRIPTION:{}",
+                        text(&format!(
+                            "{} permits left to start at {} on {}, as of {}",
+                            left, target.label, date, state.scraped_at.local
+                        ))
+                    ),
+                    "TRANSP:TRANSPARENT".to_string(),
+                    "END:VEVENT".to_string(),
+                ]);
+            }
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::TargetState;
+    use crate::timekeeping::Clock;
+
+    #[test]
+    fn one_all_day_event_per_open_date() {
+        let clock = Clock::new(Some(chrono_tz::America::Los_Angeles));
+        let state = State {
+            scraped_at: clock.stamp("2023-04-17T17:00:00Z".parse().unwrap()),
+            targets: vec![TargetState {
+                label: "Mexican border, Campo".to_string(),
+                open: vec![(NaiveDate::from_ymd_opt(2023, 4, 30).unwrap(), 3)],
+                error: None,
+                timings: None,
+            }],
+        };
+        let ics = calendar(Some(&state));
+        assert!(ics.contains("UID:20230430-mexican-border--campo@pcta\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20230430\r\nDTEND;VALUE=DATE:20230501\r\n"));
+        assert!(ics.contains("SUMMARY:Mexican border\\, Campo: 3 left\r\n"));
+        assert!(ics.lines().all(|line| line.len() <= FOLD_AT));
+        assert_eq!(calendar(None).matches("VEVENT").count(), 0);
+    }
+}
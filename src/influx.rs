@@ -0,0 +1,147 @@
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::secret::Secret;
+
+/// ```toml
+/// [influx]
+/// url = "http://localhost:8086/api/v2/write?org=home&bucket=pcta"
+/// token = "env:INFLUX_TOKEN"
+/// measurement = "pcta_remaining"
+/// ```
+///
+/// Pushes every date's permits left after each pass in line protocol, one point per target and
+/// date tagged with both, so a season's fill rate can be charted in Grafana. `url` is any line
+/// protocol write endpoint: InfluxDB 2's `/api/v2/write`, 1.x's `/write?db=pcta` or
+/// VictoriaMetrics' `/write`. Timestamps are in nanoseconds, their default precision. A date
+/// in range with none left is written as `0`, and `url = ""` pushes nothing.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InfluxConfig {
+    pub url: String,
+    /// Sent as `Authorization: Token ...`
+    pub token: Option<Secret>,
+    pub measurement: String,
+}
+
+impl Default for InfluxConfig {
+    fn default() -> Self {
+        InfluxConfig {
+            url: String::new(),
+            token: None,
+            measurement: "pcta_remaining".to_string(),
+        }
+    }
+}
+
+/// Where each pass's points go
+pub struct Influx {
+    client: Client,
+    config: InfluxConfig,
+}
+
+impl Influx {
+    /// `None` without a `url`
+    pub fn new(config: &InfluxConfig, client: Client) -> Option<Self> {
+        (!config.url.is_empty()).then(|| Influx {
+            client,
+            config: config.clone(),
+        })
+    }
+
+    /// One line per date in `start..=end`, with what `days` has left of it
+    pub fn points(
+        &self,
+        label: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+        days: &[(NaiveDate, u64)],
+        at: DateTime<Utc>,
+    ) -> Vec<String> {
+        let nanos = at.timestamp_nanos_opt().unwrap_or_default();
+        let measurement = escape(&self.config.measurement, false);
+        let target = escape(label, true);
+        start
+            .iter_days()
+            .take_while(|day| *day <= end)
+            .map(|day| {
+                let remaining = days
+                    .iter()
+                    .find(|(d, _)| *d == day)
+                    .map_or(0, |(_, left)| *left);
+                format!(
+                    "{},target={},date={} remaining={}i {}",
+                    measurement, target, day, remaining, nanos
+                )
+            })
+            .collect()
+    }
+
+    pub async fn write(&self, points: &[String]) -> anyhow::Result<()> {
+        if points.is_empty() {
+            return Ok(());
+        }
+        let mut request = self.client.post(&self.config.url).body(points.join("\n"));
+        if let Some(token) = &self.config.token {
+            request = request.header("Authorization", format!("Token {}", token.expose()?));
+        }
+        request
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .context("InfluxDB write failed")?;
+        Ok(())
+    }
+}
+
+/// Backslashes what line protocol would otherwise split on, `=` only matters in tags
+fn escape(s: &str, tag: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == ',' || c == ' ' || (tag && c == '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_string, header, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn writes_every_date_in_range() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("Authorization", "Token influx-token"))
+            .and(body_string(
+                "pcta_remaining,target=Mexican\\ border,date=2024-04-14 remaining=3i 1713286800000000000\n\
+                 pcta_remaining,target=Mexican\\ border,date=2024-04-15 remaining=0i 1713286800000000000",
+            ))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = InfluxConfig {
+            url: format!("{}/api/v2/write?org=home&bucket=pcta", server.uri()),
+            token: Some(Secret::from("influx-token".to_string())),
+            ..InfluxConfig::default()
+        };
+        let influx = Influx::new(&config, Client::new()).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 4, d).unwrap();
+        let points = influx.points(
+            "Mexican border",
+            day(14),
+            day(15),
+            &[(day(14), 3)],
+            "2024-04-16T17:00:00Z".parse().unwrap(),
+        );
+        influx.write(&points).await.unwrap();
+        assert!(Influx::new(&InfluxConfig::default(), Client::new()).is_none());
+    }
+}
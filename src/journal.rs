@@ -0,0 +1,239 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::event::Event;
+
+/// ```toml
+/// [journal]
+/// file = "pcta-journal.jsonl"
+/// max_bytes = 10000000
+/// max_age_hours = 24
+/// keep = 14
+/// gzip = true
+/// ```
+///
+/// Every event the scraper emits, one JSON object per line with when it was `logged_at`, for
+/// `grep` and `jq` after the fact. Once `file` is over `max_bytes` or its first line is older
+/// than `max_age_hours` it is moved aside with that line's time in its name, gzipped with
+/// `gzip`, and only the newest `keep` of those stay. `0` turns either limit off, `keep = 0`
+/// keeps them all and `file = ""` keeps no journal.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JournalConfig {
+    pub file: PathBuf,
+    pub max_bytes: u64,
+    pub max_age_hours: u64,
+    pub keep: usize,
+    pub gzip: bool,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        JournalConfig {
+            file: PathBuf::new(),
+            max_bytes: 10_000_000,
+            max_age_hours: 24,
+            keep: 14,
+            gzip: false,
+        }
+    }
+}
+
+/// One line of the journal
+#[derive(Serialize, Deserialize)]
+struct Line<T> {
+    logged_at: DateTime<Utc>,
+    #[serde(flatten)]
+    event: T,
+}
+
+/// The journal file being appended to
+pub struct Journal {
+    config: JournalConfig,
+    file: File,
+    size: u64,
+    /// When the first line of `file` was written
+    since: Option<DateTime<Utc>>,
+}
+
+impl Journal {
+    /// Appends to what is already in the file, `None` without one configured
+    pub fn open(config: JournalConfig) -> anyhow::Result<Option<Self>> {
+        if config.file.as_os_str().is_empty() {
+            return Ok(None);
+        }
+        let path = &config.file;
+        let file = append(path)?;
+        let size = file.metadata()?.len();
+        let since = BufReader::new(File::open(path)?)
+            .lines()
+            .next()
+            .and_then(|line| serde_json::from_str::<Line<serde_json::Value>>(&line.ok()?).ok())
+            .map(|line| line.logged_at);
+        Ok(Some(Journal {
+            config,
+            file,
+            size,
+            since,
+        }))
+    }
+
+    pub fn write(&mut self, event: &Event, at: DateTime<Utc>) -> anyhow::Result<()> {
+        if self.due(at) {
+            self.rotate()?;
+        }
+        let mut line = serde_json::to_string(&Line {
+            logged_at: at,
+            event,
+        })?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .with_context(|| format!("Failed to append to '{}'", self.config.file.display()))?;
+        self.size += line.len() as u64;
+        self.since.get_or_insert(at);
+        Ok(())
+    }
+
+    fn due(&self, at: DateTime<Utc>) -> bool {
+        let Some(since) = self.since else {
+            return false;
+        };
+        let max_age = Duration::hours(self.config.max_age_hours as i64);
+        (self.config.max_bytes > 0 && self.size >= self.config.max_bytes)
+            || (self.config.max_age_hours > 0 && at - since >= max_age)
+    }
+
+    /// Moves the file aside and starts a new one
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        let path = self.config.file.clone();
+        let since = self.since.take().unwrap_or_else(Utc::now);
+        let rotated = sibling(&path, &format!(".{}", since.format("%Y%m%dT%H%M%SZ")));
+        std::fs::rename(&path, &rotated)
+            .with_context(|| format!("Failed to rotate '{}'", path.display()))?;
+        self.file = append(&path)?;
+        self.size = 0;
+        if self.config.gzip {
+            gzip(&rotated)?;
+        }
+        self.prune(&path)
+    }
+
+    /// Removes all but the newest `keep` rotated files
+    fn prune(&self, path: &Path) -> anyhow::Result<()> {
+        if self.config.keep == 0 {
+            return Ok(());
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let prefix = format!("{}.", name);
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut rotated: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|p| {
+                p.file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with(&prefix))
+            })
+            .collect();
+        // The timestamps sort oldest first
+        rotated.sort();
+        let over = rotated.len().saturating_sub(self.config.keep);
+        for old in &rotated[..over] {
+            std::fs::remove_file(old)
+                .with_context(|| format!("Failed to remove '{}'", old.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn append(path: &Path) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open journal '{}'", path.display()))
+}
+
+/// `path` with `suffix` put after its whole file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Replaces `path` with `path.gz`
+fn gzip(path: &Path) -> anyhow::Result<()> {
+    let gz = sibling(path, ".gz");
+    let mut encoder = flate2::write::GzEncoder::new(
+        File::create(&gz).with_context(|| format!("Failed to create '{}'", gz.display()))?,
+        flate2::Compression::default(),
+    );
+    std::io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn rotates_when_full_or_old_and_keeps_the_newest() {
+        let dir = std::env::temp_dir().join(format!("pcta-journal-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("journal.jsonl");
+        let config = JournalConfig {
+            file: path.clone(),
+            max_bytes: 200,
+            keep: 1,
+            gzip: true,
+            ..JournalConfig::default()
+        };
+        let event = Event::VpnRotated {
+            at: "now".to_string(),
+            exit: "se-got-wg-001".to_string(),
+        };
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+
+        let mut journal = Journal::open(config.clone()).unwrap().unwrap();
+        journal.write(&event, at("2024-03-01T17:00:00Z")).unwrap();
+        // Picks up where the file left off
+        let mut journal = Journal::open(config).unwrap().unwrap();
+        assert_eq!(journal.since, Some(at("2024-03-01T17:00:00Z")));
+        journal.write(&event, at("2024-03-01T17:01:00Z")).unwrap();
+        assert!(journal.size < 200);
+        // A day later, however small
+        journal.write(&event, at("2024-03-02T17:00:00Z")).unwrap();
+        for minute in 1..=4 {
+            let at = at(&format!("2024-03-02T17:0{}:00Z", minute));
+            journal.write(&event, at).unwrap();
+        }
+
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["journal.jsonl", "journal.jsonl.20240302T170000Z.gz"]
+        );
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(File::open(dir.join(&names[1])).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.starts_with(
+            r#"{"logged_at":"2024-03-02T17:00:00Z","event":"vpn_rotated","at":"now","exit":"se-got-wg-001"}"#
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+pub mod alerting;
+pub mod anomaly;
+pub mod boost;
+pub mod bot;
+pub mod breaker;
+pub mod browser;
+pub mod cache;
+pub mod config;
+pub mod ctl;
+pub mod detect;
+pub mod digest;
+pub mod dns;
+pub mod doctor;
+pub mod error;
+pub mod errors;
+pub mod event;
+pub mod exec;
+pub mod export;
+pub mod extract;
+pub mod forensics;
+pub mod format;
+pub mod ha;
+pub mod headers;
+pub mod history;
+pub mod hook;
+pub mod hours;
+pub mod http;
+pub mod ics;
+pub mod influx;
+pub mod journal;
+pub mod lock;
+pub mod log;
+pub mod memory;
+pub mod notifier;
+pub mod outbox;
+pub mod overview;
+pub mod pace;
+pub mod parser;
+pub mod proxy;
+pub mod ratelimit;
+pub mod reload;
+pub mod replay;
+pub mod retry;
+pub mod robots;
+pub mod scheduler;
+pub mod scraper;
+pub mod secret;
+pub mod session;
+pub mod source;
+pub mod state;
+pub mod store;
+pub mod subscription;
+pub mod systemd;
+pub mod target;
+pub mod timekeeping;
+pub mod timing;
+pub mod vpn;
+#[cfg(feature = "web")]
+pub mod web;
+
+pub use scraper::Scraper;
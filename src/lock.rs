@@ -0,0 +1,67 @@
+use anyhow::Context;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+
+/// Keeps a second copy of the scraper from alerting the same channels. Held until dropped,
+/// and by the OS only for as long as the process lives, so a crash leaves nothing to clean up.
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Takes the lock at `path` for `what`, or says which process has it. The file is left
+    /// behind with the holder's pid in it.
+    pub fn acquire(path: &Path, what: &str) -> anyhow::Result<InstanceLock> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open lock file '{}'", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut pid = String::new();
+                file.read_to_string(&mut pid)?;
+                anyhow::bail!(
+                    "Another pcta (pid {}) is already running {}, stop it or pass --force",
+                    pid.trim(),
+                    what
+                );
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock '{}'", path.display()))
+            }
+        }
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+        Ok(InstanceLock { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_instance_is_refused_until_the_first_stops() {
+        let path = std::env::temp_dir().join(format!("pcta-{}.lock", std::process::id()));
+        let first = InstanceLock::acquire(&path, "profile `sobo`").unwrap();
+        let err = InstanceLock::acquire(&path, "profile `sobo`")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Another pcta (pid {}) is already running profile `sobo`, stop it or pass --force",
+                std::process::id()
+            )
+        );
+        drop(first);
+        assert!(InstanceLock::acquire(&path, "profile `sobo`").is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+/// `-v` counts, `PCTA_DEBUG` set counts as one
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+
+/// Every value never to show up in a log line, longest first
+static REDACTED: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// Shorter values would take ordinary words and numbers out of the logs with them
+const SHORTEST_REDACTED: usize = 4;
+
+pub fn set_verbosity(level: u8) {
+    VERBOSITY.store(level, Ordering::Relaxed);
+}
+
+/// 0 for the normal log, 1 (`-v`) adds the lines that would flood it, 2 (`-vv`) the raw
+/// payloads too
+pub fn verbosity() -> u8 {
+    let env = std::env::var_os("PCTA_DEBUG").is_some() as u8;
+    VERBOSITY.load(Ordering::Relaxed).max(env)
+}
+
+/// Keeps `value` out of every line logged from now on. Secrets add themselves when exposed.
+pub fn redact(value: &str) {
+    if value.len() < SHORTEST_REDACTED {
+        return;
+    }
+    let mut redacted = REDACTED.lock().unwrap();
+    if !redacted.iter().any(|v| v == value) {
+        redacted.push(value.to_string());
+        // A value inside a longer one must not leave the rest of that one behind
+        redacted.sort_by_key(|v| std::cmp::Reverse(v.len()));
+    }
+}
+
+/// `line` with every `redact`ed value replaced
+pub fn redacted(line: &str) -> String {
+    REDACTED
+        .lock()
+        .unwrap()
+        .iter()
+        .fold(line.to_string(), |line, value| {
+            line.replace(value.as_str(), "<redacted>")
+        })
+}
+
+/// `println!` with the `redact`ed values taken out, for everything the scraper logs
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        println!("{}", $crate::log::redacted(&format!($($arg)*)))
+    };
+}
+
+/// `info!` that only prints with `-v`, for lines that would flood the normal log
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::verbosity() >= 1 {
+            $crate::info!($($arg)*);
+        }
+    };
+}
+
+/// `info!` that only prints with `-vv`, for whole response payloads
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if $crate::log::verbosity() >= 2 {
+            $crate::info!($($arg)*);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted_values_never_reach_the_log() {
+        redact("xoxb-1234");
+        redact("xoxb-1234-5678");
+        redact("abc");
+        assert_eq!(
+            redacted("POST https://slack.com?t=xoxb-1234-5678 failed, abc"),
+            "POST https://slack.com?t=<redacted> failed, abc"
+        );
+        assert_eq!(redacted("token xoxb-1234"), "token <redacted>");
+    }
+}
@@ -0,0 +1,33 @@
+use serde::Deserialize;
+
+/// Snapshots `low` keeps in memory when `history_snapshots` doesn't say
+const LOW_HISTORY_SNAPSHOTS: usize = 500;
+
+/// ```toml
+/// [memory]
+/// low = true
+/// history_snapshots = 200
+/// ```
+///
+/// For small boards like a Pi Zero. `low` never builds the page's DOM, not even when scanning
+/// its <script> tags finds no calendar. It keeps no response cache, so every page is fetched
+/// and parsed in full. `history_snapshots` bounds the history held in memory on its own too,
+/// `0` keeps all of it or 500 with `low`, and so does `[redis]`. The history file still gets
+/// every snapshot.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MemoryConfig {
+    pub low: bool,
+    pub history_snapshots: usize,
+}
+
+impl MemoryConfig {
+    /// How many snapshots the history holds on to, `None` for all of them
+    pub fn history_limit(&self) -> Option<usize> {
+        match (self.history_snapshots, self.low) {
+            (0, true) => Some(LOW_HISTORY_SNAPSHOTS),
+            (0, false) => None,
+            (n, _) => Some(n),
+        }
+    }
+}
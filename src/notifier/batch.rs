@@ -0,0 +1,124 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+
+use super::{by_topic, keybase_message, ChannelConfig, KeybaseApi};
+
+/// ```toml
+/// [keybase.batch]
+/// every_secs = 900
+/// max_entries = 20
+/// ```
+///
+/// Log lines are held and posted together, one message per topic, once the oldest has waited
+/// `every_secs` or `max_entries` are held. `every_secs = 0` posts each line as it comes.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatchConfig {
+    pub every_secs: u64,
+    /// `0` for no limit
+    pub max_entries: usize,
+}
+
+/// ```toml
+/// [keybase]
+/// batch = { every_secs = 900 }
+/// ```
+///
+/// How the Keybase topics are posted to. Alerts and errors always go out right away, `batch`
+/// only holds back the log lines. `channels` route by severity, see `ChannelConfig`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeybaseConfig {
+    pub batch: BatchConfig,
+    pub channels: Vec<ChannelConfig>,
+}
+
+/// Log lines held for one combined post, see `BatchConfig`
+pub struct Batch {
+    config: BatchConfig,
+    held: Vec<KeybaseApi>,
+    since: Option<DateTime<Utc>>,
+}
+
+impl Batch {
+    pub fn new(config: BatchConfig) -> Self {
+        Batch {
+            config,
+            held: vec![],
+            since: None,
+        }
+    }
+
+    pub fn reconfigure(&mut self, config: BatchConfig) {
+        self.config = config;
+    }
+
+    /// Holds `msg`, returning what to post now: `msg` itself when not batching, everything held
+    /// once that is `max_entries`
+    pub fn push(&mut self, msg: KeybaseApi, now: DateTime<Utc>) -> Vec<KeybaseApi> {
+        if self.config.every_secs == 0 {
+            return vec![msg];
+        }
+        self.held.push(msg);
+        self.since.get_or_insert(now);
+        match self.config.max_entries > 0 && self.held.len() >= self.config.max_entries {
+            true => self.take(),
+            false => vec![],
+        }
+    }
+
+    /// Everything held, once the oldest has waited `every_secs`
+    pub fn due(&mut self, now: DateTime<Utc>) -> Vec<KeybaseApi> {
+        let every = Duration::seconds(self.config.every_secs as i64);
+        match self.since.is_some_and(|since| now - since >= every) {
+            true => self.take(),
+            false => vec![],
+        }
+    }
+
+    /// Everything held, one message per topic with a line per entry
+    pub fn take(&mut self) -> Vec<KeybaseApi> {
+        self.since = None;
+        let held = std::mem::take(&mut self.held);
+        by_topic(&held)
+            .into_iter()
+            .map(|(topic, lines)| keybase_message(topic, lines.join("\n") + "\n"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn log_lines_go_out_together() {
+        let log = |body: &str| keybase_message("pcta-logs", body.to_string());
+        let mut off = Batch::new(BatchConfig::default());
+        assert_eq!(off.push(log("a"), utc("2024-03-01T17:00:00Z")).len(), 1);
+
+        let mut batch = Batch::new(BatchConfig {
+            every_secs: 600,
+            max_entries: 3,
+        });
+        assert!(batch.push(log("a"), utc("2024-03-01T17:00:00Z")).is_empty());
+        assert!(batch.push(log("b"), utc("2024-03-01T17:05:00Z")).is_empty());
+        assert!(batch.due(utc("2024-03-01T17:09:59Z")).is_empty());
+        let due = batch.due(utc("2024-03-01T17:10:00Z"));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].body(), "a\nb\n");
+
+        for body in ["c", "d"] {
+            assert!(batch
+                .push(log(body), utc("2024-03-01T17:11:00Z"))
+                .is_empty());
+        }
+        let full = batch.push(log("e"), utc("2024-03-01T17:12:00Z"));
+        assert_eq!(full[0].body(), "c\nd\ne\n");
+        assert!(batch.due(utc("2024-03-01T18:00:00Z")).is_empty());
+    }
+}
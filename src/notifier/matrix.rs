@@ -0,0 +1,208 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::Notifier;
+use crate::format::{self, MessageFormatter, PlainText, Report, Templates};
+
+/// Sends `m.room.message` events to one room through the client-server API, as whichever user
+/// the access token belongs to
+pub struct Matrix {
+    client: Client,
+    homeserver: Url,
+    room_id: String,
+    access_token: String,
+    /// Transaction ids only have to be unique per access token, which the start time plus a
+    /// counter is
+    txn: AtomicU64,
+    started: i64,
+    templates: Templates,
+}
+
+impl Matrix {
+    pub fn new(
+        client: Client,
+        homeserver: &str,
+        room_id: String,
+        access_token: String,
+    ) -> anyhow::Result<Self> {
+        let url = Url::parse(homeserver)
+            .with_context(|| format!("Invalid Matrix homeserver '{}'", homeserver))?;
+        // `matrix.org:8448` parses too, as a URL with the scheme `matrix.org`
+        if !matches!(url.scheme(), "http" | "https") || url.cannot_be_a_base() {
+            anyhow::bail!(
+                "Invalid Matrix homeserver '{}', expected an http(s) URL like https://matrix.org",
+                homeserver
+            );
+        }
+        Ok(Matrix {
+            client,
+            homeserver: url,
+            room_id,
+            access_token,
+            txn: AtomicU64::new(0),
+            started: chrono::Utc::now().timestamp_millis(),
+            templates: Templates::default(),
+        })
+    }
+
+    pub fn templates(mut self, templates: Templates) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    fn send_url(&self) -> anyhow::Result<Url> {
+        let txn = format!(
+            "pcta-{}-{}",
+            self.started,
+            self.txn.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut url = self.homeserver.clone();
+        // Room ids carry `!` and `:`, the segments get percent-encoded
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Matrix homeserver '{}' has no path", self.homeserver))?
+            .pop_if_empty()
+            .extend([
+                "_matrix",
+                "client",
+                "v3",
+                "rooms",
+                &self.room_id,
+                "send",
+                "m.room.message",
+                &txn,
+            ]);
+        Ok(url)
+    }
+}
+
+/// The HTML body Matrix clients render, the plain text one is the fallback
+pub fn html(report: &Report, now: &str) -> String {
+    match report {
+        Report::Open {
+            label,
+            dates,
+            apply_url,
+            ..
+        } => {
+            let items: String = dates
+                .iter()
+                .map(|(date, remaining)| {
+                    let link = match format::apply_link(apply_url, *date) {
+                        Some(link) => format!(" <a href=\"{}\">apply</a>", escape(&link)),
+                        None => String::new(),
+                    };
+                    format!(
+                        "<li><code>{}</code> ({}): {}{}</li>",
+                        date,
+                        date.format("%a"),
+                        remaining,
+                        link
+                    )
+                })
+                .collect();
+            format!(
+                "<strong>{} new start dates open at the {}!</strong><ul>{}</ul><em>Scraped at {}</em>",
+                dates.len(),
+                escape(label),
+                items,
+                now
+            )
+        }
+        Report::Nothing { label } => format!("Nothing open at {}", escape(label)),
+        Report::Failed { label, error } => format!(
+            "<strong>Scrape of {} failed</strong><pre><code>{}</code></pre>",
+            escape(label),
+            escape(&format!("{:#}", error))
+        ),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[async_trait]
+impl Notifier for Matrix {
+    fn name(&self) -> &'static str {
+        "matrix"
+    }
+
+    async fn notify(&self, report: &Report<'_>, now: &str) -> anyhow::Result<()> {
+        let event = match self.templates.render(report, now) {
+            Some(body) => json!({"msgtype": "m.text", "body": body}),
+            None => json!({
+                "msgtype": "m.text",
+                "body": PlainText.format(report, now),
+                "format": "org.matrix.custom.html",
+                "formatted_body": html(report, now),
+            }),
+        };
+        self.client
+            .put(self.send_url()?)
+            .bearer_auth(&self.access_token)
+            .json(&event)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .with_context(|| format!("Matrix send to room {} failed", self.room_id))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn sends_to_the_room_with_a_fresh_transaction() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path_regex(
+                r"^/_matrix/client/v3/rooms/!room:example\.org/send/m\.room\.message/pcta-\d+-\d+$",
+            ))
+            .and(header("Authorization", "Bearer syt_token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"event_id":"$1"}"#))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let matrix = Matrix::new(
+            Client::new(),
+            &server.uri(),
+            "!room:example.org".to_string(),
+            "syt_token".to_string(),
+        )
+        .unwrap();
+        let report = Report::Nothing {
+            label: "Mexican Border",
+        };
+        matrix.notify(&report, "now").await.unwrap();
+        matrix.notify(&report, "now").await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_ne!(requests[0].url.path(), requests[1].url.path());
+        let event: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(event["msgtype"], "m.text");
+    }
+
+    #[test]
+    fn homeservers_must_be_http_urls() {
+        let matrix = |homeserver| {
+            Matrix::new(
+                Client::new(),
+                homeserver,
+                "!room:example.org".to_string(),
+                "syt_token".to_string(),
+            )
+        };
+        assert!(matrix("matrix.org:8448").is_err());
+        assert!(matrix("ftp://matrix.org").is_err());
+        assert!(matrix("https://matrix.org:8448").is_ok());
+    }
+}
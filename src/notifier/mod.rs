@@ -0,0 +1,471 @@
+mod batch;
+#[cfg(feature = "matrix")]
+mod matrix;
+#[cfg(feature = "ntfy")]
+mod ntfy;
+#[cfg(feature = "pushover")]
+mod pushover;
+mod route;
+#[cfg(feature = "slack")]
+mod slack;
+#[cfg(feature = "webhook")]
+mod webhook;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::config::NotifierConfig;
+#[cfg(any(
+    feature = "webhook",
+    feature = "ntfy",
+    feature = "pushover",
+    feature = "slack",
+    feature = "matrix"
+))]
+use crate::config::NotifierKind;
+use crate::format::{MessageFormatter, Report};
+
+pub use batch::{Batch, BatchConfig, KeybaseConfig};
+#[cfg(feature = "matrix")]
+pub use matrix::Matrix;
+#[cfg(feature = "ntfy")]
+pub use ntfy::Ntfy;
+#[cfg(feature = "pushover")]
+pub use pushover::Pushover;
+pub use route::{set_channels, ChannelConfig, Severity, DEBUG_TOPIC};
+#[cfg(feature = "slack")]
+pub use slack::{Destination, Slack};
+#[cfg(feature = "webhook")]
+pub use webhook::Webhook;
+
+/// Keybase team every topic lives in
+pub const TEAM: &str = "jry.zed";
+
+/// Tries of one Keybase post, a second apart and doubling, before it is given up on
+const KEYBASE_ATTEMPTS: u32 = 3;
+
+/// The CLI every post goes through. Tests never reach the real team, every post they make fails
+/// like it would without Keybase installed.
+#[cfg(not(test))]
+const KEYBASE_CLI: &str = "keybase";
+#[cfg(test)]
+const KEYBASE_CLI: &str = "pcta-test-keybase-not-installed";
+
+/// Somewhere besides Keybase that alerts and errors are delivered to
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether this lands on someone's phone, and so waits out the quiet hours
+    fn pings_people(&self) -> bool {
+        true
+    }
+
+    async fn notify(&self, report: &Report<'_>, now: &str) -> anyhow::Result<()>;
+}
+
+/// The extra notifiers listed in the config. Their requests don't go through the scraping
+/// proxies, the permit sites never see them. A kind left out of the build is an error.
+pub fn from_config(
+    configs: &[NotifierConfig],
+    #[cfg_attr(
+        not(any(
+            feature = "webhook",
+            feature = "ntfy",
+            feature = "pushover",
+            feature = "slack",
+            feature = "matrix"
+        )),
+        allow(unused_variables)
+    )]
+    client: Client,
+) -> anyhow::Result<Vec<Box<dyn Notifier>>> {
+    configs
+        .iter()
+        .map(|config| -> anyhow::Result<Box<dyn Notifier>> {
+            match &config.kind {
+                #[cfg(feature = "webhook")]
+                NotifierKind::Webhook { url, secret } => Ok(Box::new(Webhook::new(
+                    client.clone(),
+                    url.expose()?,
+                    secret.as_ref().map(|s| s.expose()).transpose()?,
+                ))),
+                #[cfg(feature = "ntfy")]
+                NotifierKind::Ntfy {
+                    server,
+                    topic,
+                    token,
+                } => Ok(Box::new(
+                    Ntfy::new(
+                        client.clone(),
+                        server,
+                        &topic.expose()?,
+                        token.as_ref().map(|s| s.expose()).transpose()?,
+                    )
+                    .templates(config.templates.clone()),
+                )),
+                #[cfg(feature = "pushover")]
+                NotifierKind::Pushover { token, user } => Ok(Box::new(
+                    Pushover::new(
+                        client.clone(),
+                        pushover::API_URL,
+                        token.expose()?,
+                        user.expose()?,
+                    )
+                    .templates(config.templates.clone()),
+                )),
+                #[cfg(feature = "slack")]
+                NotifierKind::Slack {
+                    webhook_url,
+                    errors_webhook_url,
+                    token,
+                    channel,
+                    errors_channel,
+                } => {
+                    let (alerts, errors) = match (webhook_url, token) {
+                        (Some(url), None) => (
+                            Destination::Webhook(url.expose()?),
+                            Destination::Webhook(
+                                errors_webhook_url.as_ref().unwrap_or(url).expose()?,
+                            ),
+                        ),
+                        (None, Some(token)) => {
+                            let token = token.expose()?;
+                            crate::log::redact(channel);
+                            crate::log::redact(errors_channel);
+                            (
+                                Destination::Channel {
+                                    token: token.clone(),
+                                    channel: channel.clone(),
+                                },
+                                Destination::Channel {
+                                    token,
+                                    channel: errors_channel.clone(),
+                                },
+                            )
+                        }
+                        _ => anyhow::bail!("Slack notifier needs one of `webhook_url` or `token`"),
+                    };
+                    Ok(Box::new(
+                        Slack::new(client.clone(), slack::API_URL, alerts, errors)
+                            .templates(config.templates.clone()),
+                    ))
+                }
+                #[cfg(feature = "matrix")]
+                NotifierKind::Matrix {
+                    homeserver,
+                    room_id,
+                    access_token,
+                } => {
+                    crate::log::redact(room_id);
+                    Ok(Box::new(
+                        Matrix::new(
+                            client.clone(),
+                            homeserver,
+                            room_id.clone(),
+                            access_token.expose()?,
+                        )?
+                        .templates(config.templates.clone()),
+                    ))
+                }
+                #[allow(unreachable_patterns)]
+                kind => Err(anyhow::anyhow!(
+                    "pcta was built without {} notifiers, rebuild it with `--features {}`",
+                    kind.name(),
+                    kind.name()
+                )),
+            }
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Channel {
+    name: String,
+    members_type: String,
+    topic_name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Message {
+    body: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Options {
+    channel: Channel,
+    message: Message,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Params {
+    options: Options,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct KeybaseApi {
+    method: String,
+    params: Params,
+}
+
+impl KeybaseApi {
+    pub fn topic(&self) -> &str {
+        &self.params.options.channel.topic_name
+    }
+
+    pub fn body(&self) -> &str {
+        &self.params.options.message.body
+    }
+}
+
+pub fn keybase_message(topic: &str, body: String) -> KeybaseApi {
+    KeybaseApi {
+        method: "send".to_string(),
+        params: Params {
+            options: Options {
+                channel: Channel {
+                    name: TEAM.to_string(),
+                    members_type: "team".to_string(),
+                    topic_name: topic.to_string(),
+                },
+                message: Message { body },
+            },
+        },
+    }
+}
+
+/// The bodies of `msgs` grouped by topic, topics in the order they first appear
+fn by_topic(msgs: &[KeybaseApi]) -> Vec<(&str, Vec<&str>)> {
+    let mut topics: Vec<(&str, Vec<&str>)> = vec![];
+    for msg in msgs {
+        let body = msg.body().trim_end();
+        match topics.iter_mut().find(|(topic, _)| *topic == msg.topic()) {
+            Some((_, bodies)) => bodies.push(body),
+            None => topics.push((msg.topic(), vec![body])),
+        }
+    }
+    topics
+}
+
+/// Rolls alerts held back during quiet hours into one message per topic, in the order they
+/// were first held
+pub fn morning_summary(held: &[KeybaseApi], now: &str) -> Vec<KeybaseApi> {
+    by_topic(held)
+        .into_iter()
+        .map(|(topic, alerts)| {
+            let body = format!(
+                "*Morning summary*: {} alerts held during quiet hours, as of `{}`\n\n{}\n",
+                alerts.len(),
+                now,
+                alerts.join("\n\n---\n\n")
+            );
+            keybase_message(topic, body)
+        })
+        .collect()
+}
+
+/// One message per topic for the alerts of a pass, so watches opening together ping once
+pub fn combined(alerts: Vec<KeybaseApi>) -> Vec<KeybaseApi> {
+    if by_topic(&alerts)
+        .iter()
+        .all(|(_, bodies)| bodies.len() == 1)
+    {
+        return alerts;
+    }
+    by_topic(&alerts)
+        .into_iter()
+        .map(|(topic, bodies)| keybase_message(topic, bodies.join("\n\n---\n\n") + "\n"))
+        .collect()
+}
+
+/// Posts to the team through the `keybase` CLI, or with `dry_run` only prints what it would post
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Keybase {
+    pub dry_run: bool,
+}
+
+impl Keybase {
+    pub async fn send(&self, topic: &str, body: String) {
+        self.post(&keybase_message(topic, body)).await
+    }
+
+    /// Posts `msg` like `deliver`, logging what didn't go through. Keybase being down or a topic
+    /// that doesn't exist must never stop the scraper.
+    pub async fn post(&self, msg: &KeybaseApi) {
+        if let Err(e) = self.deliver(msg).await {
+            crate::info!("{:#}", e);
+        }
+    }
+
+    /// Posts `msg` to the topics `[[keybase.channels]]` route it to, stopping at the first that
+    /// fails
+    pub async fn deliver(&self, msg: &KeybaseApi) -> Result<(), PostFailure> {
+        for topic in route::topics(msg.topic()) {
+            let msg = keybase_message(&topic, msg.body().to_string());
+            if self.dry_run {
+                crate::info!("[dry run] Would post to #{}:\n{}", topic, msg.body());
+                continue;
+            }
+            keybase_post(&msg).await?;
+        }
+        Ok(())
+    }
+}
+
+/// What `keybase chat api` prints, only the error
+#[derive(Debug, Deserialize)]
+struct ApiReply {
+    error: Option<ApiError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    code: i64,
+    message: String,
+}
+
+/// Why a post didn't go through, and whether trying again could help
+#[derive(Debug)]
+pub enum PostFailure {
+    /// The CLI couldn't reach the Keybase service, which comes and goes
+    Transient(anyhow::Error),
+    /// The API refused the message, or there is no CLI to run
+    Refused(anyhow::Error),
+}
+
+impl std::fmt::Display for PostFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostFailure::Transient(e) | PostFailure::Refused(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for PostFailure {}
+
+/// Reads how one `keybase chat api` call went from its exit status and output
+fn check_reply(success: bool, stdout: &[u8], stderr: &[u8]) -> Result<(), PostFailure> {
+    let reply = serde_json::from_slice::<ApiReply>(stdout);
+    if let Ok(ApiReply { error: Some(e) }) = reply {
+        let err = anyhow::anyhow!("Keybase API error {}: {}", e.code, e.message);
+        return Err(PostFailure::Refused(err));
+    }
+    match (success, reply) {
+        (true, Ok(_)) => Ok(()),
+        (true, Err(e)) => Err(PostFailure::Transient(
+            anyhow::Error::new(e).context("Unreadable reply from `keybase chat api`"),
+        )),
+        (false, _) => Err(PostFailure::Transient(anyhow::anyhow!(
+            "`keybase chat api` failed: {}",
+            String::from_utf8_lossy(stderr).trim()
+        ))),
+    }
+}
+
+async fn keybase_call(msg_json: &str) -> Result<(), PostFailure> {
+    let out = crate::exec::command(KEYBASE_CLI)
+        .arg("chat")
+        .arg("api")
+        .arg("-m")
+        .arg(msg_json)
+        .output()
+        .await
+        .context("Failed to call keybase API process (err)")
+        .map_err(PostFailure::Refused)?;
+    check_reply(out.status.success(), &out.stdout, &out.stderr)
+}
+
+/// Posts `msg`, retrying while the service is unreachable. What the API refuses isn't retried.
+async fn keybase_post(msg: &KeybaseApi) -> Result<(), PostFailure> {
+    let msg_json = serde_json::to_string(msg)
+        .context("Failed to encode the Keybase message")
+        .map_err(PostFailure::Refused)?;
+    let mut attempt = 1;
+    loop {
+        let err = match keybase_call(&msg_json).await {
+            Ok(()) => return Ok(()),
+            Err(PostFailure::Transient(e)) if attempt < KEYBASE_ATTEMPTS => e,
+            Err(PostFailure::Transient(e)) => {
+                return Err(PostFailure::Transient(
+                    e.context(format!("Failed to post to #{}", msg.topic())),
+                ))
+            }
+            Err(PostFailure::Refused(e)) => {
+                return Err(PostFailure::Refused(
+                    e.context(format!("Failed to post to #{}", msg.topic())),
+                ))
+            }
+        };
+        let delay = std::time::Duration::from_secs(1 << (attempt - 1));
+        crate::info!(
+            "Posting to #{} failed (attempt {}/{}), retrying in {}s: {:#}",
+            msg.topic(),
+            attempt,
+            KEYBASE_ATTEMPTS,
+            delay.as_secs(),
+            err
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// The Keybase message for one report: open dates to `channel`, nothing open to the logs and
+/// errors to the errors topic
+pub fn handle_result(
+    report: &Report,
+    channel: &str,
+    now: &str,
+    formatter: &dyn MessageFormatter,
+) -> anyhow::Result<KeybaseApi> {
+    let topic = match report {
+        Report::Open { dates: [], .. } | Report::Nothing { .. } => "pcta-logs",
+        Report::Open { .. } => channel,
+        Report::Failed { .. } => "pcta-errors",
+    };
+    let msg = formatter.format(report, now);
+    crate::info!("{}", msg);
+    Ok(keybase_message(topic, msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keybase_replies_are_checked() {
+        assert!(check_reply(true, br#"{"result":{"message":"message sent"}}"#, b"").is_ok());
+        let refused = br#"{"error":{"code":0,"message":"no conversations matched"}}"#;
+        match check_reply(true, refused, b"") {
+            Err(PostFailure::Refused(e)) => assert_eq!(
+                e.to_string(),
+                "Keybase API error 0: no conversations matched"
+            ),
+            other => panic!("{:?}", other),
+        }
+        let down = b"ERROR Keybase isn't running.";
+        assert!(matches!(
+            check_reply(false, b"", down),
+            Err(PostFailure::Transient(_))
+        ));
+    }
+
+    #[test]
+    fn alerts_of_a_pass_are_combined_per_topic() {
+        let alerts = vec![
+            keybase_message("pcta-alerts", "April open\n".to_string()),
+            keybase_message("pcta-may", "May open\n".to_string()),
+            keybase_message("pcta-alerts", "Canadian border open\n".to_string()),
+        ];
+        let combined = combined(alerts);
+        assert_eq!(combined.len(), 2);
+        assert_eq!(combined[0].topic(), "pcta-alerts");
+        assert_eq!(
+            combined[0].body(),
+            "April open\n\n---\n\nCanadian border open\n"
+        );
+        assert_eq!(combined[1].body(), "May open\n");
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::Client;
+
+use super::Notifier;
+use crate::format::{MessageFormatter, PlainText, Report, Templated, Templates};
+
+/// Pushes to an ntfy topic, `https://ntfy.sh` or a self-hosted server. Open dates go out at
+/// high priority so they get through the phone's do-not-disturb, failures at the default.
+pub struct Ntfy {
+    client: Client,
+    /// `{server}/{topic}`
+    url: String,
+    token: Option<String>,
+    templates: Templates,
+}
+
+impl Ntfy {
+    pub fn new(client: Client, server: &str, topic: &str, token: Option<String>) -> Self {
+        Ntfy {
+            client,
+            url: format!("{}/{}", server.trim_end_matches('/'), topic),
+            token,
+            templates: Templates::default(),
+        }
+    }
+
+    pub fn templates(mut self, templates: Templates) -> Self {
+        self.templates = templates;
+        self
+    }
+}
+
+#[async_trait]
+impl Notifier for Ntfy {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    async fn notify(&self, report: &Report<'_>, now: &str) -> anyhow::Result<()> {
+        let (title, priority, tags) = match report {
+            Report::Open { .. } => ("PCTA permits open", "high", "tada"),
+            _ => ("PCTA scraper", "default", "warning"),
+        };
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Title", title)
+            .header("Priority", priority)
+            .header("Tags", tags);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        request
+            .body(
+                Templated {
+                    templates: &self.templates,
+                    fallback: PlainText,
+                }
+                .format(report, now),
+            )
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .context("ntfy publish failed")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::Client;
+
+use super::Notifier;
+use crate::format::{MessageFormatter, PlainText, Report, Templated, Templates};
+
+pub const API_URL: &str = "https://api.pushover.net/1/messages.json";
+
+/// Pushes through Pushover with an application token and a user (or group) key. Open dates
+/// are sent at high priority, which bypasses the user's quiet hours on the phone.
+pub struct Pushover {
+    client: Client,
+    url: String,
+    token: String,
+    user: String,
+    templates: Templates,
+}
+
+impl Pushover {
+    pub fn new(client: Client, url: &str, token: String, user: String) -> Self {
+        Pushover {
+            client,
+            url: url.to_string(),
+            token,
+            user,
+            templates: Templates::default(),
+        }
+    }
+
+    pub fn templates(mut self, templates: Templates) -> Self {
+        self.templates = templates;
+        self
+    }
+}
+
+#[async_trait]
+impl Notifier for Pushover {
+    fn name(&self) -> &'static str {
+        "pushover"
+    }
+
+    async fn notify(&self, report: &Report<'_>, now: &str) -> anyhow::Result<()> {
+        let (title, priority) = match report {
+            Report::Open { .. } => ("PCTA permits open", "1"),
+            _ => ("PCTA scraper", "0"),
+        };
+        let message = Templated {
+            templates: &self.templates,
+            fallback: PlainText,
+        }
+        .format(report, now);
+        let form = [
+            ("token", self.token.as_str()),
+            ("user", self.user.as_str()),
+            ("title", title),
+            ("priority", priority),
+            ("message", message.as_str()),
+        ];
+        self.client
+            .post(&self.url)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .context("Pushover message failed")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subscription::Subscriber;
+    use chrono::NaiveDate;
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn posts_an_authenticated_form() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("token=app-token"))
+            .and(body_string_contains("user=user-key"))
+            .and(body_string_contains("priority=1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"status":1}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let pushover = Pushover::new(
+            Client::new(),
+            &server.uri(),
+            "app-token".to_string(),
+            "user-key".to_string(),
+        );
+        let subscribers = Subscriber::defaults();
+        let report = Report::Open {
+            label: "Mexican Border",
+            dates: &[(NaiveDate::from_ymd_opt(2023, 4, 14).unwrap(), 13)],
+            subscribers: &subscribers,
+            apply_url: "",
+            paces: &[],
+        };
+        pushover.notify(&report, "now").await.unwrap();
+    }
+}
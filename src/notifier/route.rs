@@ -0,0 +1,120 @@
+use serde::Deserialize;
+use std::sync::RwLock;
+
+/// Where the scraper posts what only `[[keybase.channels]]` with `min_severity = "debug"` want
+pub const DEBUG_TOPIC: &str = "pcta-debug";
+
+/// How much a Keybase message matters, least first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// What every pass does, unchanged calendars included
+    Debug,
+    /// What changed about the scraper, `pcta-logs`
+    Info,
+    /// Dates coming open, a watch's channel
+    Alert,
+    /// Failures, `pcta-errors`
+    Error,
+}
+
+impl Severity {
+    /// What a message for one of the scraper's own topics is. Any other topic is a watch's.
+    pub fn of(topic: &str) -> Severity {
+        match topic {
+            DEBUG_TOPIC => Severity::Debug,
+            "pcta-logs" => Severity::Info,
+            "pcta-errors" => Severity::Error,
+            _ => Severity::Alert,
+        }
+    }
+}
+
+/// ```toml
+/// [[keybase.channels]]
+/// topic = "pcta-everything"
+/// min_severity = "info"
+///
+/// [[keybase.channels]]
+/// topic = "pcta-phone"
+/// min_severity = "alert"
+/// ```
+///
+/// Once any are listed, messages go to every channel taking their severity instead of the
+/// built-in topics. Alerts of a watch with a `channel` of its own still go there too.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelConfig {
+    pub topic: String,
+    pub min_severity: Severity,
+}
+
+/// The channels every Keybase post is routed through, set once for the process
+static CHANNELS: RwLock<Vec<ChannelConfig>> = RwLock::new(vec![]);
+
+pub fn set_channels(channels: &[ChannelConfig]) {
+    *CHANNELS.write().unwrap() = channels.to_vec();
+}
+
+/// The topics a message for `topic` goes to. Without channels that is `topic`, debug
+/// messages excepted.
+pub fn topics(topic: &str) -> Vec<String> {
+    route(&CHANNELS.read().unwrap(), topic)
+}
+
+fn route(channels: &[ChannelConfig], topic: &str) -> Vec<String> {
+    let severity = Severity::of(topic);
+    if channels.is_empty() {
+        return match severity {
+            Severity::Debug => vec![],
+            _ => vec![topic.to_string()],
+        };
+    }
+    let mut topics = vec![];
+    // A watch's own channel was picked for it, not for a severity
+    if severity == Severity::Alert && topic != "pcta-alerts" {
+        topics.push(topic.to_string());
+    }
+    for channel in channels.iter().filter(|c| c.min_severity <= severity) {
+        if !topics.contains(&channel.topic) {
+            topics.push(channel.topic.clone());
+        }
+    }
+    topics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_take_what_is_severe_enough() {
+        assert_eq!(route(&[], "pcta-errors"), vec!["pcta-errors"]);
+        assert!(route(&[], DEBUG_TOPIC).is_empty());
+
+        let channels = [
+            ChannelConfig {
+                topic: "pcta-everything".to_string(),
+                min_severity: Severity::Debug,
+            },
+            ChannelConfig {
+                topic: "pcta-phone".to_string(),
+                min_severity: Severity::Alert,
+            },
+        ];
+        assert_eq!(route(&channels, DEBUG_TOPIC), vec!["pcta-everything"]);
+        assert_eq!(route(&channels, "pcta-logs"), vec!["pcta-everything"]);
+        assert_eq!(
+            route(&channels, "pcta-alerts"),
+            vec!["pcta-everything", "pcta-phone"]
+        );
+        assert_eq!(
+            route(&channels, "pcta-sobo"),
+            vec!["pcta-sobo", "pcta-everything", "pcta-phone"]
+        );
+        assert_eq!(
+            route(&channels, "pcta-errors"),
+            vec!["pcta-everything", "pcta-phone"]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::Notifier;
+use crate::format::{self, MessageFormatter, PlainText, Report, Templates};
+
+pub const API_URL: &str = "https://slack.com/api/chat.postMessage";
+
+/// Block Kit allows at most this many fields in one section
+const FIELDS_PER_SECTION: usize = 10;
+
+/// Where one severity of message is posted
+pub enum Destination {
+    /// An incoming webhook, which is tied to its channel
+    Webhook(String),
+    /// A channel posted to with a bot token
+    Channel { token: String, channel: String },
+}
+
+/// Posts Block Kit messages to Slack, alerts and failures to their own destinations
+pub struct Slack {
+    client: Client,
+    api_url: String,
+    alerts: Destination,
+    errors: Destination,
+    templates: Templates,
+}
+
+impl Slack {
+    pub fn new(client: Client, api_url: &str, alerts: Destination, errors: Destination) -> Self {
+        Slack {
+            client,
+            api_url: api_url.to_string(),
+            alerts,
+            errors,
+            templates: Templates::default(),
+        }
+    }
+
+    pub fn templates(mut self, templates: Templates) -> Self {
+        self.templates = templates;
+        self
+    }
+}
+
+/// What `chat.postMessage` answers, it reports errors with a 200
+#[derive(Deserialize)]
+struct ApiResponse {
+    ok: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// A header, the details and a footer with the scrape time
+pub fn blocks(report: &Report, now: &str) -> Value {
+    let header =
+        |text: String| json!({"type": "header", "text": {"type": "plain_text", "text": text}});
+    let footer = json!({
+        "type": "context",
+        "elements": [{"type": "mrkdwn", "text": format!("Scraped at `{}`", now)}],
+    });
+    let mut blocks = vec![];
+    match report {
+        Report::Open {
+            label,
+            dates,
+            apply_url,
+            ..
+        } => {
+            blocks.push(header(format!("{} new start dates open", dates.len())));
+            blocks.push(json!({
+                "type": "section",
+                "text": {"type": "mrkdwn", "text": format!("*{}*", label)},
+            }));
+            for chunk in dates.chunks(FIELDS_PER_SECTION) {
+                let fields: Vec<Value> = chunk
+                    .iter()
+                    .map(|(date, remaining)| {
+                        let mut text =
+                            format!("*{}* ({})\n{} left", date, date.format("%a"), remaining);
+                        if let Some(link) = format::apply_link(apply_url, *date) {
+                            text += &format!(" - <{}|apply>", link);
+                        }
+                        json!({"type": "mrkdwn", "text": text})
+                    })
+                    .collect();
+                blocks.push(json!({"type": "section", "fields": fields}));
+            }
+        }
+        Report::Nothing { label } => {
+            blocks.push(header(format!("Nothing open at {}", label)));
+        }
+        Report::Failed { label, error } => {
+            blocks.push(header(format!("Scrape of {} failed", label)));
+            blocks.push(json!({
+                "type": "section",
+                "text": {"type": "mrkdwn", "text": format!("```\n{:#}\n```", error)},
+            }));
+        }
+    }
+    blocks.push(footer);
+    Value::Array(blocks)
+}
+
+#[async_trait]
+impl Notifier for Slack {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn notify(&self, report: &Report<'_>, now: &str) -> anyhow::Result<()> {
+        let destination = match report {
+            Report::Failed { .. } => &self.errors,
+            _ => &self.alerts,
+        };
+        // A template replaces the blocks too, it is all the user wants to see
+        let mut message = match self.templates.render(report, now) {
+            Some(text) => json!({
+                "text": text,
+                "blocks": [{"type": "section", "text": {"type": "mrkdwn", "text": text}}],
+            }),
+            None => json!({
+                // Shown in push notifications and by clients that can't render blocks
+                "text": PlainText.format(report, now),
+                "blocks": blocks(report, now),
+            }),
+        };
+        match destination {
+            Destination::Webhook(url) => {
+                self.client
+                    .post(url)
+                    .json(&message)
+                    .send()
+                    .await
+                    .and_then(|res| res.error_for_status())
+                    .context("Slack webhook failed")?;
+            }
+            Destination::Channel { token, channel } => {
+                message["channel"] = json!(channel);
+                let res: ApiResponse = self
+                    .client
+                    .post(&self.api_url)
+                    .bearer_auth(token)
+                    .json(&message)
+                    .send()
+                    .await
+                    .and_then(|res| res.error_for_status())
+                    .context("Slack chat.postMessage failed")?
+                    .json()
+                    .await
+                    .context("Slack answered with something other than JSON")?;
+                if !res.ok {
+                    anyhow::bail!(
+                        "Slack refused the message to {}: {}",
+                        channel,
+                        res.error.unwrap_or_default()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subscription::Subscriber;
+    use chrono::NaiveDate;
+    use wiremock::matchers::{body_partial_json, header, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn dates_are_split_into_sections_of_ten_fields() {
+        let dates: Vec<(NaiveDate, u64)> = (1..=12)
+            .map(|d| (NaiveDate::from_ymd_opt(2023, 4, d).unwrap(), 3))
+            .collect();
+        let subscribers = Subscriber::defaults();
+        let report = Report::Open {
+            label: "Mexican Border",
+            dates: &dates,
+            subscribers: &subscribers,
+            apply_url: "",
+            paces: &[],
+        };
+        let blocks = blocks(&report, "now");
+        let blocks = blocks.as_array().unwrap();
+        assert_eq!(blocks[0]["type"], "header");
+        assert_eq!(blocks[2]["fields"].as_array().unwrap().len(), 10);
+        assert_eq!(blocks[3]["fields"].as_array().unwrap().len(), 2);
+        assert_eq!(blocks[4]["type"], "context");
+    }
+
+    #[tokio::test]
+    async fn failures_go_to_the_errors_channel() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("Authorization", "Bearer xoxb-1"))
+            .and(body_partial_json(json!({"channel": "#pcta-errors"})))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"ok":true}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(r#"{"ok":false,"error":"channel_not_found"}"#),
+            )
+            .mount(&server)
+            .await;
+        let channel = |channel: &str| Destination::Channel {
+            token: "xoxb-1".to_string(),
+            channel: channel.to_string(),
+        };
+        let slack = Slack::new(
+            Client::new(),
+            &server.uri(),
+            channel("#nowhere"),
+            channel("#pcta-errors"),
+        );
+        let error = anyhow::anyhow!("connection reset");
+        let failed = Report::Failed {
+            label: "Mexican Border",
+            error: &error,
+        };
+        slack.notify(&failed, "now").await.unwrap();
+        let err = slack
+            .notify(&Report::Nothing { label: "x" }, "now")
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("channel_not_found"));
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use sha2::Sha256;
+
+use super::Notifier;
+use crate::format::{Json, MessageFormatter, Report};
+
+/// Header carrying `sha256=<hex HMAC of the body>` when the webhook has a secret
+pub const SIGNATURE_HEADER: &str = "X-Pcta-Signature";
+
+/// POSTs every report as a JSON event to a URL of the user's choosing, for Home Assistant, n8n
+/// and the like. With a secret the body is signed so the receiver can tell it came from us.
+pub struct Webhook {
+    client: Client,
+    url: String,
+    secret: Option<String>,
+}
+
+impl Webhook {
+    pub fn new(client: Client, url: String, secret: Option<String>) -> Self {
+        Webhook {
+            client,
+            url,
+            secret,
+        }
+    }
+}
+
+/// Lowercase hex HMAC-SHA256 of `body`
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[async_trait]
+impl Notifier for Webhook {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn pings_people(&self) -> bool {
+        false
+    }
+
+    async fn notify(&self, report: &Report<'_>, now: &str) -> anyhow::Result<()> {
+        let body = Json.format(report, now);
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json");
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, &body)));
+        }
+        request
+            .body(body)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .context("Webhook POST failed")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subscription::Subscriber;
+    use chrono::NaiveDate;
+    use wiremock::matchers::{header_exists, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn signs_the_event_it_posts() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header_exists(SIGNATURE_HEADER))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let webhook = Webhook::new(Client::new(), server.uri(), Some("hunter2".to_string()));
+        let subscribers = Subscriber::defaults();
+        let report = Report::Open {
+            label: "Mexican Border",
+            dates: &[(NaiveDate::from_ymd_opt(2023, 4, 14).unwrap(), 13)],
+            subscribers: &subscribers,
+            apply_url: "",
+            paces: &[],
+        };
+        webhook.notify(&report, "now").await.unwrap();
+
+        let request = &server.received_requests().await.unwrap()[0];
+        let body = String::from_utf8(request.body.clone()).unwrap();
+        let (_, signature) = request
+            .headers
+            .iter()
+            .find(|(name, _)| name.as_str().eq_ignore_ascii_case(SIGNATURE_HEADER))
+            .unwrap();
+        assert_eq!(
+            signature.last().as_str(),
+            format!("sha256={}", sign("hunter2", &body))
+        );
+        let event: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(event["event"], "availability");
+    }
+
+    #[test]
+    fn signature_matches_a_known_vector() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use crate::notifier::{Keybase, KeybaseApi, PostFailure};
+
+/// ```toml
+/// [outbox]
+/// file = "pcta-outbox.jsonl"
+/// max_backoff_secs = 600
+/// ```
+///
+/// Alerts Keybase couldn't take wait in `file` and are tried again every pass, backing off up
+/// to `max_backoff_secs` while it stays down. They go out in the order they fired, before any
+/// newer alert, also after a restart. `""` to only hold them until the process exits. An alert
+/// Keybase refuses, like one to a topic that doesn't exist, is logged and dropped instead, it
+/// would never go through.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutboxConfig {
+    pub file: PathBuf,
+    pub max_backoff_secs: u64,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        OutboxConfig {
+            file: PathBuf::from("pcta-outbox.jsonl"),
+            max_backoff_secs: 600,
+        }
+    }
+}
+
+impl OutboxConfig {
+    pub fn file(&self) -> Option<&Path> {
+        Some(self.file.as_path()).filter(|p| !p.as_os_str().is_empty())
+    }
+}
+
+/// First wait after a failed delivery, doubling with every one after
+const FIRST_BACKOFF_SECS: i64 = 30;
+
+/// One undelivered alert, one line of the file
+#[derive(Serialize, Deserialize)]
+struct Queued {
+    queued_at: DateTime<Utc>,
+    msg: KeybaseApi,
+}
+
+/// The alerts waiting for Keybase, oldest first
+pub struct Outbox {
+    config: OutboxConfig,
+    queue: VecDeque<Queued>,
+    /// Failed deliveries in a row, for the backoff
+    failures: u32,
+    retry_at: Option<DateTime<Utc>>,
+}
+
+impl Outbox {
+    /// The outbox with what an earlier run left in the file
+    pub fn open(config: OutboxConfig) -> anyhow::Result<Self> {
+        let mut queue = VecDeque::new();
+        if let Some(path) = config.file() {
+            match std::fs::read_to_string(path) {
+                Ok(text) => {
+                    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+                        let queued = serde_json::from_str(line).with_context(|| {
+                            format!("Invalid queued alert in '{}'", path.display())
+                        })?;
+                        queue.push_back(queued);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to read '{}'", path.display()))
+                }
+            }
+        }
+        if !queue.is_empty() {
+            crate::info!("{} undelivered alerts in the outbox", queue.len());
+        }
+        Ok(Outbox {
+            config,
+            queue,
+            failures: 0,
+            retry_at: None,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Posts `msg` behind whatever is still waiting. `None` once it went out, or why it had to
+    /// be queued instead.
+    pub async fn post(
+        &mut self,
+        keybase: Keybase,
+        msg: KeybaseApi,
+        now: DateTime<Utc>,
+    ) -> Option<anyhow::Error> {
+        self.queue.push_back(Queued {
+            queued_at: now,
+            msg,
+        });
+        // Waiting out the backoff, the new alert waits with the rest
+        let failed = match self.retry_at.is_some_and(|at| now < at) {
+            true => Some(anyhow::anyhow!("Keybase is still failing, queued")),
+            false => self.deliver(keybase, now).await,
+        };
+        self.save();
+        failed
+    }
+
+    /// Tries what is waiting again once the backoff is over
+    pub async fn flush(&mut self, keybase: Keybase, now: DateTime<Utc>) {
+        if self.queue.is_empty() || self.retry_at.is_some_and(|at| now < at) {
+            return;
+        }
+        let before = self.queue.len();
+        match self.deliver(keybase, now).await {
+            None => crate::info!("Delivered {} queued alerts", before),
+            Some(e) => crate::info!(
+                "Delivering {} queued alerts failed, {} left: {:#}",
+                before,
+                self.queue.len(),
+                e
+            ),
+        }
+        self.save();
+    }
+
+    /// Posts from the front until one fails for now, which stays first. Refused ones are dropped.
+    async fn deliver(&mut self, keybase: Keybase, now: DateTime<Utc>) -> Option<anyhow::Error> {
+        while let Some(queued) = self.queue.front() {
+            match keybase.deliver(&queued.msg).await {
+                Ok(()) => {}
+                Err(PostFailure::Refused(e)) => crate::info!(
+                    "Dropping the alert queued at {}, Keybase refused it: {:#}",
+                    queued.queued_at,
+                    e
+                ),
+                Err(e @ PostFailure::Transient(_)) => {
+                    self.failures += 1;
+                    self.retry_at = Some(now + self.backoff());
+                    return Some(e.into());
+                }
+            }
+            self.queue.pop_front();
+        }
+        self.failures = 0;
+        self.retry_at = None;
+        None
+    }
+
+    fn backoff(&self) -> Duration {
+        let secs = FIRST_BACKOFF_SECS.saturating_mul(1 << (self.failures - 1).min(20));
+        Duration::seconds(secs.min(self.config.max_backoff_secs as i64))
+    }
+
+    /// Writes the queue over the file, which goes once it is empty. Failing to only costs the
+    /// alerts a restart, so it is logged and the queue kept in memory.
+    fn save(&self) {
+        let Some(path) = self.config.file() else {
+            return;
+        };
+        let saved = match self.queue.is_empty() {
+            true => match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+            false => self.write(path),
+        };
+        if let Err(e) = saved {
+            crate::info!("Failed to save the outbox '{}': {:#}", path.display(), e);
+        }
+    }
+
+    fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let mut text = String::new();
+        for queued in &self.queue {
+            text += &serde_json::to_string(queued)?;
+            text += "\n";
+        }
+        // Written aside and moved over, a crash never leaves half a queue
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, text)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifier::keybase_message;
+
+    #[tokio::test]
+    async fn queued_alerts_survive_a_restart_in_order() {
+        let name = format!("pcta-outbox-{}.jsonl", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let config = OutboxConfig {
+            file: path.clone(),
+            ..OutboxConfig::default()
+        };
+        let queued = |topic: &str, body: &str| Queued {
+            queued_at: "2024-03-01T17:00:00Z".parse().unwrap(),
+            msg: keybase_message(topic, body.to_string()),
+        };
+        let mut outbox = Outbox::open(config.clone()).unwrap();
+        outbox.queue.push_back(queued("pcta-alerts", "April open"));
+        outbox.queue.push_back(queued("pcta-may", "May open"));
+        outbox.save();
+
+        let mut outbox = Outbox::open(config).unwrap();
+        let bodies: Vec<&str> = outbox.queue.iter().map(|q| q.msg.body()).collect();
+        assert_eq!(bodies, vec!["April open", "May open"]);
+
+        outbox.failures = 3;
+        assert_eq!(outbox.backoff(), Duration::seconds(120));
+        outbox.failures = 10;
+        assert_eq!(outbox.backoff(), Duration::seconds(600));
+
+        let keybase = Keybase { dry_run: true };
+        outbox
+            .flush(keybase, "2024-03-01T17:01:00Z".parse().unwrap())
+            .await;
+        assert!(outbox.is_empty());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn refused_alerts_dont_hold_up_the_rest() {
+        let mut outbox = Outbox::open(OutboxConfig {
+            file: PathBuf::new(),
+            ..OutboxConfig::default()
+        })
+        .unwrap();
+        let at = "2024-03-01T17:00:00Z".parse().unwrap();
+        // Tests have no Keybase CLI to run, which it refuses like a topic that doesn't exist
+        let keybase = Keybase { dry_run: false };
+        let msg = keybase_message("pcta-no-such-topic", "April open".to_string());
+        assert!(outbox.post(keybase, msg, at).await.is_none());
+        assert!(outbox.is_empty());
+        assert_eq!((outbox.failures, outbox.retry_at), (0, None));
+    }
+}
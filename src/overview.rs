@@ -0,0 +1,223 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+use crate::boost::Cron;
+use crate::history::{History, Snapshot};
+use crate::target::Target;
+
+/// Dates the overview lists as having changed the most
+const BIGGEST_CHANGES: usize = 5;
+
+/// ```toml
+/// [overview]
+/// # sec min hour day-of-month month day-of-week, in the business hours' timezone
+/// at = "0 0 9 * * Mon"
+/// channel = "pcta-logs"
+/// ```
+///
+/// Once a week, every target's whole season from the history: which dates are full, which have
+/// space and which changed the most since the week before. For anyone following the season
+/// rather than one window. Off unless `at` is set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverviewConfig {
+    pub at: Option<Cron>,
+    pub channel: String,
+}
+
+impl Default for OverviewConfig {
+    fn default() -> Self {
+        OverviewConfig {
+            at: None,
+            channel: "pcta-logs".to_string(),
+        }
+    }
+}
+
+/// When the next overview is due
+pub struct Overview {
+    at: Option<Cron>,
+    tz: Tz,
+    next: Option<DateTime<Utc>>,
+}
+
+impl Overview {
+    pub fn new(config: &OverviewConfig, tz: Tz, now: DateTime<Utc>) -> Self {
+        Overview {
+            next: config.at.as_ref().and_then(|at| at.after(now, tz)),
+            at: config.at.clone(),
+            tz,
+        }
+    }
+
+    /// Whether the overview is due at `now`, which moves on to the next one when it is
+    pub fn due(&mut self, now: DateTime<Utc>) -> bool {
+        if self.next.is_none_or(|next| now < next) {
+            return false;
+        }
+        self.next = self.at.as_ref().and_then(|at| at.after(now, self.tz));
+        true
+    }
+}
+
+/// The overview of `targets` as the history has them at `now`, against a week before
+pub fn season(targets: &[Target], history: &History, now: DateTime<Utc>, display: &str) -> String {
+    let mut lines = vec![format!("*Weekly overview* `{}`", display)];
+    for target in targets {
+        let label = target.label();
+        lines.push(String::new());
+        match history.as_of(&label, now) {
+            Some(latest) => {
+                let before = history.as_of(&label, now - Duration::weeks(1));
+                lines.extend(calendar(target, &latest, before.as_ref()));
+            }
+            None => lines.push(format!("*{}*: not scraped yet", label)),
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// One target's part of the overview
+fn calendar(target: &Target, latest: &Snapshot, before: Option<&Snapshot>) -> Vec<String> {
+    let left = |snapshot: &Snapshot, day: NaiveDate| {
+        snapshot
+            .days
+            .iter()
+            .find(|(d, _)| *d == day)
+            .map_or(0, |(_, left)| *left)
+    };
+    let listed = [Some(latest), before]
+        .into_iter()
+        .flatten()
+        .flat_map(|snapshot| snapshot.days.iter().map(|(day, _)| *day));
+    let days: Vec<NaiveDate> = match target.span(listed) {
+        Some((start, end)) => start.iter_days().take_while(|day| *day <= end).collect(),
+        None => vec![],
+    };
+    let full: Vec<NaiveDate> = days
+        .iter()
+        .copied()
+        .filter(|day| left(latest, *day) == 0)
+        .collect();
+    let mut lines = vec![format!(
+        "*{}* `{}`: {} dates with space, {} full",
+        latest.label,
+        target.window(),
+        days.len() - full.len(),
+        full.len()
+    )];
+    if !full.is_empty() {
+        lines.push(format!("* Full: {}", ranges(&full).join(", ")));
+    }
+    for day in days.iter().filter(|day| left(latest, **day) > 0) {
+        lines.push(format!(
+            "* `{}` ({}): {} left",
+            day,
+            day.format("%a"),
+            left(latest, *day)
+        ));
+    }
+    let Some(before) = before else {
+        lines.push("* Nothing from a week ago to compare with".to_string());
+        return lines;
+    };
+    let mut changes: Vec<(NaiveDate, u64, u64)> = days
+        .iter()
+        .map(|day| (*day, left(before, *day), left(latest, *day)))
+        .filter(|(_, was, is)| was != is)
+        .collect();
+    // Biggest first, earliest first among equals
+    changes.sort_by_key(|(day, was, is)| (std::cmp::Reverse(was.abs_diff(*is)), *day));
+    match changes.is_empty() {
+        true => lines.push("* No change since last week".to_string()),
+        false => {
+            let biggest: Vec<String> = changes
+                .iter()
+                .take(BIGGEST_CHANGES)
+                .map(|(day, was, is)| format!("`{}` {} -> {}", day, was, is))
+                .collect();
+            lines.push(format!("* Since last week: {}", biggest.join(", ")));
+        }
+    }
+    lines
+}
+
+/// Runs of consecutive dates, each as `first` or `first`-`last`
+fn ranges(days: &[NaiveDate]) -> Vec<String> {
+    let mut runs: Vec<(NaiveDate, NaiveDate)> = vec![];
+    for day in days {
+        match runs.last_mut() {
+            Some((_, last)) if last.succ_opt() == Some(*day) => *last = *day,
+            _ => runs.push((*day, *day)),
+        }
+    }
+    runs.iter()
+        .map(|(first, last)| match first == last {
+            true => format!("`{}`", first),
+            false => format!("`{}`-`{}`", first, last),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_the_season_and_what_changed_in_a_week() {
+        let target: Target = toml::from_str(
+            r#"
+            terminus = "mexican-border"
+            start = "2024-04-01"
+            end = "2024-04-05"
+            "#,
+        )
+        .unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 4, d).unwrap();
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let history = History::open(None).unwrap();
+        let snapshot = |when, days| Snapshot {
+            at: at(when),
+            label: "Mexican border".to_string(),
+            days,
+        };
+        let config: OverviewConfig = toml::from_str(r#"at = "0 0 9 * * Mon""#).unwrap();
+        let mut overview = Overview::new(&config, chrono_tz::UTC, at("2024-03-10T12:00:00Z"));
+        assert!(!overview.due(at("2024-03-11T08:59:00Z")));
+        assert!(overview.due(at("2024-03-11T09:00:00Z")));
+        assert!(!overview.due(at("2024-03-11T09:05:00Z")));
+
+        let targets = [target];
+        let msg = season(&targets, &history, at("2024-03-11T09:00:00Z"), "now");
+        assert!(msg.ends_with(": not scraped yet\n"));
+
+        history
+            .record(snapshot(
+                "2024-03-03T17:00:00Z",
+                vec![(day(1), 20), (day(2), 20), (day(5), 8)],
+            ))
+            .unwrap();
+        history
+            .record(snapshot(
+                "2024-03-10T17:00:00Z",
+                vec![(day(4), 2), (day(5), 6)],
+            ))
+            .unwrap();
+        let msg = season(&targets, &history, at("2024-03-11T09:00:00Z"), "now");
+        let lines: Vec<&str> = msg.lines().skip(3).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "* Full: `2024-04-01`-`2024-04-03`",
+                "* `2024-04-04` (Thu): 2 left",
+                "* `2024-04-05` (Fri): 6 left",
+                "* Since last week: `2024-04-01` 20 -> 0, `2024-04-02` 20 -> 0, \
+                 `2024-04-04` 0 -> 2, `2024-04-05` 8 -> 6",
+            ]
+        );
+        assert!(
+            msg.contains("*Mexican border* `2024-04-01..2024-04-05`: 2 dates with space, 3 full\n")
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::fmt;
+
+use crate::history::Snapshot;
+
+/// How fast an open date's permits have been going, measured from the earliest of the latest
+/// snapshots it was open in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pace {
+    pub taken: u64,
+    pub over: Duration,
+    pub left: u64,
+}
+
+impl Pace {
+    pub fn per_hour(&self) -> f64 {
+        self.taken as f64 * 3600.0 / self.over.num_seconds() as f64
+    }
+
+    /// When the last of `left` would go at this pace, rounded up to the minute
+    pub fn gone_within(&self) -> Duration {
+        let secs = (self.left * self.over.num_seconds() as u64).div_ceil(self.taken);
+        Duration::minutes(secs.div_ceil(60) as i64)
+    }
+}
+
+impl fmt::Display for Pace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let minutes = self.gone_within().num_minutes();
+        let within = match minutes {
+            0..=59 => format!("{} min", minutes),
+            60..=2879 => format!("{}h {}m", minutes / 60, minutes % 60),
+            _ => format!("{} days", minutes / 1440),
+        };
+        write!(
+            f,
+            "~{:.1} taken/h, likely gone within {}",
+            self.per_hour(),
+            within
+        )
+    }
+}
+
+/// The pace of every date in `open` as scraped `at`, over `snapshots` of its target from before,
+/// oldest first. A date only counts the snapshots since it last came open, and one that just
+/// did or hasn't gone down has none.
+pub fn paces(
+    snapshots: &[Snapshot],
+    open: &[(NaiveDate, u64)],
+    at: DateTime<Utc>,
+) -> Vec<(NaiveDate, Pace)> {
+    open.iter()
+        .filter_map(|(date, left)| {
+            let (since, first) = snapshots
+                .iter()
+                .rev()
+                .map_while(|s| {
+                    let (_, remaining) = s.days.iter().find(|(d, _)| d == date)?;
+                    Some((s.at, *remaining))
+                })
+                .last()?;
+            let pace = Pace {
+                taken: first.checked_sub(*left).filter(|taken| *taken > 0)?,
+                over: Some(at - since).filter(|over| over.num_seconds() > 0)?,
+                left: *left,
+            };
+            Some((*date, pace))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_since_the_date_came_open() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 4, d).unwrap();
+        let at = |s: &str| format!("2024-03-01T{}:00Z", s).parse().unwrap();
+        let snapshot = |when, days| Snapshot {
+            at: at(when),
+            label: "Mexican border".to_string(),
+            days,
+        };
+        let snapshots = vec![
+            snapshot("16:00", vec![(day(15), 40)]),
+            snapshot("16:30", vec![]),
+            snapshot("17:00", vec![(day(14), 9), (day(15), 30)]),
+            snapshot("17:30", vec![(day(14), 9), (day(15), 20), (day(16), 5)]),
+        ];
+        let open = [(day(14), 9), (day(15), 10), (day(16), 5), (day(17), 2)];
+        let paces = paces(&snapshots, &open, at("18:00"));
+        // 14th hasn't gone down, 16th only just opened and the 17th is new
+        assert_eq!(paces.len(), 1);
+        let (date, pace) = paces[0];
+        assert_eq!(date, day(15));
+        assert_eq!((pace.taken, pace.over), (20, Duration::hours(1)));
+        assert_eq!(pace.gone_within(), Duration::minutes(30));
+        assert_eq!(pace.to_string(), "~20.0 taken/h, likely gone within 30 min");
+    }
+}
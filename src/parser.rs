@@ -0,0 +1,271 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Cow;
+
+use crate::detect::{BlockKind, Blocked};
+use crate::error::ScrapeError;
+use crate::extract;
+
+/// Daily capacity assumed when neither the config nor the page gives one
+pub const LIMIT: u64 = 50;
+
+/// Missing fields read as empty, `calendar` skips the entry rather than failing the page
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    // YYYY-MM-DD
+    #[serde(default)]
+    pub start_date: String,
+    /// Permits already issued for the date, not what is left. Published as a string of a u64
+    /// but sometimes as the number itself.
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub num: String,
+}
+
+/// A JSON string as is and anything else as its JSON text, so `"12"` and `12` both read as
+/// `"12"` and a `null` only fails its own entry
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => s,
+        value => value.to_string(),
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Data {
+    /// Permits issued per start date, missing or `0` on a page that doesn't say
+    #[serde(default)]
+    pub limit: Option<u64>,
+    pub calendar: Vec<Entry>,
+}
+
+/// One start date on the calendar. The portal publishes `issued` against a daily `capacity`,
+/// everything downstream of the parser works in `remaining`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PermitDay {
+    pub date: NaiveDate,
+    pub issued: u64,
+    pub capacity: u64,
+    /// Over-issued days happen, they just have none left
+    pub remaining: u64,
+}
+
+impl PermitDay {
+    pub fn new(date: NaiveDate, issued: u64, capacity: u64) -> Self {
+        PermitDay {
+            date,
+            issued,
+            capacity,
+            remaining: capacity.saturating_sub(issued),
+        }
+    }
+}
+
+/// `(date, remaining)` for every day, the shape alerts and watches take
+pub fn remaining(days: &[PermitDay]) -> Vec<(NaiveDate, u64)> {
+    days.iter().map(|day| (day.date, day.remaining)).collect()
+}
+
+/// Pulls the calendar JSON out of the availability page. Every <script> is searched rather than
+/// a fixed position, so the page layout can shift without breaking us.
+pub fn extract(text: &str) -> anyhow::Result<Data> {
+    from_objects(&objects(text, false), text)
+}
+
+/// The `var data` object literal of every <script> that has one, in page order, still unparsed.
+/// The scripts are found by scanning the page and borrowed from it. Only a page where that finds
+/// none is parsed into a full DOM to look again, and not even then when `scan_only`.
+pub fn objects(text: &str, scan_only: bool) -> Vec<Cow<'_, str>> {
+    let scanned: Vec<Cow<str>> = extract::scripts(text)
+        .filter_map(extract::object_literal)
+        .map(Cow::Borrowed)
+        .collect();
+    if !scanned.is_empty() || scan_only {
+        return scanned;
+    }
+    crate::debug!("No calendar object found by scanning the page, parsing its DOM");
+    dom_objects(text).into_iter().map(Cow::Owned).collect()
+}
+
+fn dom_objects(text: &str) -> Vec<String> {
+    let html = scraper::Html::parse_document(text);
+    let script_selector = scraper::Selector::parse("script").unwrap();
+    html.select(&script_selector)
+        .filter_map(|script| extract::object_literal(&script.inner_html()).map(str::to_string))
+        .collect()
+}
+
+/// The first of `objects` that is valid calendar JSON, `text` being the page they came from
+pub fn from_objects(objects: &[Cow<str>], text: &str) -> anyhow::Result<Data> {
+    let mut invalid = None;
+    for data_str in objects {
+        crate::trace!("Calendar JSON from the page: {}", data_str);
+        match serde_json::from_str::<Data>(data_str) {
+            Ok(data) => return Ok(data),
+            Err(e) => invalid = Some(e),
+        }
+    }
+
+    let err = match invalid {
+        Some(e) => ScrapeError::json("We parsed Invalid JSON from the PCTA <script> tag, investigate the script tag or the extractor result")(e),
+        None if extract::assigned(text) => ScrapeError::ParseHtml(
+            "The page assigns the calendar but its object literal couldn't be read, investigate the extractor".to_string(),
+        ),
+        None => Blocked::new(
+            BlockKind::Unrecognized,
+            "Failed to find the calendar <script> in HTML document. We may be getting IP blocked or CAPTCHA",
+        )
+        .into(),
+    };
+    Err(err.into())
+}
+
+/// Every date on the calendar, against the capacity the page itself states in `limit`. A
+/// `limit` from the config wins over the page, and `LIMIT` is only the last resort. Entries
+/// that don't read are logged and skipped, only a calendar with none left is an error.
+pub fn calendar(data: Data, limit: Option<u64>) -> anyhow::Result<Vec<PermitDay>> {
+    let capacity = limit
+        .or(data.limit.filter(|limit| *limit > 0))
+        .unwrap_or(LIMIT);
+    let mut results: Vec<PermitDay> = vec![];
+    let mut skipped = vec![];
+
+    for entry in &data.calendar {
+        let start_date_fmt = "%Y-%m-%d";
+        let invalid = |field, value: &str| ScrapeError::InvalidEntry {
+            date: entry.start_date.clone(),
+            field,
+            value: value.to_string(),
+            from: "PCTA",
+        };
+        let day = chrono::NaiveDate::parse_from_str(&entry.start_date, start_date_fmt)
+            .map_err(|_| invalid("start_date", &entry.start_date))
+            .and_then(|date| {
+                let num = entry.num.parse::<u64>();
+                num.map(|num| PermitDay::new(date, num, capacity))
+                    .map_err(|_| invalid("num", &entry.num))
+            });
+        match day {
+            Ok(day) => results.push(day),
+            Err(e) => {
+                crate::info!("Skipping a calendar entry: {}", e);
+                skipped.push(e);
+            }
+        }
+    }
+
+    if !skipped.is_empty() {
+        if results.is_empty() {
+            return Err(skipped.swap_remove(0).into());
+        }
+        crate::info!(
+            "Skipped {} of {} calendar entries",
+            skipped.len(),
+            data.calendar.len()
+        );
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn entry(start_date: &str, num: &str) -> Entry {
+        Entry {
+            start_date: start_date.to_string(),
+            num: num.to_string(),
+        }
+    }
+
+    #[test]
+    fn scanning_finds_what_the_dom_does() {
+        for page in [
+            include_str!("../fixtures/mexican-border.html"),
+            include_str!("../fixtures/mexican-border-reformatted.html"),
+            include_str!("../fixtures/captcha.html"),
+        ] {
+            assert_eq!(objects(page, true), dom_objects(page));
+        }
+        let page = "<SCRIPT src=a.js></SCRIPT><script>var data = {\"calendar\": []}</script>";
+        assert_eq!(objects(page, true), vec!["{\"calendar\": []}"]);
+        // An unclosed <script> isn't scanned, the DOM still reads it
+        let page = "<script>var data = {\"calendar\": []}";
+        assert!(objects(page, true).is_empty());
+        assert_eq!(objects(page, false), vec!["{\"calendar\": []}"]);
+    }
+
+    #[test]
+    fn remaining_is_limit_minus_issued() {
+        let data = Data {
+            limit: Some(50),
+            calendar: vec![
+                entry("2023-04-02", "10"),
+                entry("2023-04-03", "50"),
+                // over-issued days happen, they're just full
+                entry("2023-04-04", "52"),
+            ],
+        };
+        let days = calendar(data, None).unwrap();
+        assert_eq!(days[0], PermitDay::new(date("2023-04-02"), 10, 50));
+        assert_eq!(days[0].remaining, 40);
+        assert_eq!(
+            remaining(&days),
+            vec![
+                (date("2023-04-02"), 40),
+                (date("2023-04-03"), 0),
+                (date("2023-04-04"), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn capacity_comes_from_the_page() {
+        let data = Data {
+            limit: Some(60),
+            calendar: vec![entry("2023-04-02", "10")],
+        };
+        assert_eq!(calendar(data, None).unwrap()[0].remaining, 50);
+    }
+
+    #[test]
+    fn config_limit_wins_and_the_constant_is_a_fallback() {
+        let data = |limit| Data {
+            limit,
+            calendar: vec![entry("2023-04-02", "10")],
+        };
+        assert_eq!(calendar(data(Some(60)), Some(20)).unwrap()[0].capacity, 20);
+        assert_eq!(calendar(data(Some(0)), None).unwrap()[0].capacity, LIMIT);
+        let data: Data = serde_json::from_str(r#"{"calendar": []}"#).unwrap();
+        assert_eq!(data.limit, None);
+    }
+
+    #[test]
+    fn invalid_entries_are_skipped() {
+        let data: Data = serde_json::from_str(
+            r#"{"calendar": [
+                {"start_date": "2023-04-02", "num": 10},
+                {"start_date": "2023-04-03", "num": "12"},
+                {"start_date": "2023-04-04", "num": null},
+                {"start_date": "2023-04-05"},
+                {"num": "3"}
+            ]}"#,
+        )
+        .unwrap();
+        let days = calendar(data, Some(50)).unwrap();
+        assert_eq!(
+            remaining(&days),
+            vec![(date("2023-04-02"), 40), (date("2023-04-03"), 38)]
+        );
+
+        // Nothing left to go by is a page we can't read
+        let data = Data {
+            limit: Some(50),
+            calendar: vec![entry("2023-04-02", "many")],
+        };
+        assert!(calendar(data, None).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::Context;
+use reqwest::{ClientBuilder, Proxy};
+use std::sync::Arc;
+
+use crate::config::{ProxyConfig, Rotation};
+use crate::dns::Router;
+use crate::http::{self, HttpConfig};
+
+/// A ring of HTTP/SOCKS5 proxies. The reqwest `Client` bakes its proxy in at build time, so
+/// rotating means handing out a fresh builder for the next proxy in the ring.
+pub struct ProxyPool {
+    urls: Vec<String>,
+    rotation: Rotation,
+    current: usize,
+    http: HttpConfig,
+    dns: Option<Arc<Router>>,
+}
+
+impl ProxyPool {
+    /// Resolves the configured URLs, which may keep their credentials in a secret store
+    pub fn new(config: &ProxyConfig) -> anyhow::Result<Self> {
+        let urls = config
+            .urls
+            .iter()
+            .map(|url| url.expose().context("Failed to resolve proxy URL"))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(ProxyPool {
+            urls,
+            rotation: config.rotate,
+            current: 0,
+            http: HttpConfig::default(),
+            dns: None,
+        })
+    }
+
+    /// Builds every client with `http`'s timeouts and protocol settings
+    pub fn http(mut self, http: HttpConfig) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Looks the permit sites up through `router` instead of the system resolver
+    pub fn dns(mut self, router: Option<Arc<Router>>) -> Self {
+        self.dns = router;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.urls.is_empty()
+    }
+
+    /// The proxy requests currently go through, `None` when the pool is empty
+    pub fn current(&self) -> Option<&str> {
+        if self.urls.is_empty() {
+            return None;
+        }
+        Some(&self.urls[self.current % self.urls.len()])
+    }
+
+    /// `current` without the username and password, for logs and chat
+    pub fn current_redacted(&self) -> Option<String> {
+        let url = self.current()?;
+        let mut parsed = reqwest::Url::parse(url).ok()?;
+        let _ = parsed.set_username("");
+        let _ = parsed.set_password(None);
+        Some(parsed.to_string())
+    }
+
+    /// A client builder routed through the current proxy, or a direct one for an empty pool
+    pub fn builder(&self) -> anyhow::Result<ClientBuilder> {
+        let mut builder = http::bind(http::builder(&self.http), &self.http)?;
+        if let Some(router) = &self.dns {
+            builder = builder.dns_resolver(router.clone());
+        }
+        if let Some(url) = self.current() {
+            let proxy = Proxy::all(url).with_context(|| format!("Invalid proxy URL '{}'", url))?;
+            builder = builder.proxy(proxy);
+        }
+        Ok(builder)
+    }
+
+    /// Moves on to the next proxy if the rotation policy asks for it after this tick. Returns
+    /// whether it rotated.
+    pub fn after_tick(&mut self, blocked: bool) -> bool {
+        let rotate = match self.rotation {
+            Rotation::OnBlock => blocked,
+            Rotation::EveryTick => true,
+        };
+        if !rotate || self.urls.len() < 2 {
+            return false;
+        }
+        self.current = (self.current + 1) % self.urls.len();
+        true
+    }
+}
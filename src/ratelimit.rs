@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Token bucket shared by every request to the permit sites, however many targets and watches
+/// want a scrape. Holds up to `burst` tokens and refills `per_minute` of them a minute.
+pub struct RateLimiter {
+    per_minute: u32,
+    burst: u32,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl RateLimiter {
+    /// `per_minute == 0` never waits
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        let burst = burst.max(1);
+        RateLimiter {
+            per_minute,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst as f64,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        RateLimiter::new(0, 1)
+    }
+
+    /// Waits until a request may go out and takes its token
+    pub async fn acquire(&self) {
+        if self.per_minute == 0 {
+            return;
+        }
+        let per_sec = self.per_minute as f64 / 60.0;
+        // Holding the lock while sleeping queues the waiters up in order
+        let mut bucket = self.bucket.lock().await;
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_sec).min(self.burst as f64);
+        bucket.refilled = now;
+        if bucket.tokens < 1.0 {
+            let wait = (1.0 - bucket.tokens) / per_sec;
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+            bucket.tokens = 1.0;
+            bucket.refilled = Instant::now();
+        }
+        bucket.tokens -= 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn burst_then_waits_for_refill() {
+        // One token every 50ms
+        let limiter = RateLimiter::new(1200, 2);
+        let start = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(40));
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(45));
+    }
+}
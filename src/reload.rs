@@ -0,0 +1,258 @@
+use anyhow::Context;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::bot::Control;
+use crate::config::Config;
+
+/// Sections read afresh on every pass, or handed to what uses them on reload. The others set
+/// up connections, sessions and servers once at startup.
+const RELOADABLE: [&str; 11] = [
+    "portal",
+    "targets",
+    "recreation_gov",
+    "browser",
+    "schedule",
+    "alerting",
+    "notifiers",
+    "templates",
+    "hook",
+    "anomaly",
+    "errors",
+];
+
+/// Every section's name and contents, to tell which changed. Spelled out so a new section
+/// can't be forgotten here.
+fn sections(config: &Config) -> Vec<(&'static str, String)> {
+    let Config {
+        portal,
+        targets,
+        vpn,
+        proxy,
+        session,
+        browser,
+        recreation_gov,
+        subscribers,
+        bot,
+        control,
+        ha,
+        redis,
+        schedule,
+        rate_limit,
+        state,
+        display,
+        alerting,
+        digest,
+        overview,
+        notifiers,
+        keybase,
+        templates,
+        hook,
+        anomaly,
+        errors,
+        breaker,
+        politeness,
+        http,
+        dns,
+        debug,
+        outbox,
+        memory,
+        journal,
+        influx,
+        profiles,
+    } = config;
+    // Secrets only show where they come from in `Debug`
+    vec![
+        ("portal", format!("{:?}", portal)),
+        ("targets", format!("{:?}", targets)),
+        ("vpn", format!("{:?}", vpn)),
+        ("proxy", format!("{:?}", proxy)),
+        ("session", format!("{:?}", session)),
+        ("browser", format!("{:?}", browser)),
+        ("recreation_gov", format!("{:?}", recreation_gov)),
+        ("subscribers", format!("{:?}", subscribers)),
+        ("bot", format!("{:?}", bot)),
+        ("control", format!("{:?}", control)),
+        ("ha", format!("{:?}", ha)),
+        ("redis", format!("{:?}", redis)),
+        ("schedule", format!("{:?}", schedule)),
+        ("rate_limit", format!("{:?}", rate_limit)),
+        ("state", format!("{:?}", state)),
+        ("display", format!("{:?}", display)),
+        ("alerting", format!("{:?}", alerting)),
+        ("digest", format!("{:?}", digest)),
+        ("overview", format!("{:?}", overview)),
+        ("notifiers", format!("{:?}", notifiers)),
+        ("keybase", format!("{:?}", keybase)),
+        ("templates", format!("{:?}", templates)),
+        ("hook", format!("{:?}", hook)),
+        ("anomaly", format!("{:?}", anomaly)),
+        ("errors", format!("{:?}", errors)),
+        ("breaker", format!("{:?}", breaker)),
+        ("politeness", format!("{:?}", politeness)),
+        ("http", format!("{:?}", http)),
+        ("dns", format!("{:?}", dns)),
+        ("debug", format!("{:?}", debug)),
+        ("outbox", format!("{:?}", outbox)),
+        ("memory", format!("{:?}", memory)),
+        ("journal", format!("{:?}", journal)),
+        ("influx", format!("{:?}", influx)),
+        ("profile", format!("{:?}", profiles)),
+    ]
+}
+
+/// How a reloaded config differs from the running one
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    /// Sections taken over right away
+    pub applied: Vec<&'static str>,
+    /// Sections that changed but only apply after a restart
+    pub restart: Vec<&'static str>,
+    /// `label start..end` of targets that came or went
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Changes {
+    pub fn between(old: &Config, new: &Config) -> Self {
+        let mut changes = Changes::default();
+        for ((name, before), (_, after)) in sections(old).into_iter().zip(sections(new)) {
+            if before == after {
+                continue;
+            }
+            match RELOADABLE.contains(&name) {
+                true => changes.applied.push(name),
+                false => changes.restart.push(name),
+            }
+        }
+        let window = |config: &Config| -> Vec<String> {
+            config
+                .targets
+                .iter()
+                .map(|t| format!("{} {}", t.label(), t.window()))
+                .collect()
+        };
+        let (before, after) = (window(old), window(new));
+        changes.added = after
+            .iter()
+            .filter(|t| !before.contains(t))
+            .cloned()
+            .collect();
+        changes.removed = before
+            .iter()
+            .filter(|t| !after.contains(t))
+            .cloned()
+            .collect();
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.restart.is_empty()
+    }
+}
+
+fn list(sections: &[&str]) -> String {
+    sections
+        .iter()
+        .map(|s| format!("`[{}]`", s))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// For the logs channel: what changed, and what still needs a restart
+pub fn announce(changes: &Changes, now: &str) -> String {
+    if changes.is_empty() {
+        return format!("`{}` - Reloaded the config, nothing changed", now);
+    }
+    let mut msg = format!("`{}` - *Reloaded the config*", now);
+    if !changes.applied.is_empty() {
+        msg += &format!(", applied {}", list(&changes.applied));
+    }
+    for target in &changes.added {
+        msg += &format!("\n+ `{}`", target);
+    }
+    for target in &changes.removed {
+        msg += &format!("\n- `{}`", target);
+    }
+    if !changes.restart.is_empty() {
+        msg += &format!("\nRestart to apply {}", list(&changes.restart));
+    }
+    msg
+}
+
+/// Asks for a reload whenever the file at `path` is written. Editors often replace the file
+/// instead of writing it, so its directory is what gets watched. Stops with the returned
+/// watcher.
+pub fn watch(path: &Path, control: Arc<Control>) -> anyhow::Result<RecommendedWatcher> {
+    let path = std::fs::canonicalize(path)
+        .with_context(|| format!("Failed to find config '{}'", path.display()))?;
+    let dir = path
+        .parent()
+        .context("Config file has no directory")?
+        .to_path_buf();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        let ours = event.paths.contains(&path);
+        if ours && (event.kind.is_modify() || event.kind.is_create()) {
+            control.reload();
+        }
+    })?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch '{}'", dir.display()))?;
+    Ok(watcher)
+}
+
+/// Asks for a reload on every SIGHUP
+#[cfg(unix)]
+pub fn on_sighup(control: Arc<Control>) -> anyhow::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            control.reload();
+        }
+    });
+    Ok(())
+}
+
+/// Windows has no SIGHUP, saving the config file still reloads it
+#[cfg(not(unix))]
+pub fn on_sighup(_: Arc<Control>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_applied_from_restart_only_changes() {
+        let old = Config::default();
+        let new: Config = toml::from_str(
+            r#"
+            [[targets]]
+            terminus = "mexican-border"
+            start = "2024-04-01"
+            end = "2024-05-15"
+
+            [alerting]
+            cooldown_secs = 600
+
+            [vpn]
+            provider = "none"
+            "#,
+        )
+        .unwrap();
+        let changes = Changes::between(&old, &new);
+        assert_eq!(changes.applied, vec!["targets", "alerting"]);
+        assert_eq!(changes.restart, vec!["vpn"]);
+        assert_eq!(changes.added, vec!["Mexican border 2024-04-01..2024-05-15"]);
+        assert!(announce(&changes, "now").contains("Restart to apply `[vpn]`"));
+        assert!(Changes::between(&old, &Config::default()).is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::Context;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::path::{Path, PathBuf};
+
+/// How saved bodies are stamped, sortable and free of `-` so the key can follow it
+const STAMP: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// A response body `--save-bodies` wrote, `20240301T170000.123Z-pcta-mexican-border.html`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Saved {
+    pub at: DateTime<Utc>,
+    /// The source's `PermitSource::key`
+    pub key: String,
+    /// What the source fetched, its `PermitSource::parse` knows
+    pub kind: String,
+    pub path: PathBuf,
+}
+
+impl Saved {
+    fn from_path(path: &Path) -> Option<Saved> {
+        let (kind, name) = (path.extension()?, path.file_stem()?);
+        let (stamp, key) = name.to_str()?.split_once('-')?;
+        let at = NaiveDateTime::parse_from_str(stamp, STAMP).ok()?.and_utc();
+        Some(Saved {
+            at,
+            key: key.to_string(),
+            kind: kind.to_str()?.to_string(),
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+/// Writes `body` into `dir` as fetched at `at`, returning where
+pub fn save(
+    dir: &Path,
+    at: DateTime<Utc>,
+    key: &str,
+    kind: &str,
+    body: &str,
+) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    let path = dir.join(format!("{}-{}.{}", at.format(STAMP), key, kind));
+    std::fs::write(&path, body)
+        .with_context(|| format!("Failed to save body to '{}'", path.display()))?;
+    Ok(path)
+}
+
+/// The bodies saved in `dir`, oldest first. Files not named like one are left out.
+pub fn list(dir: &Path) -> anyhow::Result<Vec<Saved>> {
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read '{}'", dir.display()))?;
+    let mut saved = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        match Saved::from_path(&path) {
+            Some(body) => saved.push(body),
+            None => crate::info!("Not a saved body, skipping '{}'", path.display()),
+        }
+    }
+    saved.sort_by(|a, b| (a.at, &a.key).cmp(&(b.at, &b.key)));
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_bodies_list_oldest_first() {
+        let dir = std::env::temp_dir().join(format!("pcta-bodies-{}", std::process::id()));
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        save(
+            &dir,
+            at("2024-03-01T17:00:30Z"),
+            "pcta-mexican-border",
+            "html",
+            "b",
+        )
+        .unwrap();
+        save(
+            &dir,
+            at("2024-03-01T17:00:00Z"),
+            "recreation-gov-1-2",
+            "json",
+            "a",
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let saved = list(&dir).unwrap();
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved[0].key, "recreation-gov-1-2");
+        assert_eq!(saved[0].at, at("2024-03-01T17:00:00Z"));
+        assert_eq!(saved[1].kind, "html");
+        assert_eq!(std::fs::read_to_string(&saved[1].path).unwrap(), "b");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
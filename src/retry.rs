@@ -0,0 +1,83 @@
+use rand::Rng;
+use std::future::Future;
+use std::time::Duration;
+
+use crate::error::ScrapeError;
+
+const RETRY_ATTEMPTS: u32 = 3;
+const RETRY_BASE_MS: u64 = 500;
+
+/// How a failed scrape attempt should be handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// Timeouts, dropped connections and 5xx responses. Worth retrying in place.
+    Transient,
+    /// 403s and CAPTCHA pages. Retrying from the same IP only digs the hole deeper.
+    Blocked,
+    /// Anything else (bad JSON, unexpected page layout). Retrying won't change the answer.
+    Fatal,
+}
+
+pub fn classify(err: &anyhow::Error) -> Failure {
+    match ScrapeError::find(err) {
+        Some(ScrapeError::Blocked(_) | ScrapeError::CaptchaDetected(_)) => Failure::Blocked,
+        Some(ScrapeError::Network(e)) => network(e),
+        Some(
+            ScrapeError::ParseHtml(_)
+            | ScrapeError::ParseJson { .. }
+            | ScrapeError::InvalidEntry { .. },
+        ) => Failure::Fatal,
+        // Requests made outside the sources (robots.txt, the session's own) aren't wrapped
+        None => err
+            .downcast_ref::<reqwest::Error>()
+            .map_or(Failure::Fatal, network),
+    }
+}
+
+fn network(e: &reqwest::Error) -> Failure {
+    if e.is_timeout() || e.is_connect() {
+        return Failure::Transient;
+    }
+    match e.status() {
+        Some(status) if status.is_server_error() => Failure::Transient,
+        Some(status) if status.as_u16() == 403 => Failure::Blocked,
+        _ => Failure::Fatal,
+    }
+}
+
+/// Runs `f` up to `RETRY_ATTEMPTS` times, sleeping with jittered exponential backoff between
+/// attempts. Only `Failure::Transient` errors are retried, everything else is returned
+/// immediately so the caller can escalate (VPN reconnect, error channel).
+pub async fn with_backoff<T, F, Fut>(mut f: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Ok(v) => return Ok(v),
+            Err(e) if attempt < RETRY_ATTEMPTS && classify(&e) == Failure::Transient => {
+                let delay = backoff(attempt);
+                crate::info!(
+                    "Transient scrape failure (attempt {}/{}), retrying in {}ms: {}",
+                    attempt,
+                    RETRY_ATTEMPTS,
+                    delay.as_millis(),
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Full exponential delay for `attempt` with up to half of it shaved off at random, so retries
+/// from a failed tick don't land on a fixed cadence.
+fn backoff(attempt: u32) -> Duration {
+    let max = RETRY_BASE_MS * 2u64.pow(attempt - 1);
+    let ms = rand::thread_rng().gen_range(max / 2..=max);
+    Duration::from_millis(ms)
+}
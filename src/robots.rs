@@ -0,0 +1,270 @@
+use anyhow::Context;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// robots.txt is fetched again after this long
+const ROBOTS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// ```toml
+/// [politeness]
+/// enabled = true
+/// user_agent = "pcta/0.1 (+https://github.com/jryio/pcta)"
+/// min_interval_secs = 10
+/// robots_txt = true
+/// ```
+///
+/// For running openly instead of in stealth: requests say who we are instead of passing for a
+/// browser, go out at least `min_interval_secs` apart or further if robots.txt asks for a
+/// Crawl-delay, and paths robots.txt disallows for us fail instead of being fetched.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolitenessConfig {
+    pub enabled: bool,
+    pub user_agent: String,
+    pub min_interval_secs: u64,
+    pub robots_txt: bool,
+}
+
+impl Default for PolitenessConfig {
+    fn default() -> Self {
+        PolitenessConfig {
+            enabled: false,
+            user_agent: format!(
+                "pcta/{} (+https://github.com/jryio/pcta)",
+                env!("CARGO_PKG_VERSION")
+            ),
+            min_interval_secs: 10,
+            robots_txt: true,
+        }
+    }
+}
+
+/// The rules of one robots.txt that apply to us
+#[derive(Debug, Default, PartialEq)]
+pub struct Robots {
+    /// `(allowed, pattern)`, the longest matching pattern wins
+    rules: Vec<(bool, String)>,
+    pub crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Picks the group naming `agent`'s product token, or the `*` one if none does
+    pub fn parse(text: &str, agent: &str) -> Self {
+        let token = agent
+            .split(['/', ' '])
+            .next()
+            .unwrap_or(agent)
+            .to_lowercase();
+        let (mut ours, mut anyone) = (None::<Robots>, None::<Robots>);
+        // The agents of the group being read, and whether its rules started yet
+        let mut agents: Vec<String> = vec![];
+        let mut in_rules = false;
+        let mut group = Robots::default();
+        let mut finish = |agents: &[String], group: Robots| {
+            if agents
+                .iter()
+                .any(|a| !a.is_empty() && token.contains(a.as_str()))
+            {
+                ours.get_or_insert(group);
+            } else if agents.iter().any(|a| a == "*") {
+                anyone.get_or_insert(group);
+            }
+        };
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        finish(&agents, std::mem::take(&mut group));
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_lowercase());
+                }
+                "allow" | "disallow" if value.is_empty() => in_rules = true,
+                "allow" => {
+                    in_rules = true;
+                    group.rules.push((true, value.to_string()));
+                }
+                "disallow" => {
+                    in_rules = true;
+                    group.rules.push((false, value.to_string()));
+                }
+                "crawl-delay" => {
+                    in_rules = true;
+                    group.crawl_delay = value.parse::<f64>().ok().map(Duration::from_secs_f64);
+                }
+                _ => {}
+            }
+        }
+        finish(&agents, group);
+        ours.or(anyone).unwrap_or_default()
+    }
+
+    pub fn allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| matches(pattern, path))
+            // Allow wins a tie
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+/// robots.txt path patterns: a prefix where `*` is any run of characters and a trailing `$`
+/// anchors the end
+fn matches(pattern: &str, path: &str) -> bool {
+    fn glob(pattern: &[u8], path: &[u8], anchored: bool) -> bool {
+        match pattern.split_first() {
+            None => !anchored || path.is_empty(),
+            Some((b'*', rest)) => (0..=path.len()).any(|i| glob(rest, &path[i..], anchored)),
+            Some((c, rest)) => path.first() == Some(c) && glob(rest, &path[1..], anchored),
+        }
+    }
+    match pattern.strip_suffix('$') {
+        Some(pattern) => glob(pattern.as_bytes(), path.as_bytes(), true),
+        None => glob(pattern.as_bytes(), path.as_bytes(), false),
+    }
+}
+
+/// Spaces requests out and checks them against robots.txt, shared by every session like the
+/// rate limiter
+pub struct Politeness {
+    config: PolitenessConfig,
+    robots: Mutex<HashMap<String, (Instant, Robots)>>,
+    last: Mutex<Option<Instant>>,
+}
+
+impl Politeness {
+    pub fn new(config: PolitenessConfig) -> Self {
+        Politeness {
+            config,
+            robots: Mutex::new(HashMap::new()),
+            last: Mutex::new(None),
+        }
+    }
+
+    pub fn user_agent(&self) -> &str {
+        &self.config.user_agent
+    }
+
+    /// Errors if robots.txt disallows `url`, otherwise waits until it is polite to fetch it
+    pub async fn wait_turn(&self, client: &Client, url: &str) -> anyhow::Result<()> {
+        let url = Url::parse(url).with_context(|| format!("Invalid URL '{}'", url))?;
+        let mut interval = Duration::from_secs(self.config.min_interval_secs);
+        if self.config.robots_txt {
+            let origin = url.origin().ascii_serialization();
+            let mut robots = self.robots.lock().await;
+            let stale = robots
+                .get(&origin)
+                .is_none_or(|(fetched, _)| fetched.elapsed() > ROBOTS_TTL);
+            if stale {
+                let fetched = self.fetch_robots(client, &origin).await;
+                robots.insert(origin.clone(), (Instant::now(), fetched));
+            }
+            let rules = &robots[&origin].1;
+            if !rules.allowed(url.path()) {
+                anyhow::bail!("robots.txt disallows {} for {}", url, self.user_agent());
+            }
+            interval = interval.max(rules.crawl_delay.unwrap_or_default());
+        }
+        // Holding the lock while sleeping queues the waiters up in order
+        let mut last = self.last.lock().await;
+        if let Some(wait) = last.and_then(|at| interval.checked_sub(at.elapsed())) {
+            tokio::time::sleep(wait).await;
+        }
+        *last = Some(Instant::now());
+        Ok(())
+    }
+
+    /// A robots.txt that can't be fetched is taken to allow everything
+    async fn fetch_robots(&self, client: &Client, origin: &str) -> Robots {
+        let url = format!("{}/robots.txt", origin);
+        let res = client
+            .get(&url)
+            .header("User-Agent", self.user_agent())
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+        let text = match res {
+            Ok(res) => res.text().await,
+            Err(e) => Err(e),
+        };
+        match text {
+            Ok(text) => Robots::parse(&text, self.user_agent()),
+            Err(e) => {
+                crate::info!("No robots.txt at {}, assuming anything goes: {}", url, e);
+                Robots::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const ROBOTS: &str = "\
+User-agent: *
+Disallow: /admin
+Crawl-delay: 5
+
+User-agent: Googlebot
+User-agent: pcta
+Disallow: /permit/
+Allow: /permit/availability$
+Crawl-delay: 2.5
+";
+
+    #[test]
+    fn our_group_beats_the_wildcard() {
+        let robots = Robots::parse(ROBOTS, "pcta/0.1 (+https://github.com/jryio/pcta)");
+        assert_eq!(robots.crawl_delay, Some(Duration::from_millis(2500)));
+        assert!(robots.allowed("/permit/availability"));
+        assert!(!robots.allowed("/permit/availability/2023"));
+        assert!(robots.allowed("/admin"));
+
+        let robots = Robots::parse(ROBOTS, "somebot/1.0");
+        assert!(!robots.allowed("/admin/users"));
+        assert!(robots.allowed("/permit/new"));
+    }
+
+    #[test]
+    fn wildcards_match_any_run() {
+        assert!(matches("/*.json$", "/api/2023.json"));
+        assert!(!matches("/*.json$", "/api/2023.json?x"));
+        assert!(matches("/api/*/month", "/api/445859/month/x"));
+        assert!(!matches("/api", "/ap"));
+    }
+
+    #[tokio::test]
+    async fn disallowed_urls_are_refused() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/robots.txt"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(ROBOTS))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let polite = Politeness::new(PolitenessConfig {
+            enabled: true,
+            min_interval_secs: 0,
+            ..PolitenessConfig::default()
+        });
+        let client = Client::new();
+        let allowed = format!("{}/permit/availability", server.uri());
+        polite.wait_turn(&client, &allowed).await.unwrap();
+        let refused = format!("{}/permit/new", server.uri());
+        assert!(polite.wait_turn(&client, &refused).await.is_err());
+    }
+}
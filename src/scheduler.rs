@@ -0,0 +1,1226 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::future;
+use rand::Rng;
+use reqwest::{Client, ClientBuilder};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::alerting::Alerter;
+use crate::bot::{self, Control};
+use crate::breaker::{Admit, Breaker, State as BreakerState};
+use crate::config::{Config, ScheduleConfig};
+use crate::ctl;
+use crate::digest::Digest;
+use crate::errors::{Aggregator, Post};
+use crate::event::{Event, Events};
+use crate::forensics;
+use crate::format::{Markdown, Report, Templated};
+use crate::ha::{self, Heartbeat, Role};
+use crate::history::{History, Snapshot};
+use crate::hook::Opened;
+use crate::http;
+use crate::influx::Influx;
+use crate::journal::Journal;
+use crate::notifier::{
+    self, handle_result, keybase_message, Batch, Keybase, KeybaseApi, Notifier, Severity,
+    DEBUG_TOPIC,
+};
+use crate::outbox::Outbox;
+use crate::overview::{self, Overview};
+use crate::pace;
+use crate::proxy::ProxyPool;
+use crate::ratelimit::RateLimiter;
+use crate::reload;
+use crate::replay::Saved;
+use crate::retry;
+use crate::robots::Politeness;
+use crate::scraper::Scraper;
+use crate::session::Session;
+use crate::state::{State, TargetState};
+use crate::store::Store;
+use crate::subscription::Subscriber;
+use crate::systemd;
+use crate::target::{Target, Watch};
+use crate::timekeeping::Clock;
+use crate::vpn::{self, KillSwitch, VpnProvider};
+#[cfg(feature = "web")]
+use crate::web::{self, Dashboard};
+
+/// Bounds of the normal gap between scrapes, in seconds. Each gap is drawn fresh so the
+/// request timing never settles into a pattern.
+const PERIOD_MIN: u64 = 24;
+const PERIOD_MAX: u64 = 40;
+
+/// When the next scrape happens, and whether the office closes before then
+struct Tick {
+    at: DateTime<Utc>,
+    closed: bool,
+}
+
+/// The one place deciding when to scrape: `cron` if configured, otherwise `secs` from now
+/// pushed to the next business hours if that falls outside them
+fn next_tick(schedule: &ScheduleConfig, now: DateTime<Utc>, secs: u64) -> Tick {
+    // Idle a day and look again if nothing would ever fire
+    let fallback = now + chrono::Duration::days(1);
+    if !schedule.cron.is_empty() {
+        let at = schedule.next_cron(now).unwrap_or(fallback);
+        return Tick { at, closed: false };
+    }
+    let at = now + chrono::Duration::seconds(secs as i64);
+    match schedule.is_open(at) {
+        true => Tick { at, closed: false },
+        false => Tick {
+            at: schedule.next_open(at).unwrap_or(fallback),
+            closed: true,
+        },
+    }
+}
+
+/// What one pass over all targets found
+pub(crate) struct Pass {
+    /// Open dates alerted on, summed over all watches
+    pub open: usize,
+    pub failures: Vec<retry::Failure>,
+    /// Whether any calendar had a date for a watch, the season is up
+    pub listed: bool,
+    /// As written to the state file
+    pub state: State,
+}
+
+/// What every profile's loop in the process goes through together
+#[derive(Clone)]
+struct Shared {
+    /// Held while checking or rotating the tunnel, so no loop checks halfway through another's
+    /// rotation
+    vpn: Arc<Mutex<Box<dyn VpnProvider>>>,
+    kill_switch: Arc<KillSwitch>,
+    limiter: Arc<RateLimiter>,
+    history: Arc<History>,
+    store: Option<Arc<Store>>,
+    events: Arc<Events>,
+}
+
+/// What lives for the whole run and shapes how results go out
+struct Context {
+    keybase: Keybase,
+    clock: Clock,
+    alerter: Alerter,
+    digest: Digest,
+    overview: Overview,
+    errors: Aggregator,
+    notifiers: Vec<Box<dyn Notifier>>,
+    history: Arc<History>,
+    /// Where alerts are claimed so other instances don't send them again
+    store: Option<Arc<Store>>,
+    /// Alerts waiting for Keybase to take them
+    outbox: Outbox,
+    /// Log lines held for `[keybase.batch]`
+    logs: Batch,
+    events: Arc<Events>,
+    /// Where each pass's calendars are pushed as time series
+    influx: Option<Influx>,
+}
+
+impl Context {
+    fn new(
+        scraper: &Scraper,
+        history: Arc<History>,
+        store: Option<Arc<Store>>,
+        events: Arc<Events>,
+    ) -> anyhow::Result<Self> {
+        let config = &scraper.config;
+        let clock = Clock::new(config.display.timezone);
+        Ok(Context {
+            keybase: Keybase {
+                dry_run: scraper.dry_run,
+            },
+            clock,
+            alerter: Alerter::new(config.alerting.clone()),
+            digest: Digest::new(
+                &config.digest,
+                config.schedule.business_hours.tz,
+                clock.now(),
+            ),
+            overview: Overview::new(
+                &config.overview,
+                config.schedule.business_hours.tz,
+                clock.now(),
+            ),
+            errors: Aggregator::new(config.errors.clone()),
+            notifiers: notifier::from_config(&config.notifiers, http::client(&config.http)?)?,
+            history,
+            store,
+            outbox: Outbox::open(config.outbox.clone())?,
+            logs: Batch::new(config.keybase.batch.clone()),
+            events,
+            influx: Influx::new(&config.influx, http::client(&config.http)?),
+        })
+    }
+
+    /// Posts a log line, or holds it for the next batch
+    async fn log(&mut self, msg: KeybaseApi, at: DateTime<Utc>) {
+        for msg in self.logs.push(msg, at) {
+            self.keybase.post(&msg).await;
+        }
+    }
+
+    /// Posts the held log lines once the batch is due, or all of them with `all`
+    async fn flush_logs(&mut self, at: DateTime<Utc>, all: bool) {
+        let due = match all {
+            true => self.logs.take(),
+            false => self.logs.due(at),
+        };
+        for msg in due {
+            self.keybase.post(&msg).await;
+        }
+    }
+
+    /// Posts an alert, behind the ones still waiting in the outbox. The notifiers had it
+    /// already, so they are told once when Keybase starts missing alerts.
+    async fn deliver(&mut self, msg: KeybaseApi, at: DateTime<Utc>, now: &str) {
+        let first = self.outbox.is_empty();
+        if let Some(error) = self.outbox.post(self.keybase, msg, at).await {
+            let waiting = self.outbox.len();
+            crate::info!("{} - Alert queued, {} waiting: {:#}", now, waiting, error);
+            if first {
+                let label = "Keybase";
+                self.notify(
+                    &Report::Failed {
+                        label,
+                        error: &error,
+                    },
+                    at,
+                    now,
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Says which of the dates `watch` alerted on filled up since, as `[alerting] closed`
+    /// routes it. Alerts among them go out with the pass's others, or wait out the quiet hours.
+    async fn closed(
+        &mut self,
+        watch: &Watch,
+        alerts: &mut Vec<KeybaseApi>,
+        at: DateTime<Utc>,
+        now: &str,
+    ) {
+        let severity = self.alerter.policy().closed;
+        let topic = match severity {
+            Severity::Debug => DEBUG_TOPIC,
+            Severity::Info => "pcta-logs",
+            Severity::Alert => &watch.channel,
+            Severity::Error => "pcta-errors",
+        };
+        for closed in self.alerter.closed(&watch.name) {
+            self.events.emit(Event::DateClosed {
+                label: watch.name.clone(),
+                closed_at: now.to_string(),
+                date: closed.date,
+                open_minutes: closed.open_for.num_minutes(),
+            });
+            let msg = keybase_message(topic, closed.message(&watch.name, now));
+            match severity {
+                Severity::Alert if self.alerter.is_quiet(at) => self.alerter.hold(msg),
+                Severity::Alert => alerts.push(msg),
+                _ => self.log(msg, at).await,
+            }
+        }
+    }
+
+    /// The part of `due` no other instance alerted on already. When that can't be told the
+    /// alert goes out, twice is better than never.
+    async fn claim(
+        &self,
+        watch: &str,
+        due: Vec<(NaiveDate, u64)>,
+        cooldown_secs: u64,
+        now: &str,
+    ) -> Vec<(NaiveDate, u64)> {
+        let Some(store) = &self.store else {
+            return due;
+        };
+        let mut claimed = vec![];
+        for (date, remaining) in due {
+            match store.claim(watch, date, remaining, cooldown_secs).await {
+                Ok(true) => claimed.push((date, remaining)),
+                Ok(false) => {
+                    crate::info!(
+                        "{} - {} {} was alerted by another instance",
+                        now,
+                        watch,
+                        date
+                    );
+                }
+                Err(e) => {
+                    crate::info!("{} - Claiming the {} alert failed: {:#}", now, watch, e);
+                    claimed.push((date, remaining));
+                }
+            }
+        }
+        claimed
+    }
+
+    /// Hands `report` to the configured notifiers. The ones that would wake someone during
+    /// quiet hours get it once those end instead.
+    async fn notify(&mut self, report: &Report<'_>, at: DateTime<Utc>, now: &str) {
+        self.events.emit(Event::from_report(report, now));
+        let quiet = self.alerter.is_quiet(at);
+        if quiet && self.notifiers.iter().any(|n| n.pings_people()) {
+            self.alerter.hold_report(report);
+        }
+        self.tell(
+            report,
+            |notifier| !quiet || !notifier.pings_people(),
+            at,
+            now,
+        )
+        .await;
+    }
+
+    /// Hands what the notifiers that ping people missed during the quiet hours to them, once
+    /// they are over
+    async fn release_held(&mut self, at: DateTime<Utc>, now: &str) {
+        for held in self.alerter.release_reports(at) {
+            self.tell(&held.report(), |notifier| notifier.pings_people(), at, now)
+                .await;
+        }
+    }
+
+    /// Hands `report` to the notifiers `to` picks. Keybase gets the message too, so one of them
+    /// failing only goes to the errors topic, repeats collapsed like a target's.
+    async fn tell(
+        &mut self,
+        report: &Report<'_>,
+        to: impl Fn(&dyn Notifier) -> bool,
+        at: DateTime<Utc>,
+        now: &str,
+    ) {
+        for notifier in &self.notifiers {
+            if !to(notifier.as_ref()) {
+                continue;
+            }
+            if self.keybase.dry_run {
+                crate::info!("[dry run] Would notify {}", notifier.name());
+                continue;
+            }
+            let label = format!("{} notifier", notifier.name());
+            let msg = match notifier.notify(report, now).await {
+                Ok(()) => self.errors.recovered(&label, at),
+                Err(e) => {
+                    crate::info!("{} - {} failed: {:#}", now, label, e);
+                    match self.errors.failed(&label, &format!("{:#}", e), at) {
+                        Post::First => Some(format!("*{} failed*: {:#}", label, e)),
+                        Post::Repeat(summary) => Some(summary),
+                        Post::Quiet => None,
+                    }
+                }
+            };
+            if let Some(msg) = msg {
+                self.keybase
+                    .send("pcta-errors", format!("`{}` - {}", now, msg))
+                    .await;
+            }
+        }
+    }
+}
+
+/// Scrapes every target once, concurrently with `schedule.parallel`, and posts the alerts for
+/// its watches, or its error. One target failing never stops the others being handled, and the
+/// alerts of the pass go out as one message per topic.
+async fn scrape_targets(
+    scraper: &Scraper,
+    cx: &mut Context,
+    session: &Session,
+    proxy: Option<&str>,
+    targets: &[Target],
+    subscribers: &[Subscriber],
+    at: DateTime<Utc>,
+) -> anyhow::Result<Pass> {
+    let config = &scraper.config;
+    let (keybase, clock) = (cx.keybase, cx.clock);
+    let now = &clock.format(at);
+    let formatter = Templated {
+        templates: &config.templates,
+        fallback: Markdown {
+            table: config.alerting.table,
+        },
+    };
+    cx.outbox.flush(keybase, at).await;
+    let mut pass = Pass {
+        open: 0,
+        failures: vec![],
+        listed: false,
+        state: State {
+            scraped_at: clock.stamp(at),
+            targets: vec![],
+        },
+    };
+    // Transient failures are retried in place, only blocks and exhausted retries escalate
+    let scrape = |target| retry::with_backoff(move || scraper.scrape(target, session, proxy));
+    let results = match config.schedule.parallel {
+        true => future::join_all(targets.iter().map(scrape)).await,
+        false => {
+            let mut results = vec![];
+            for target in targets {
+                results.push(scrape(target).await);
+            }
+            results
+        }
+    };
+    // Alerts of this pass, posted together at the end
+    let mut alerts = vec![];
+    let mut points = vec![];
+    for (target, res) in targets.iter().zip(results) {
+        cx.digest.scraped(res.is_err());
+        cx.events.emit(Event::Scraped {
+            label: target.label(),
+            scraped_at: now.to_string(),
+            changed: res.as_ref().is_ok_and(|scraped| scraped.changed),
+            days: res.as_ref().map_or(0, |scraped| scraped.days.len()),
+            error: res.as_ref().err().map(|e| format!("{:#}", e)),
+        });
+        if let Ok(scraped) = &res {
+            cx.digest.timed(&scraped.timings);
+            pass.listed |= scraped.listed;
+            let span = target.span(scraped.days.iter().map(|(day, _)| *day));
+            if let Some((influx, (start, end))) = cx.influx.as_ref().zip(span) {
+                points.extend(influx.points(&target.label(), start, end, &scraped.days, at));
+            }
+        }
+        if res.is_ok() {
+            if let Some(msg) = cx.errors.recovered(&target.label(), at) {
+                let msg = format!("`{}` - {}", now, msg);
+                cx.log(keybase_message("pcta-logs", msg), at).await;
+            }
+        }
+        match &res {
+            Ok(scraped) if !scraped.changed => {
+                for watch in target.watches() {
+                    let open = watch.open_dates(&scraped.days, clock.today(at));
+                    pass.open += open.len();
+                    // Another scrape a date has been closed for all the same
+                    let holding = watch.holding(&scraped.days, clock.today(at));
+                    cx.alerter.track(&watch.name, &holding, at);
+                    cx.closed(&watch, &mut alerts, at, now).await;
+                    cx.digest.watch(&watch.name, open);
+                }
+                let msg = format!("{} - No change at {}", now, target.label());
+                crate::debug!("{}", msg);
+                cx.log(keybase_message(DEBUG_TOPIC, msg), at).await;
+            }
+            Ok(scraped) => {
+                let apply_url = scraper.apply_url(target);
+                for watch in target.watches() {
+                    let open = watch.open_dates(&scraped.days, clock.today(at));
+                    pass.open += open.len();
+                    let holding = watch.holding(&scraped.days, clock.today(at));
+                    let due = cx.alerter.due(&watch.name, &open, &holding, at);
+                    cx.closed(&watch, &mut alerts, at, now).await;
+                    let due = cx
+                        .claim(&watch.name, due, config.alerting.cooldown_secs, now)
+                        .await;
+                    cx.digest.watch(&watch.name, open.clone());
+                    cx.digest.alerted(due.len());
+                    if due.is_empty() && !open.is_empty() {
+                        crate::info!("{} - {} still open, alerted recently", now, watch.name);
+                        continue;
+                    }
+                    let open = watch.ranked(due, clock.today(at));
+                    if config.hook.enabled() {
+                        config.hook.fire(
+                            &Opened {
+                                watch: &watch.name,
+                                target: &target.label(),
+                                apply_url: &apply_url,
+                                dates: &open,
+                            },
+                            scraper.dry_run,
+                        );
+                    }
+                    let paces = match config.alerting.pace_over {
+                        0 => vec![],
+                        n => pace::paces(&cx.history.latest(&target.label(), n - 1), &open, at),
+                    };
+                    let report = match open.is_empty() {
+                        true => Report::Nothing { label: &watch.name },
+                        false => Report::Open {
+                            label: &watch.name,
+                            dates: &open,
+                            subscribers,
+                            apply_url: &apply_url,
+                            paces: &paces,
+                        },
+                    };
+                    if !open.is_empty() {
+                        cx.notify(&report, at, now).await;
+                    }
+                    let msg = handle_result(&report, &watch.channel, now, &formatter)?;
+                    if !open.is_empty() && cx.alerter.is_quiet(at) {
+                        crate::info!("{} - Quiet hours, holding the {} alert", now, watch.name);
+                        cx.alerter.hold(msg);
+                        continue;
+                    }
+                    match open.is_empty() {
+                        true => {
+                            cx.events.emit(Event::from_report(&report, now));
+                            cx.log(msg, at).await
+                        }
+                        false => alerts.push(msg),
+                    }
+                }
+            }
+            Err(e) => {
+                let label = target.label();
+                match cx.errors.failed(&label, &forensics::unkept(e), at) {
+                    Post::First => {
+                        let report = Report::Failed {
+                            label: &label,
+                            error: e,
+                        };
+                        let msg = handle_result(&report, "pcta-alerts", now, &formatter)?;
+                        keybase.post(&msg).await;
+                        cx.notify(&report, at, now).await;
+                    }
+                    Post::Repeat(summary) => {
+                        keybase
+                            .send("pcta-errors", format!("`{}` - {}", now, summary))
+                            .await;
+                    }
+                    Post::Quiet => crate::info!("{} - {} failed the same way again", now, label),
+                }
+                pass.failures.push(retry::classify(e));
+            }
+        }
+        if let Some(scraped) = res.as_ref().ok().filter(|scraped| scraped.changed) {
+            let recorded = cx.history.record(Snapshot {
+                at,
+                label: target.label(),
+                days: scraped.days.clone(),
+            });
+            // A history that can't be written must not cost the pass its alerts
+            if let Err(e) = recorded {
+                crate::info!("{} - Failed to record {}: {:#}", now, target.label(), e);
+            }
+        }
+        pass.state.targets.push(TargetState {
+            label: target.label(),
+            error: res.as_ref().err().map(|e| format!("{:#}", e)),
+            timings: res.as_ref().ok().map(|scraped| scraped.timings),
+            open: res.map(|scraped| scraped.days).unwrap_or_default(),
+        });
+        match pass.state.targets.last().and_then(|t| t.timings) {
+            Some(timings) => crate::info!(
+                "{} - Completed a scrape of {} in {}",
+                now,
+                target.label(),
+                timings
+            ),
+            None => crate::info!("{} - Completed a scrape of {}", now, target.label()),
+        }
+    }
+    for msg in notifier::combined(alerts) {
+        cx.deliver(msg, at, now).await;
+    }
+    for msg in cx.alerter.release(at, now) {
+        cx.deliver(msg, at, now).await;
+    }
+    cx.release_held(at, now).await;
+    if let Some(influx) = &cx.influx {
+        // A metrics server that is down only leaves a gap in the charts
+        if let Err(e) = influx.write(&points).await {
+            crate::info!("{} - Failed to push to InfluxDB: {:#}", now, e);
+        }
+    }
+    if let Some(path) = config.state.file() {
+        pass.state.save(path)?;
+    }
+    Ok(pass)
+}
+
+async fn open_history(config: &Config, store: Option<Arc<Store>>) -> anyhow::Result<History> {
+    let history = match store {
+        Some(store) => History::shared(store).await?,
+        None => History::open(config.state.history())?,
+    };
+    Ok(history.keep(config.memory.history_limit()))
+}
+
+fn rate_limiter(config: &Config) -> Arc<RateLimiter> {
+    Arc::new(RateLimiter::new(
+        config.rate_limit.requests_per_minute,
+        config.rate_limit.burst,
+    ))
+}
+
+/// The first identity, rate limited by `limiter` and polite as configured
+fn open_session(
+    scraper: &Scraper,
+    builder: ClientBuilder,
+    limiter: Arc<RateLimiter>,
+) -> anyhow::Result<Session> {
+    let config = &scraper.config;
+    let session = Session::new(builder, config.session.cookie_file())?
+        .max_body(config.http.max_body_bytes)
+        .limited(limiter)
+        .save_bodies(scraper.save_bodies.clone())
+        .debug_dir(config.debug.dir().map(Path::to_path_buf));
+    let session = match config.politeness.enabled {
+        true => session.polite(Arc::new(Politeness::new(config.politeness.clone()))),
+        false => session,
+    };
+    let session = match config.memory.low {
+        true => session.uncached(),
+        false => session,
+    };
+    Ok(match config.http.compression {
+        true => session,
+        false => session.uncompressed(),
+    })
+}
+
+/// A single pass for `pcta once`, leaving scheduling and VPN rotation to whatever runs it
+pub(crate) async fn once(scraper: Scraper, proxies: ProxyPool) -> anyhow::Result<Pass> {
+    let config = &scraper.config;
+    let cookie_file = config.session.cookie_file();
+    let session = open_session(&scraper, proxies.builder()?, rate_limiter(config))?;
+    let store = Store::open(&config.redis)?.map(Arc::new);
+    let history = Arc::new(open_history(config, store.clone()).await?);
+    notifier::set_channels(&config.keybase.channels);
+    let events = Arc::new(Events::default().journal(Journal::open(config.journal.clone())?));
+    let mut cx = Context::new(&scraper, history, store, events)?;
+    let at = cx.clock.now();
+    warn_over(config, &cx, at).await;
+    let pass = scrape_targets(
+        &scraper,
+        &mut cx,
+        &session,
+        proxies.current(),
+        &config.targets,
+        &config.subscribers,
+        at,
+    )
+    .await?;
+    cx.flush_logs(at, true).await;
+    if let Some(path) = cookie_file {
+        session.save_cookies(path)?;
+    }
+    Ok(pass)
+}
+
+/// Behind `Scraper::replay`: each of `saved` as one pass over the target it was fetched for,
+/// stamped with when it was
+pub(crate) async fn replay(mut scraper: Scraper, saved: Vec<Saved>) -> anyhow::Result<()> {
+    let session = Session::new(ClientBuilder::new(), None)?;
+    notifier::set_channels(&scraper.config.keybase.channels);
+    let history = Arc::new(History::open(None)?);
+    let mut cx = Context::new(&scraper, history, None, Arc::new(Events::default()))?;
+    let (mut passes, mut open) = (0, 0);
+    for body in saved {
+        let Some(target) = scraper.target_for(&body.key).cloned() else {
+            crate::info!("No target in the config saves `{}`, skipping", body.key);
+            continue;
+        };
+        crate::info!("Replaying {}", body.path.display());
+        scraper.replaying = Some(body.clone());
+        let subscribers = scraper.config.subscribers.clone();
+        let pass = scrape_targets(
+            &scraper,
+            &mut cx,
+            &session,
+            None,
+            &[target],
+            &subscribers,
+            body.at,
+        )
+        .await?;
+        passes += 1;
+        open += pass.open;
+    }
+    cx.flush_logs(cx.clock.now(), true).await;
+    crate::info!("Replayed {} passes, {} open dates alerted on", passes, open);
+    Ok(())
+}
+
+/// Reconnects the VPN and verifies the new exit, `true` if the IP changed. Without a VPN that
+/// can rotate there is nothing to do or say.
+async fn reconnect(
+    scraper: &Scraper,
+    shared: &Shared,
+    echo_client: &Client,
+    keybase: Keybase,
+    now: &str,
+) -> anyhow::Result<bool> {
+    let vpn = shared.vpn.lock().await;
+    if !vpn.can_rotate() {
+        return Ok(false);
+    }
+    if scraper.dry_run {
+        crate::info!("[dry run] Would reconnect {} VPN", vpn.name());
+        return Ok(false);
+    }
+    match vpn::rotate_verified(
+        vpn.as_ref(),
+        echo_client,
+        &scraper.config.vpn.expected_country,
+    )
+    .await
+    {
+        Ok(exit) => {
+            let msg = format!("`{}` - *Reconnected to the VPN*, exit `{}`", now, exit);
+            crate::info!("{}", msg);
+            keybase.send("pcta-logs", msg).await;
+            shared.events.emit(Event::VpnRotated {
+                at: now.to_string(),
+                exit: exit.to_string(),
+            });
+            Ok(true)
+        }
+        Err(e) => {
+            let msg = format!("`{}` - *VPN rotation silently failed*: {:#}", now, e);
+            crate::info!("{}", msg);
+            keybase.send("pcta-errors", msg).await;
+            Ok(false)
+        }
+    }
+}
+
+/// Reads the config file again and takes over what applies without a restart. A config that
+/// doesn't load is reported and the running one kept.
+async fn reload(scraper: &mut Scraper, cx: &mut Context, now: &str) -> anyhow::Result<()> {
+    let keybase = cx.keybase;
+    let changes = Config::load().and_then(|config| scraper.reconfigure(config));
+    let changes = match changes {
+        Ok(changes) => changes,
+        Err(e) => {
+            let msg = format!(
+                "`{}` - *Config reload failed*, keeping the old one: {:#}",
+                now, e
+            );
+            crate::info!("{}", msg);
+            keybase.send("pcta-errors", msg).await;
+            return Ok(());
+        }
+    };
+    let config = &scraper.config;
+    cx.alerter.reconfigure(config.alerting.clone());
+    cx.errors.reconfigure(config.errors.clone());
+    match notifier::from_config(&config.notifiers, http::client(&config.http)?) {
+        Ok(notifiers) => cx.notifiers = notifiers,
+        Err(e) => crate::info!("{} - Keeping the old notifiers: {:#}", now, e),
+    }
+    let msg = match &scraper.profile {
+        Some(name) => format!("`{}` {}", name, reload::announce(&changes, now)),
+        None => reload::announce(&changes, now),
+    };
+    crate::info!("{}", msg);
+    let at = cx.clock.now();
+    cx.log(keybase_message("pcta-logs", msg), at).await;
+    Ok(())
+}
+
+/// Behind `Scraper::run`: a scrape loop per profile and what the process has once for all of
+/// them. When one loop stops with an error the process does.
+pub(crate) async fn run(
+    scraper: Scraper,
+    vpn: Box<dyn VpnProvider>,
+    kill_switch: KillSwitch,
+) -> anyhow::Result<()> {
+    let config = &scraper.config;
+    let keybase = Keybase {
+        dry_run: scraper.dry_run,
+    };
+    let clock = Clock::new(config.display.timezone);
+    notifier::set_channels(&config.keybase.channels);
+    let store = Store::open(&config.redis)?.map(Arc::new);
+    let shared = Shared {
+        vpn: Arc::new(Mutex::new(vpn)),
+        kill_switch: Arc::new(kill_switch),
+        limiter: rate_limiter(config),
+        history: Arc::new(open_history(config, store.clone()).await?),
+        store,
+        events: Arc::new(Events::default().journal(Journal::open(config.journal.clone())?)),
+    };
+
+    let names: Vec<String> = config.profiles.iter().map(|p| p.name.clone()).collect();
+    let control = Control::with_profiles(config.subscribers.clone(), &names);
+    if config.bot.enabled && !scraper.dry_run {
+        let control = control.clone();
+        tokio::spawn(async move {
+            // The scraper keeps going without its chat commands
+            if let Err(e) = bot::listen(notifier::TEAM, keybase, control).await {
+                crate::info!("Chat bot stopped: {:#}", e);
+            }
+        });
+    }
+
+    if let Some(path) = config.control.socket() {
+        let (path, control) = (path.to_path_buf(), control.clone());
+        tokio::spawn(async move {
+            if let Err(e) = ctl::serve(&path, control).await {
+                crate::info!("Control socket stopped: {:#}", e);
+            }
+        });
+    }
+    #[cfg(not(feature = "web"))]
+    if scraper.web.is_some() {
+        crate::info!("Not serving the web UI, pcta was built without the `web` feature");
+    }
+    #[cfg(feature = "web")]
+    if let Some(addr) = scraper.web {
+        let dashboard = Dashboard {
+            control: control.clone(),
+            history: shared.history.clone(),
+            events: shared.events.clone(),
+            clock,
+        };
+        tokio::spawn(async move {
+            // Like the bot, the scraper keeps going without it
+            if let Err(e) = web::serve(addr, dashboard).await {
+                crate::info!("Web UI stopped: {:#}", e);
+            }
+        });
+    }
+
+    if config.ha.role != Role::Off {
+        let (ha, control) = (config.ha.clone(), control.clone());
+        let heartbeat: Arc<dyn Heartbeat> = match &shared.store {
+            Some(store) => store.clone(),
+            None => Arc::from(ha.heartbeat()),
+        };
+        tokio::spawn(async move {
+            // Without it a standby would stand by forever, say so loudly
+            if let Err(e) = ha::run(ha, heartbeat, control, keybase, clock).await {
+                let msg = format!("*Heartbeat stopped*: {:#}", e);
+                crate::info!("{}", msg);
+                keybase.send("pcta-errors", msg).await;
+            }
+        });
+    }
+
+    // Dropping the watcher stops it, so it lives as long as the loop
+    let _watcher = match reload::watch(Path::new(&Config::path()), control.clone()) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            crate::info!("Not watching the config file for changes: {:#}", e);
+            None
+        }
+    };
+    reload::on_sighup(control.clone())?;
+
+    systemd::notify("READY=1");
+    let loops = scraper.pipelines().into_iter().map(|scraper| {
+        let control = match &scraper.profile {
+            Some(name) => control.profile(name).unwrap_or_else(|| control.clone()),
+            None => control.clone(),
+        };
+        tokio::spawn(pipeline(scraper, shared.clone(), control))
+    });
+    let (stopped, _, _) = future::select_all(loops).await;
+    stopped?
+}
+
+/// Says loudly on the errors channel which targets' windows are behind us. The scraper keeps
+/// going, their dates just can't open again.
+async fn warn_over(config: &Config, cx: &Context, at: DateTime<Utc>) {
+    let today = cx.clock.today(at);
+    let over = config
+        .targets
+        .iter()
+        .filter_map(|target| Some((target, target.end.filter(|end| *end < today)?)));
+    for (target, end) in over {
+        let msg = format!(
+            "`{}` - *{} is over*: its window ended on {}, move it to this season in the config",
+            cx.clock.format(at),
+            target.label(),
+            end
+        );
+        crate::info!("{}", msg);
+        cx.keybase.send("pcta-errors", msg).await;
+    }
+}
+
+/// One profile's scrape loop
+async fn pipeline(
+    mut scraper: Scraper,
+    shared: Shared,
+    control: Arc<Control>,
+) -> anyhow::Result<()> {
+    let mut proxies = scraper.proxies()?;
+    let config = &scraper.config;
+    let cookie_file = config.session.cookie_file().map(Path::to_path_buf);
+    let clear_cookies = config.session.clear_cookies_on_rotate;
+    let mut session = open_session(&scraper, proxies.builder()?, shared.limiter.clone())?;
+    // Exit-IP checks verify the tunnel, so they must not go out through a proxy
+    let echo_client = http::bound_client(&config.http)?;
+
+    let mut cx = Context::new(
+        &scraper,
+        shared.history.clone(),
+        shared.store.clone(),
+        shared.events.clone(),
+    )?;
+    let (keybase, clock) = (cx.keybase, cx.clock);
+    warn_over(config, &cx, clock.now()).await;
+    let mut breaker = Breaker::new(config.breaker.clone());
+    // Whether the last pass was skipped by the kill switch, to only say so once
+    let mut exposed = false;
+    // Whether the season's calendar isn't up yet and scrapes are spaced out
+    let mut off_season = false;
+    // Whether the last tick was spent standing by, the shared history is stale once it wasn't
+    let mut stood_by = false;
+    let mut next = next_tick(&config.schedule, clock.now(), 0).at;
+
+    loop {
+        control.set_next(next);
+        let reload_requested = tokio::select! {
+            _ = systemd::sleep((next - clock.now()).to_std().unwrap_or_default()) => false,
+            _ = control.scrape_requested() => {
+                crate::info!("{} - Scrape requested", clock.format(clock.now()));
+                false
+            }
+            _ = control.reload_requested() => true,
+        };
+        if reload_requested {
+            // Then back to waiting for the scrape that was due anyway
+            reload(&mut scraper, &mut cx, &clock.format(clock.now())).await?;
+            continue;
+        }
+        let config = &scraper.config;
+
+        let at = clock.now();
+        let now = clock.format(at);
+        if let Some(msg) = cx.digest.take(at, &now, clock.uptime()) {
+            keybase.send(&config.digest.channel, msg).await;
+        }
+        if cx.overview.due(at) {
+            let msg = overview::season(&config.targets, &cx.history, at, &now);
+            keybase.send(&config.overview.channel, msg).await;
+        }
+        cx.flush_logs(at, false).await;
+        let jittered = rand::thread_rng().gen_range(PERIOD_MIN..=PERIOD_MAX);
+        let secs = config.schedule.interval(clock.now(), jittered);
+        let tick = next_tick(&config.schedule, clock.now(), secs);
+        next = tick.at;
+
+        if control.paused() {
+            crate::info!("{} - Paused, skipping scrape", now);
+            continue;
+        }
+        if control.standing_by() {
+            crate::info!("{} - Standing by while the primary is up", now);
+            stood_by = true;
+            continue;
+        }
+        if std::mem::take(&mut stood_by) {
+            // Alerts go by what the primary last saw, not what this instance did before
+            if let Err(e) = shared.history.refresh().await {
+                crate::info!("{} - Failed to read the shared history: {:#}", now, e);
+            }
+        }
+
+        let admit = breaker.admit(at);
+        if let Admit::No(until) = admit {
+            crate::info!("{} - Cooling down until {}", now, clock.format(until));
+            next = next.max(until);
+            continue;
+        }
+        let checked = shared
+            .kill_switch
+            .check(shared.vpn.lock().await.as_ref(), &echo_client)
+            .await;
+        if let Err(e) = checked {
+            let msg = format!("`{}` - *Not scraping, the VPN looks down*: {:#}", now, e);
+            crate::info!("{}", msg);
+            if !exposed {
+                keybase.send("pcta-errors", msg).await;
+            }
+            exposed = true;
+            continue;
+        }
+        if exposed {
+            let msg = format!("`{}` - *VPN is back*, scraping again", now);
+            crate::info!("{}", msg);
+            keybase.send("pcta-errors", msg).await;
+            exposed = false;
+        }
+
+        let mut targets = &config.targets[..];
+        if admit == Admit::Probe {
+            crate::info!("{} - Cool-down over, probing with a fresh identity", now);
+            reconnect(&scraper, &shared, &echo_client, keybase, &now).await?;
+            session = session.rotate(proxies.builder()?, clear_cookies)?;
+            targets = &targets[..targets.len().min(1)];
+        }
+
+        if session.exhausted(config.session.max_requests) {
+            crate::info!("{} - Session used up, rotating identity", now);
+            session = session.rotate(proxies.builder()?, clear_cookies)?;
+        }
+
+        let pass = scrape_targets(
+            &scraper,
+            &mut cx,
+            &session,
+            proxies.current(),
+            targets,
+            &control.subscribers(),
+            at,
+        )
+        .await?;
+        let failures = pass.failures;
+        control.scraped(pass.state);
+        let all_failed = !failures.is_empty() && failures.len() == targets.len();
+        if let Some(pause) = cx.errors.pass(all_failed) {
+            next = clock.now() + pause;
+            let msg = format!(
+                "`{}` - *Pausing scraping for {}m* after {} passes in a row where everything failed",
+                now,
+                pause.num_minutes(),
+                config.errors.pause_after
+            );
+            crate::info!("{}", msg);
+            keybase.send("pcta-errors", msg).await;
+            cx.events.emit(Event::SchedulerPaused {
+                at: now.clone(),
+                minutes: pause.num_minutes(),
+            });
+        }
+        // Only a pass where every calendar was read can tell the season isn't up
+        let off = !pass.listed && config.schedule.off_season_secs > 0;
+        if failures.is_empty() && off != off_season {
+            let msg = match off {
+                true => format!(
+                    "`{}` - *No dates for any watch in the calendar yet*, scraping every {}m until the season's calendar is up",
+                    now,
+                    config.schedule.off_season_secs / 60
+                ),
+                false => format!(
+                    "`{}` - *The season's calendar is up*, back to scraping every {}-{}s",
+                    now, PERIOD_MIN, PERIOD_MAX
+                ),
+            };
+            crate::info!("{}", msg);
+            cx.log(keybase_message("pcta-logs", msg), at).await;
+            off_season = off;
+        }
+        if off_season {
+            let secs = config.schedule.off_season_secs as i64;
+            next = next.max(clock.now() + chrono::Duration::seconds(secs));
+        }
+        if let Some(path) = &cookie_file {
+            session.save_cookies(path)?;
+        }
+        control.set_status(format!(
+            "Last scrape `{}`: {} open dates across {} targets, {} failed",
+            now,
+            pass.open,
+            config.targets.len(),
+            failures.len()
+        ));
+
+        // Reconnect to the VPN to try and get around IP blocking. A parse failure is our problem,
+        // not the IP's, so a new tunnel wouldn't help there.
+        let failure = [retry::Failure::Blocked, retry::Failure::Transient]
+            .into_iter()
+            .find(|f| failures.contains(f))
+            .or(failures.first().copied());
+        if let Some(msg) = breaker.record(failures.contains(&retry::Failure::Blocked), at) {
+            let msg = format!("`{}` - {}", now, msg);
+            crate::info!("{}", msg);
+            keybase.send("pcta-errors", msg).await;
+        }
+        if let BreakerState::Open { until } = breaker.state() {
+            // Tripped, the cool-down's probe brings its own identity
+            next = next.max(until);
+            continue;
+        }
+        if let Some(failure) = failure.filter(|f| *f != retry::Failure::Fatal) {
+            crate::info!("{} - Scrape failed as {:?}", now, failure);
+            if reconnect(&scraper, &shared, &echo_client, keybase, &now).await? {
+                // New IP, new browser
+                session = session.rotate(proxies.builder()?, clear_cookies)?;
+            }
+        }
+
+        // Without a VPN to rotate, the proxies are the only way to a new IP
+        let rotate_proxy = match shared.vpn.lock().await.can_rotate() {
+            true => failure == Some(retry::Failure::Blocked),
+            false => failure.is_some_and(|f| f != retry::Failure::Fatal),
+        };
+        if proxies.after_tick(rotate_proxy) {
+            session = session.rotate(proxies.builder()?, clear_cookies)?;
+            let proxy = proxies.current_redacted().unwrap_or_default();
+            let msg = format!("`{}` - *Rotated to proxy* `{}`", now, proxy);
+            cx.events.emit(Event::ProxyRotated {
+                at: now.clone(),
+                proxy: proxy.to_string(),
+            });
+            crate::info!("{}", msg);
+            cx.log(keybase_message("pcta-logs", msg), at).await;
+        }
+
+        let wait = next - clock.now();
+        if tick.closed {
+            // Business hours are over, say so once instead of every tick
+            let msg = format!(
+                "Not scraping outside business hours {}. Next scrape in : {}h {}m {}s",
+                config.schedule.business_hours,
+                wait.num_hours(),
+                wait.num_minutes() % 60,
+                wait.num_seconds() % 60
+            );
+            crate::info!("{}", msg);
+            cx.log(keybase_message("pcta-logs", msg), at).await;
+        }
+        crate::info!(
+            "{} - {} - Seconds until next scrape",
+            now,
+            wait.num_seconds()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn interval_waits_for_business_hours() {
+        let schedule = ScheduleConfig::default();
+        // 16:59:50 PDT, 30s later the office is closed until 09:00 the next day
+        let tick = next_tick(&schedule, utc("2023-04-17T23:59:50Z"), 30);
+        assert!(tick.closed);
+        assert_eq!(tick.at, utc("2023-04-18T16:00:00Z"));
+        let tick = next_tick(&schedule, utc("2023-04-17T20:00:00Z"), 30);
+        assert!(!tick.closed);
+        assert_eq!(tick.at, utc("2023-04-17T20:00:30Z"));
+    }
+
+    #[test]
+    fn cron_replaces_hours_and_interval() {
+        let schedule: ScheduleConfig = toml::from_str(
+            r#"
+            skip_weekdays = ["Sat", "Sun"]
+            cron = ["*/30 * 9-16 * * *", "0 */10 0-8,17-23 * * *"]
+            "#,
+        )
+        .unwrap();
+        // Friday night PDT
+        let at = |now: &str| next_tick(&schedule, utc(now), 0).at;
+        assert_eq!(at("2023-04-15T02:03:00Z"), utc("2023-04-15T02:10:00Z"));
+        assert_eq!(at("2023-04-17T20:00:10Z"), utc("2023-04-17T20:00:30Z"));
+        // Saturday and Sunday are skipped entirely
+        assert_eq!(at("2023-04-15T06:55:00Z"), utc("2023-04-17T07:00:00Z"));
+    }
+    #[tokio::test]
+    async fn no_vpn_is_never_rotated() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        // Every exit IP lookup would go through here
+        let echo = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&echo)
+            .await;
+        let echo_client = Client::builder()
+            .proxy(reqwest::Proxy::all(echo.uri()).unwrap())
+            .build()
+            .unwrap();
+        let vpn: Box<dyn VpnProvider> = Box::new(vpn::NoVpn);
+        let shared = Shared {
+            vpn: Arc::new(Mutex::new(vpn)),
+            kill_switch: Arc::new(KillSwitch::off()),
+            limiter: Arc::new(RateLimiter::new(60, 1)),
+            history: Arc::new(History::open(None).unwrap()),
+            store: None,
+            events: Arc::new(Events::default()),
+        };
+        let mut events = shared.events.subscribe();
+        let scraper = Scraper::new(Config::default());
+        let keybase = Keybase::default();
+        let rotated = reconnect(&scraper, &shared, &echo_client, keybase, "now").await;
+        assert!(!rotated.unwrap());
+        assert!(events.try_recv().is_err());
+    }
+
+    #[cfg(feature = "ntfy")]
+    #[tokio::test]
+    async fn quiet_hours_hold_pings_until_morning() {
+        use wiremock::matchers::{body_string_contains, method};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        let ntfy = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("2024-04-14"))
+            .and(body_string_contains("2024-04-15"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&ntfy)
+            .await;
+        let config: Config = toml::from_str(&format!(
+            r#"
+            [alerting]
+            quiet_hours = "22:00-07:00 UTC"
+
+            [outbox]
+            file = ""
+
+            [[notifiers]]
+            kind = "ntfy"
+            server = "{}"
+            topic = "pcta-test"
+            "#,
+            ntfy.uri()
+        ))
+        .unwrap();
+        let scraper = Scraper::new(config);
+        let history = Arc::new(History::open(None).unwrap());
+        let mut cx = Context::new(&scraper, history, None, Arc::new(Events::default())).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 4, d).unwrap();
+        let (night, later) = ([(day(14), 3)], [(day(15), 2)]);
+        let report = |dates| Report::Open {
+            label: "Early",
+            dates,
+            subscribers: &[],
+            apply_url: "",
+            paces: &[],
+        };
+        cx.notify(&report(&night), utc("2024-03-01T23:00:00Z"), "23:00")
+            .await;
+        cx.notify(&report(&later), utc("2024-03-02T01:00:00Z"), "01:00")
+            .await;
+        cx.release_held(utc("2024-03-02T06:59:00Z"), "06:59").await;
+        assert!(ntfy.received_requests().await.unwrap().is_empty());
+        cx.release_held(utc("2024-03-02T07:00:00Z"), "07:00").await;
+        cx.release_held(utc("2024-03-02T07:10:00Z"), "07:10").await;
+    }
+
+    #[tokio::test]
+    async fn keybase_failing_does_not_stop_a_pass() {
+        let config: Config = toml::from_str(
+            r#"
+            [[targets]]
+            terminus = "mexican-border"
+
+            [session]
+            cookie_file = ""
+
+            [outbox]
+            file = ""
+            "#,
+        )
+        .unwrap();
+        let simulated = crate::scraper::parse_simulated("2030-04-15=3").unwrap();
+        // Never dry run, so every log line, the alert and the error post all fail
+        let scraper = Scraper::new(config).simulate(Some(simulated));
+        let proxies = ProxyPool::new(&Default::default()).unwrap();
+        let pass = once(scraper, proxies).await.unwrap();
+        assert_eq!(pass.open, 1);
+    }
+}
@@ -0,0 +1,702 @@
+use anyhow::Context;
+use chrono::{NaiveDate, Utc};
+use clap::ValueEnum;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use crate::anomaly::Detector;
+use crate::config::Config;
+use crate::dns::Router;
+use crate::http;
+use crate::lock::InstanceLock;
+use crate::proxy::ProxyPool;
+use crate::reload::Changes;
+use crate::replay::{self, Saved};
+use crate::scheduler;
+use crate::session::Session;
+use crate::source::{self, Scraped};
+use crate::target::{Target, Terminus};
+use crate::timekeeping::Clock;
+use crate::timing;
+use crate::vpn::{self, KillSwitch};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Source {
+    /// The JSON endpoint behind the calendar (`api_url` of the target), the HTML page as fallback
+    Api,
+    /// The `var data` object embedded in the availability page
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Engine {
+    /// Plain HTTP request through reqwest
+    Http,
+    /// Headless Chromium, executes the page's JavaScript like a real visitor would
+    Browser,
+    /// HTTP first, the browser only when HTTP gets blocked
+    Auto,
+}
+
+/// How a single pass went, reported by `pcta once` as its exit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    NothingOpen,
+    /// At least one watch alerted, even if other targets failed
+    Open,
+    /// Nothing open and at least one target failed to scrape
+    Failed,
+}
+
+impl Outcome {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Outcome::NothingOpen => 0,
+            Outcome::Open => 10,
+            Outcome::Failed => 1,
+        }
+    }
+}
+
+/// A made-up calendar for `--simulate`, every date with the permits it has left
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simulated(pub Vec<(NaiveDate, u64)>);
+
+/// `2024-04-15=3,2024-04-16=0`
+pub fn parse_simulated(s: &str) -> anyhow::Result<Simulated> {
+    let days = s
+        .split(',')
+        .map(str::trim)
+        .filter(|day| !day.is_empty())
+        .map(|day| {
+            let parsed = day.split_once('=').and_then(|(date, left)| {
+                Some((date.trim().parse().ok()?, left.trim().parse().ok()?))
+            });
+            parsed.with_context(|| format!("Invalid day '{}', expected '2024-04-15=3'", day))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if days.is_empty() {
+        anyhow::bail!("No days to simulate, expected '2024-04-15=3,2024-04-16=0'");
+    }
+    Ok(Simulated(days))
+}
+
+/// Watches the permit portal. Embedders build one from a `Config` and either drive single
+/// scrapes themselves or hand it the whole loop with `run`:
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// let config = pcta::config::Config::load()?;
+/// pcta::Scraper::new(config).run().await
+/// # }
+/// ```
+pub struct Scraper {
+    pub(crate) config: Config,
+    engine: Engine,
+    source: Source,
+    pub(crate) dry_run: bool,
+    pub(crate) web: Option<SocketAddr>,
+    /// Run even if another instance holds the lock
+    force: bool,
+    /// `--save-bodies`
+    pub(crate) save_bodies: Option<PathBuf>,
+    /// The body `scrape` reads instead of fetching, during `replay`
+    pub(crate) replaying: Option<Saved>,
+    /// `--target`, kept to narrow a reloaded config down the same way
+    only: Vec<Terminus>,
+    /// Which `[[profile]]` this scrapes for, if split off from one with profiles
+    pub(crate) profile: Option<String>,
+    /// `--simulate`, what every target's calendar reads instead of fetching
+    simulated: Option<Simulated>,
+    anomalies: Detector,
+}
+
+impl Scraper {
+    pub fn new(config: Config) -> Self {
+        Scraper {
+            anomalies: Detector::new(config.anomaly.clone()),
+            config,
+            engine: Engine::Http,
+            source: Source::Html,
+            dry_run: false,
+            web: None,
+            force: false,
+            save_bodies: None,
+            replaying: None,
+            only: vec![],
+            profile: None,
+            simulated: None,
+        }
+    }
+
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    pub fn source(mut self, source: Source) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Scrape and parse as usual, but print the messages and VPN changes instead of making them
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Serve the web UI on `addr` while `run` goes
+    pub fn web(mut self, addr: Option<SocketAddr>) -> Self {
+        self.web = addr;
+        self
+    }
+
+    /// Keep every response body in `dir` to `replay` later
+    pub fn save_bodies(mut self, dir: Option<PathBuf>) -> Self {
+        self.save_bodies = dir;
+        self
+    }
+
+    /// Every target's calendar reads as `simulated` instead of being fetched, and the pass goes on
+    /// to alert, route and fire the hook for real. For drills, so neither the state file nor
+    /// the history keep any of it, of the profiles either.
+    pub fn simulate(mut self, simulated: Option<Simulated>) -> Self {
+        if simulated.is_some() {
+            forget(&mut self.config);
+        }
+        self.simulated = simulated;
+        self
+    }
+
+    /// Scrape even while another instance holds the `[state] lock`
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Only scrape the targets for these termini, all configured targets when empty. Applies to
+    /// every profile.
+    pub fn only(mut self, termini: &[Terminus]) -> Self {
+        self.only = termini.to_vec();
+        narrow(&mut self.config, &self.only);
+        self
+    }
+
+    /// Takes over the sections of a reloaded `config` that apply without a restart, see
+    /// `reload::Changes`. A config without targets is refused.
+    pub(crate) fn reconfigure(&mut self, config: Config) -> anyhow::Result<Changes> {
+        let mut config = match &self.profile {
+            Some(name) => {
+                let profile = config.profiles.into_iter().find(|p| p.name == *name);
+                profile
+                    .with_context(|| format!("Profile `{}` is gone, restart to drop it", name))?
+                    .config
+            }
+            None => config,
+        };
+        narrow(&mut config, &self.only);
+        if config.targets.is_empty() {
+            anyhow::bail!("No targets to scrape in the reloaded config");
+        }
+        let changes = Changes::between(&self.config, &config);
+        let Config {
+            portal,
+            targets,
+            recreation_gov,
+            browser,
+            schedule,
+            alerting,
+            notifiers,
+            templates,
+            hook,
+            anomaly,
+            errors,
+            ..
+        } = config;
+        self.anomalies.reconfigure(anomaly.clone());
+        self.config.portal = portal;
+        self.config.targets = targets;
+        self.config.recreation_gov = recreation_gov;
+        self.config.browser = browser;
+        self.config.schedule = schedule;
+        self.config.alerting = alerting;
+        self.config.notifiers = notifiers;
+        self.config.templates = templates;
+        self.config.hook = hook;
+        self.config.anomaly = anomaly;
+        self.config.errors = errors;
+        Ok(changes)
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Connects the VPN and scrapes on schedule forever, each profile in a loop of its own.
+    /// Nothing is scraped until the tunnel is confirmed up.
+    pub async fn run(self) -> anyhow::Result<()> {
+        let _locks = self.lock()?;
+        let (vpn, kill_switch) = self.connect().await?;
+        scheduler::run(self, vpn, kill_switch).await
+    }
+
+    /// Scrapes every target once, posts the alerts and writes the state file, profile after
+    /// profile. Only the connection is set up, retrying later or rotating is up to the caller.
+    pub async fn once(self) -> anyhow::Result<Outcome> {
+        let _locks = self.lock()?;
+        let (_vpn, _) = self.connect().await?;
+        let (mut open, mut failed) = (0, false);
+        for scraper in self.pipelines() {
+            let proxies = scraper.proxies()?;
+            let pass = scheduler::once(scraper, proxies).await?;
+            open += pass.open;
+            failed |= !pass.failures.is_empty();
+        }
+        Ok(match (open, failed) {
+            (0, false) => Outcome::NothingOpen,
+            (0, true) => Outcome::Failed,
+            _ => Outcome::Open,
+        })
+    }
+
+    /// Runs the bodies saved in `dir` through parsing, filtering and alerting as if they had
+    /// just been fetched, oldest first and each as a pass of its own. Nothing is posted or
+    /// written, the messages are printed like `--dry-run` does. With profiles only the
+    /// top-level targets are replayed.
+    pub async fn replay(mut self, dir: &Path) -> anyhow::Result<()> {
+        self.dry_run = true;
+        self.config.state.file = PathBuf::new();
+        self.config.outbox.file = PathBuf::new();
+        let saved = replay::list(dir)?;
+        crate::info!("Replaying {} bodies from '{}'", saved.len(), dir.display());
+        scheduler::replay(self, saved).await
+    }
+
+    /// The target whose source saves bodies as `key`
+    pub(crate) fn target_for(&self, key: &str) -> Option<&Target> {
+        self.config.targets.iter().find(|target| {
+            source::for_target(target, &self.config, self.engine, self.source).key() == key
+        })
+    }
+
+    /// One scraper per profile, or just this one without profiles. Profiles `--target` left
+    /// without targets are dropped.
+    pub(crate) fn pipelines(mut self) -> Vec<Scraper> {
+        if self.config.profiles.is_empty() {
+            return vec![self];
+        }
+        let mut pipelines = vec![];
+        for profile in std::mem::take(&mut self.config.profiles) {
+            if profile.config.targets.is_empty() {
+                crate::info!(
+                    "Profile `{}` has no targets left, skipping it",
+                    profile.name
+                );
+                continue;
+            }
+            pipelines.push(Scraper {
+                anomalies: Detector::new(profile.config.anomaly.clone()),
+                config: profile.config,
+                engine: self.engine,
+                source: self.source,
+                dry_run: self.dry_run,
+                web: None,
+                force: self.force,
+                save_bodies: self.save_bodies.clone(),
+                replaying: None,
+                only: self.only.clone(),
+                profile: Some(profile.name),
+                simulated: self.simulated.clone(),
+            });
+        }
+        pipelines
+    }
+
+    /// One lock per profile, so two instances may split the profiles between them
+    fn lock(&self) -> anyhow::Result<Vec<InstanceLock>> {
+        if self.force {
+            crate::info!("Not checking for another running instance, --force");
+            return Ok(vec![]);
+        }
+        let locks: Vec<(String, &Config)> = match self.config.profiles.is_empty() {
+            true => vec![("this config".to_string(), &self.config)],
+            false => self
+                .config
+                .profiles
+                .iter()
+                .map(|p| (format!("profile `{}`", p.name), &p.config))
+                .collect(),
+        };
+        locks
+            .into_iter()
+            .filter_map(|(what, config)| Some((what, config.state.lock()?)))
+            .map(|(what, path)| InstanceLock::acquire(path, &what))
+            .collect()
+    }
+
+    /// A fresh proxy ring for one loop
+    pub(crate) fn proxies(&self) -> anyhow::Result<ProxyPool> {
+        let dns = Router::from_config(&self.config, http::bound_client(&self.config.http)?)?;
+        let proxies = ProxyPool::new(&self.config.proxy)?
+            .http(self.config.http.clone())
+            .dns(Some(dns));
+        if !proxies.is_empty() {
+            crate::info!(
+                "Routing requests through {} proxies",
+                self.config.proxy.urls.len()
+            );
+        }
+        Ok(proxies)
+    }
+
+    async fn connect(&self) -> anyhow::Result<(Box<dyn vpn::VpnProvider>, KillSwitch)> {
+        // Establish connection on the VPN to prevent IP scrape detection
+        let vpn = vpn::from_config(&self.config.vpn);
+        let kill_switch = match self.dry_run {
+            true => {
+                crate::info!("[dry run] Would connect {} VPN", vpn.name());
+                KillSwitch::off()
+            }
+            false => {
+                // Not bound to the tunnel's interface, there is no tunnel yet
+                let client = http::client(&self.config.http)?;
+                let kill_switch = KillSwitch::arm(&self.config.vpn, vpn.as_ref(), &client).await;
+                vpn.connect().await?;
+                crate::info!("{} VPN connected", vpn.name());
+                kill_switch
+            }
+        };
+
+        let nothing = match self.config.profiles.is_empty() {
+            true => self.config.targets.is_empty(),
+            false => self
+                .config
+                .profiles
+                .iter()
+                .all(|p| p.config.targets.is_empty()),
+        };
+        if nothing {
+            anyhow::bail!("No targets to scrape, check `targets` in the config and `--target`");
+        }
+        Ok((vpn, kill_switch))
+    }
+
+    /// Where to apply for `target`'s permits, `{date}` standing in for the start date
+    pub fn apply_url(&self, target: &Target) -> String {
+        source::for_target(target, &self.config, self.engine, self.source).apply_url()
+    }
+
+    /// Fetches `target`'s calendar from its permit source and keeps the open dates in its
+    /// range, paired with the permits they have left. A calendar that looks broken is an
+    /// `anomaly::Suspicious` error rather than a result.
+    pub async fn scrape(
+        &self,
+        target: &Target,
+        session: &Session,
+        proxy: Option<&str>,
+    ) -> anyhow::Result<Scraped> {
+        let source = source::for_target(target, &self.config, self.engine, self.source);
+        let today = Clock::new(self.config.display.timezone).today(Utc::now());
+        if let Some(Simulated(days)) = &self.simulated {
+            crate::info!("Simulating {} days at {}", days.len(), target.label());
+            return Ok(Scraped {
+                days: target.open_dates(days.clone()),
+                listed: target.lists(days, today),
+                ..Scraped::fresh(vec![])
+            });
+        }
+        let fetch = async {
+            let Some(saved) = &self.replaying else {
+                return source.fetch(session, proxy).await;
+            };
+            let body = std::fs::read_to_string(&saved.path)
+                .with_context(|| format!("Failed to read '{}'", saved.path.display()))?;
+            Ok(Scraped::fresh(source.parse(&saved.kind, &body)?))
+        };
+        let (scraped, timings) = timing::measure(fetch).await;
+        let scraped = scraped?;
+        if scraped.changed {
+            self.anomalies
+                .check(&target.label(), &scraped.days, target.terminus().is_some())?;
+        }
+        Ok(Scraped {
+            listed: target.lists(&scraped.days, today),
+            days: target.open_dates(scraped.days),
+            changed: scraped.changed,
+            timings,
+        })
+    }
+}
+
+/// Drops the targets whose terminus isn't in `only`, unless it is empty, from the profiles too
+fn narrow(config: &mut Config, only: &[Terminus]) {
+    if !only.is_empty() {
+        config.targets.retain(|target| {
+            target
+                .terminus()
+                .is_none_or(|terminus| only.contains(&terminus))
+        });
+    }
+    for profile in &mut config.profiles {
+        narrow(&mut profile.config, only);
+    }
+}
+
+/// Turns off the state and history files, of the profiles too
+fn forget(config: &mut Config) {
+    config.state.file = PathBuf::new();
+    config.state.history = PathBuf::new();
+    for profile in &mut config.profiles {
+        forget(&mut profile.config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::BlockKind;
+    use crate::error::ScrapeError;
+    use crate::retry;
+    use crate::target::SourceConfig;
+    use reqwest::Client;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn serve(status: u16, body: &str) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(Terminus::MexicanBorder.path()))
+            .respond_with(ResponseTemplate::new(status).set_body_raw(body, "text/html"))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    async fn scrape_from(server: &MockServer) -> anyhow::Result<Vec<(NaiveDate, u64)>> {
+        let mut config = season_2023();
+        config.portal.base_url = server.uri();
+        let session = Session::new(Client::builder(), None)?;
+        let target = config.targets[0].clone();
+        let scraped = Scraper::new(config).scrape(&target, &session, None).await?;
+        Ok(scraped.days)
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    /// The fixtures are from the 2023 season
+    fn season_2023() -> Config {
+        Config {
+            targets: Target::default_targets_from(date("2023-03-01")),
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn scrapes_open_dates_from_normal_page() {
+        let server = serve(200, include_str!("../fixtures/mexican-border.html")).await;
+        let open = scrape_from(&server).await.unwrap();
+        assert_eq!(
+            open,
+            vec![
+                (date("2023-04-02"), 2),
+                (date("2023-04-14"), 13),
+                (date("2023-05-05"), 38),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn replays_a_saved_page() {
+        let dir = std::env::temp_dir().join(format!("pcta-replay-{}", std::process::id()));
+        let body = include_str!("../fixtures/mexican-border.html");
+        let at = "2023-04-01T17:00:00Z".parse().unwrap();
+        replay::save(&dir, at, "pcta-mexican-border", "html", body).unwrap();
+        let mut scraper = Scraper::new(season_2023());
+        let saved = replay::list(&dir).unwrap().remove(0);
+        let target = scraper.target_for(&saved.key).unwrap().clone();
+        scraper.replaying = Some(saved);
+        let session = Session::new(Client::builder(), None).unwrap();
+        let scraped = scraper.scrape(&target, &session, None).await.unwrap();
+        assert_eq!(scraped.days[0], (date("2023-04-02"), 2));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn simulated_days_are_read_instead_of_fetched() {
+        assert!(parse_simulated("2023-04-15=3,tomorrow=1").is_err());
+        assert!(parse_simulated(" ").is_err());
+        let simulated = parse_simulated("2023-04-15=3, 2023-04-16=0,2024-01-01=9").unwrap();
+        let scraper = Scraper::new(season_2023()).simulate(Some(simulated));
+        assert!(scraper.config.state.file().is_none());
+        let target = scraper.config.targets[0].clone();
+        // Nothing listens there, only the simulated days are read
+        let session = Session::new(Client::builder(), None).unwrap();
+        let scraped = scraper.scrape(&target, &session, None).await.unwrap();
+        assert_eq!(scraped.days, vec![(date("2023-04-15"), 3)]);
+        assert!(scraped.changed);
+    }
+
+    #[tokio::test]
+    async fn api_answers_are_hashed_and_blocks_not_retried_as_html() {
+        let server = MockServer::start().await;
+        let json = r#"{"limit":50,"calendar":[{"start_date":"2023-04-14","num":"37"}]}"#;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(json, "application/json"))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api"))
+            .respond_with(ResponseTemplate::new(403).set_body_raw("Forbidden", "text/html"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(Terminus::MexicanBorder.path()))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let mut config = season_2023();
+        config.portal.base_url = server.uri();
+        let mut target = config.targets[0].clone();
+        target.source = SourceConfig::Pcta {
+            terminus: Terminus::MexicanBorder,
+            api_url: format!("{}/api", server.uri()),
+        };
+        let scraper = Scraper::new(config).source(Source::Api);
+        let session = Session::new(Client::builder(), None).unwrap();
+        let scraped = scraper.scrape(&target, &session, None).await.unwrap();
+        assert_eq!(
+            (scraped.days.clone(), scraped.changed),
+            (vec![(date("2023-04-14"), 13)], true)
+        );
+        let again = scraper.scrape(&target, &session, None).await.unwrap();
+        assert_eq!((again.days, again.changed), (scraped.days, false));
+        let err = scraper.scrape(&target, &session, None).await.unwrap_err();
+        assert_eq!(retry::classify(&err), retry::Failure::Blocked);
+    }
+
+    #[tokio::test]
+    async fn empty_calendar_has_nothing_open() {
+        let server = serve(200, include_str!("../fixtures/empty-calendar.html")).await;
+        assert!(scrape_from(&server).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn captcha_page_is_a_block() {
+        let server = serve(200, include_str!("../fixtures/captcha.html")).await;
+        let err = scrape_from(&server).await.unwrap_err();
+        assert_eq!(retry::classify(&err), retry::Failure::Blocked);
+        let scrape_error = ScrapeError::find(&err).unwrap();
+        assert!(matches!(scrape_error, ScrapeError::CaptchaDetected(_)));
+        assert_eq!(scrape_error.blocked().unwrap().kind, BlockKind::Captcha);
+    }
+
+    #[tokio::test]
+    async fn malformed_json_is_fatal() {
+        let server = serve(200, include_str!("../fixtures/malformed-json.html")).await;
+        let err = scrape_from(&server).await.unwrap_err();
+        assert_eq!(retry::classify(&err), retry::Failure::Fatal);
+    }
+
+    #[tokio::test]
+    async fn malformed_json_keeps_the_response() {
+        let server = serve(200, include_str!("../fixtures/malformed-json.html")).await;
+        let dir = std::env::temp_dir().join(format!("pcta-kept-{}", std::process::id()));
+        let mut config = season_2023();
+        config.portal.base_url = server.uri();
+        let session = Session::new(Client::builder(), None)
+            .unwrap()
+            .debug_dir(Some(dir.clone()));
+        let target = config.targets[0].clone();
+        let err = Scraper::new(config)
+            .scrape(&target, &session, None)
+            .await
+            .unwrap_err();
+        assert_eq!(retry::classify(&err), retry::Failure::Fatal);
+
+        let kept = err.downcast_ref::<crate::forensics::Kept>().unwrap();
+        assert!(kept.0.starts_with(&dir));
+        let text = std::fs::read_to_string(&kept.0).unwrap();
+        assert!(text.starts_with("HTTP 200 OK\n"));
+        assert!(text.ends_with(include_str!("../fixtures/malformed-json.html")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn server_error_is_transient() {
+        let server = serve(503, "Service Unavailable").await;
+        let err = scrape_from(&server).await.unwrap_err();
+        assert_eq!(retry::classify(&err), retry::Failure::Transient);
+    }
+
+    #[tokio::test]
+    async fn not_modified_reuses_the_last_calendar() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("If-None-Match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_raw(include_str!("../fixtures/mexican-border.html"), "text/html"),
+            )
+            .mount(&server)
+            .await;
+
+        let mut config = season_2023();
+        config.portal.base_url = server.uri();
+        let session = Session::new(Client::builder(), None).unwrap();
+        let target = config.targets[0].clone();
+        let scraper = Scraper::new(config);
+        let first = scraper.scrape(&target, &session, None).await.unwrap();
+        let second = scraper.scrape(&target, &session, None).await.unwrap();
+        assert!(first.changed);
+        assert!(!second.changed);
+        assert_eq!(first.days, second.days);
+    }
+
+    #[tokio::test]
+    async fn same_calendar_in_a_changed_page_is_unchanged() {
+        let page = include_str!("../fixtures/mexican-border.html");
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(page, "text/html"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(format!("{}<!-- csrf 8f2a -->", page), "text/html"),
+            )
+            .mount(&server)
+            .await;
+
+        let mut config = season_2023();
+        config.portal.base_url = server.uri();
+        let session = Session::new(Client::builder(), None).unwrap();
+        let target = config.targets[0].clone();
+        let scraper = Scraper::new(config);
+        assert!(
+            scraper
+                .scrape(&target, &session, None)
+                .await
+                .unwrap()
+                .changed
+        );
+        assert!(
+            !scraper
+                .scrape(&target, &session, None)
+                .await
+                .unwrap()
+                .changed
+        );
+    }
+}
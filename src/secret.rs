@@ -0,0 +1,127 @@
+use anyhow::Context;
+use serde::Deserialize;
+use std::fmt;
+
+/// A credential in the config (webhook URL, bot token, SMTP password, ...) that shouldn't have to
+/// sit there in plaintext. Written as one of:
+///
+/// ```toml
+/// token = "env:TELEGRAM_TOKEN"       # or TELEGRAM_TOKEN_FILE pointing at a file
+/// token = "file:/run/secrets/telegram"
+/// token = "keyring:pcta/telegram"   # service/user in the OS keychain
+/// token = "123456:plain-text-token"
+/// ```
+///
+/// It's looked up when used rather than when the config is read, so a rotated file or keychain
+/// entry is picked up without a restart. `Debug` never prints the value, and no log line does
+/// once it has been looked up.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub struct Secret(String);
+
+impl From<String> for Secret {
+    fn from(s: String) -> Self {
+        Secret(s)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.split_once(':') {
+            Some((scheme @ ("env" | "file" | "keyring"), at)) => {
+                write!(f, "Secret({}:{})", scheme, at)
+            }
+            _ => write!(f, "Secret(<redacted>)"),
+        }
+    }
+}
+
+impl Secret {
+    /// The value, which is kept out of the logs from then on
+    pub fn expose(&self) -> anyhow::Result<String> {
+        let value = match self.0.split_once(':') {
+            Some(("env", name)) => from_env(name),
+            Some(("file", path)) => from_file(path),
+            Some(("keyring", entry)) => from_keyring(entry),
+            _ => Ok(self.0.clone()),
+        }?;
+        crate::log::redact(&value);
+        Ok(value)
+    }
+}
+
+/// `NAME`, or the contents of the file `NAME_FILE` names, the Docker / systemd credentials way
+fn from_env(name: &str) -> anyhow::Result<String> {
+    if let Ok(value) = std::env::var(name) {
+        return Ok(value);
+    }
+    match std::env::var(format!("{}_FILE", name)) {
+        Ok(path) => from_file(&path),
+        Err(_) => anyhow::bail!("Neither {} nor {}_FILE is set", name, name),
+    }
+}
+
+fn from_file(path: &str) -> anyhow::Result<String> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read secret file '{}'", path))?;
+    Ok(text.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Asks the OS keychain through its CLI: `security` on macOS, libsecret's `secret-tool`
+/// everywhere else but Windows, which has no such CLI
+fn from_keyring(entry: &str) -> anyhow::Result<String> {
+    let (service, user) = entry
+        .split_once('/')
+        .with_context(|| format!("Expected keyring:service/user, got 'keyring:{}'", entry))?;
+    if cfg!(windows) {
+        anyhow::bail!("keyring: secrets need a Unix keychain, use env: or file: on Windows");
+    }
+    let mut command = match cfg!(target_os = "macos") {
+        true => {
+            let mut c = crate::exec::blocking_command("security");
+            c.args(["find-generic-password", "-s", service, "-a", user, "-w"]);
+            c
+        }
+        false => {
+            let mut c = crate::exec::blocking_command("secret-tool");
+            c.args(["lookup", "service", service, "user", user]);
+            c
+        }
+    };
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run the keychain CLI for '{}'", entry))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "No keychain entry for service '{}', user '{}'",
+            service,
+            user
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_literals_env_and_files() {
+        let path = std::env::temp_dir().join("pcta-secret-test");
+        std::fs::write(&path, "from-file\n").unwrap();
+        std::env::set_var("PCTA_TEST_SECRET_FILE", &path);
+
+        assert_eq!(Secret::from("plain".to_string()).expose().unwrap(), "plain");
+        let file = Secret::from(format!("file:{}", path.display()));
+        assert_eq!(file.expose().unwrap(), "from-file");
+        let env = Secret::from("env:PCTA_TEST_SECRET".to_string());
+        assert_eq!(env.expose().unwrap(), "from-file");
+        assert!(Secret::from("env:PCTA_TEST_UNSET".to_string())
+            .expose()
+            .is_err());
+        assert_eq!(
+            format!("{:?}", Secret::from("hunter2".to_string())),
+            "Secret(<redacted>)"
+        );
+    }
+}
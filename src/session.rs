@@ -0,0 +1,237 @@
+use anyhow::Context;
+use reqwest::header::{HeaderMap, ACCEPT_ENCODING};
+use reqwest::{Client, ClientBuilder, Response};
+use reqwest_cookie_store::{CookieStore, CookieStoreMutex};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use ua_generator::ua::spoof_ua;
+
+use crate::cache::ResponseCache;
+use crate::error::ScrapeError;
+use crate::forensics::{self, Head};
+use crate::headers;
+use crate::http;
+use crate::ratelimit::RateLimiter;
+use crate::replay;
+use crate::robots::Politeness;
+
+/// One browser identity: a user agent, the headers that browser sends and the client holding its
+/// cookie jar. A real browser doesn't change any of those between page loads, so neither do we
+/// until the IP underneath changes or the session has served `max_requests`.
+pub struct Session {
+    pub user_agent: String,
+    /// Header profile matching `user_agent`, includes the User-Agent header itself
+    pub headers: HeaderMap,
+    pub client: Client,
+    /// Conditional-request validators, kept and cleared along with the cookies
+    pub cache: Arc<ResponseCache>,
+    jar: Arc<CookieStoreMutex>,
+    requests: AtomicU32,
+    limiter: Arc<RateLimiter>,
+    politeness: Option<Arc<Politeness>>,
+    /// Whether the client decodes gzip and brotli, the headers can't offer them otherwise
+    compressed: bool,
+    /// Bytes a scraped body may not go over decompressed, `0` for no limit
+    max_body: u64,
+    /// Where every response body is kept for `pcta replay`
+    bodies: Option<PathBuf>,
+    /// Where responses that failed to parse are kept
+    debug_dir: Option<PathBuf>,
+}
+
+impl Session {
+    /// Starts an identity on top of `builder`, picking up cookies from a previous run if
+    /// `cookie_file` exists so we look like a returning browser
+    pub fn new(builder: ClientBuilder, cookie_file: Option<&Path>) -> anyhow::Result<Self> {
+        let store = match cookie_file {
+            Some(path) if path.exists() => load_cookies(path)?,
+            _ => CookieStore::default(),
+        };
+        let jar = Arc::new(CookieStoreMutex::new(store));
+        let cache = Arc::new(ResponseCache::default());
+        Session::with_jar(
+            builder,
+            jar,
+            cache,
+            Arc::new(RateLimiter::unlimited()),
+            None,
+        )
+    }
+
+    /// Makes every request wait its turn on `limiter`, which outlives rotations
+    pub fn limited(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    /// Identifies as `politeness`'s user agent instead of a browser and has it vet and space
+    /// out every request, across rotations too
+    pub fn polite(mut self, politeness: Arc<Politeness>) -> Self {
+        self.user_agent = politeness.user_agent().to_string();
+        self.headers = headers::identified(&self.user_agent);
+        self.politeness = Some(politeness);
+        self
+    }
+
+    /// Stops offering compressed responses, for a client built with `http.compression = false`
+    pub fn uncompressed(mut self) -> Self {
+        self.headers.remove(ACCEPT_ENCODING);
+        self.compressed = false;
+        self
+    }
+
+    /// Keeps no responses to validate or compare against, see `ResponseCache::off`
+    pub fn uncached(mut self) -> Self {
+        self.cache = Arc::new(ResponseCache::off());
+        self
+    }
+
+    /// Refuses scraped bodies over `bytes`, see `http::body`
+    pub fn max_body(mut self, bytes: u64) -> Self {
+        self.max_body = bytes;
+        self
+    }
+
+    /// The body of a scraped `response` from `url`, if it is one of `types` and not too large
+    pub async fn body(
+        &self,
+        response: Response,
+        types: &[&str],
+        url: &str,
+    ) -> Result<String, ScrapeError> {
+        http::body(response, types, self.max_body, url).await
+    }
+
+    /// Keeps every response body in `dir`, see `replay`
+    pub fn save_bodies(mut self, dir: Option<PathBuf>) -> Self {
+        self.bodies = dir;
+        self
+    }
+
+    /// Keeps `body` if saving bodies, a failure to is only logged
+    pub fn save_body(&self, key: &str, kind: &str, body: &str) {
+        if let Some(dir) = &self.bodies {
+            if let Err(e) = replay::save(dir, chrono::Utc::now(), key, kind, body) {
+                crate::info!("{:#}", e);
+            }
+        }
+    }
+
+    /// Keeps the responses that fail to parse in `dir`, see `forensics`
+    pub fn debug_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.debug_dir = dir;
+        self
+    }
+
+    /// Keeps the response behind parse error `err` and names the file in it, when a debug dir
+    /// is set
+    pub fn failed_parse(
+        &self,
+        key: &str,
+        head: &Head,
+        body: &str,
+        err: anyhow::Error,
+    ) -> anyhow::Error {
+        let Some(dir) = &self.debug_dir else {
+            return err;
+        };
+        match forensics::dump(dir, chrono::Utc::now(), key, head, body) {
+            Ok(path) => err.context(forensics::Kept(path)),
+            Err(e) => {
+                crate::info!("{:#}", e);
+                err
+            }
+        }
+    }
+
+    fn with_jar(
+        builder: ClientBuilder,
+        jar: Arc<CookieStoreMutex>,
+        cache: Arc<ResponseCache>,
+        limiter: Arc<RateLimiter>,
+        politeness: Option<Arc<Politeness>>,
+    ) -> anyhow::Result<Self> {
+        let client = builder
+            .cookie_provider(jar.clone())
+            .build()
+            .context("Reqwest client build failed")?;
+        let user_agent = spoof_ua().to_string();
+        let session = Session {
+            headers: headers::profile(&user_agent),
+            user_agent,
+            client,
+            cache,
+            jar,
+            requests: AtomicU32::new(0),
+            limiter,
+            politeness: None,
+            compressed: true,
+            max_body: 0,
+            bodies: None,
+            debug_dir: None,
+        };
+        Ok(match politeness {
+            Some(politeness) => session.polite(politeness),
+            None => session,
+        })
+    }
+
+    /// Swaps in a new user agent and client, for when the IP rotated. The cookie jar carries
+    /// over unless `clear_cookies` is set.
+    pub fn rotate(self, builder: ClientBuilder, clear_cookies: bool) -> anyhow::Result<Self> {
+        if clear_cookies {
+            self.jar.lock().unwrap().clear();
+            self.cache.clear();
+        }
+        let (compressed, bodies, debug_dir) = (self.compressed, self.bodies, self.debug_dir);
+        let session =
+            Session::with_jar(builder, self.jar, self.cache, self.limiter, self.politeness)?
+                .max_body(self.max_body)
+                .save_bodies(bodies)
+                .debug_dir(debug_dir);
+        Ok(match compressed {
+            true => session,
+            false => session.uncompressed(),
+        })
+    }
+
+    /// Waits for the rate limit and, in the politeness mode, for robots.txt to allow `url`.
+    /// Then counts a request against this identity.
+    pub async fn record_request(&self, url: &str) -> anyhow::Result<()> {
+        self.limiter.acquire().await;
+        if let Some(politeness) = &self.politeness {
+            politeness.wait_turn(&self.client, url).await?;
+        }
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Whether the identity has been used `max_requests` times. `0` means no limit.
+    pub fn exhausted(&self, max_requests: u32) -> bool {
+        max_requests > 0 && self.requests.load(Ordering::Relaxed) >= max_requests
+    }
+
+    /// Writes the persistent cookies out, session cookies die with the process like they would
+    /// in a browser
+    pub fn save_cookies(&self, path: &Path) -> anyhow::Result<()> {
+        let mut file = File::create(path)
+            .with_context(|| format!("Failed to create cookie file '{}'", path.display()))?;
+        self.jar
+            .lock()
+            .unwrap()
+            .save_json(&mut file)
+            .map_err(|e| anyhow::anyhow!(e))
+            .with_context(|| format!("Failed to write cookie file '{}'", path.display()))
+    }
+}
+
+fn load_cookies(path: &Path) -> anyhow::Result<CookieStore> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open cookie file '{}'", path.display()))?;
+    CookieStore::load_json(BufReader::new(file))
+        .map_err(|e| anyhow::anyhow!(e))
+        .with_context(|| format!("Invalid cookie file '{}'", path.display()))
+}
@@ -0,0 +1,108 @@
+mod pcta;
+mod recreation_gov;
+
+use async_trait::async_trait;
+use chrono::NaiveDate;
+
+use crate::config::Config;
+use crate::scraper::{Engine, Source};
+use crate::session::Session;
+use crate::target::{SourceConfig, Target};
+use crate::timing::Timings;
+
+pub use pcta::Pcta;
+pub use recreation_gov::RecreationGov;
+
+/// A permit system we can read availability from. Everything after `fetch` (range filtering,
+/// alerting, scheduling) only sees `(date, remaining)` pairs, so a source owns its own URL
+/// layout, parsing and idea of what a "limit" is.
+#[async_trait]
+pub trait PermitSource: Send + Sync {
+    /// How alerts refer to this source
+    fn name(&self) -> String;
+
+    /// The page a human would open to see (and grab) the availability
+    fn url(&self) -> String;
+
+    /// Where to apply for a start date, `{date}` standing in for it. The availability page
+    /// unless the site's `apply_url` is configured.
+    fn apply_url(&self) -> String {
+        self.url()
+    }
+
+    async fn fetch(&self, session: &Session, proxy: Option<&str>) -> anyhow::Result<Scraped>;
+
+    /// What this source's bodies are saved under with `--save-bodies`
+    fn key(&self) -> String;
+
+    /// Every date in a body `fetch` saved as `kind`, for `pcta replay`
+    fn parse(&self, kind: &str, body: &str) -> anyhow::Result<Vec<(NaiveDate, u64)>>;
+}
+
+/// What a source read: every date it knows about with the permits it has left, and whether
+/// that's news since the last fetch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scraped {
+    pub days: Vec<(NaiveDate, u64)>,
+    /// `false` when the source answered with exactly what it said last time
+    pub changed: bool,
+    /// Whether the calendar had a date for any watch, full or not, see `Target::lists`
+    pub listed: bool,
+    pub timings: Timings,
+}
+
+impl Scraped {
+    pub fn fresh(days: Vec<(NaiveDate, u64)>) -> Self {
+        Scraped {
+            days,
+            changed: true,
+            listed: true,
+            timings: Timings::default(),
+        }
+    }
+
+    pub fn unchanged(days: Vec<(NaiveDate, u64)>) -> Self {
+        Scraped {
+            days,
+            changed: false,
+            listed: true,
+            timings: Timings::default(),
+        }
+    }
+}
+
+/// Builds the source a configured target reads from
+pub fn for_target(
+    target: &Target,
+    config: &Config,
+    engine: Engine,
+    source: Source,
+) -> Box<dyn PermitSource> {
+    match &target.source {
+        SourceConfig::Pcta { terminus, api_url } => Box::new(Pcta {
+            terminus: *terminus,
+            base_url: config.portal.base_url.clone(),
+            api_url: api_url.clone(),
+            engine,
+            source,
+            browser_binary: config.browser.binary.clone(),
+            scan: config.memory.low,
+            limit: config.portal.limit,
+            apply_url: config.portal.apply_url.clone(),
+        }),
+        SourceConfig::RecreationGov {
+            permit_id,
+            division,
+            ..
+        } => Box::new(RecreationGov {
+            base_url: config.recreation_gov.base_url.clone(),
+            apply_url: config.recreation_gov.apply_url.clone(),
+            permit_id: permit_id.clone(),
+            division: division.clone(),
+            name: target.label(),
+            // `Target::validate` made sure of both
+            start: target.start.unwrap_or_default(),
+            end: target.end.unwrap_or_default(),
+        }),
+    }
+}
@@ -0,0 +1,363 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use reqwest::header::{
+    ACCEPT, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, PRAGMA,
+};
+use reqwest::StatusCode;
+use std::time::Instant;
+
+use super::{PermitSource, Scraped};
+use crate::browser;
+use crate::cache::{self, Entry};
+use crate::detect::{self, Blocked};
+use crate::error::ScrapeError;
+use crate::forensics::Head;
+use crate::http;
+use crate::parser::{self, calendar, extract, Data};
+use crate::retry;
+use crate::scraper::{Engine, Source};
+use crate::session::Session;
+use crate::target::Terminus;
+use crate::timing;
+
+/// One of the PCTA portal's availability pages
+pub struct Pcta {
+    pub terminus: Terminus,
+    pub base_url: String,
+    pub api_url: String,
+    pub engine: Engine,
+    pub source: Source,
+    pub browser_binary: String,
+    /// `[memory] low`, never parse the page into a DOM
+    pub scan: bool,
+    /// Overrides the page's daily capacity
+    pub limit: Option<u64>,
+    pub apply_url: String,
+}
+
+#[async_trait]
+impl PermitSource for Pcta {
+    fn name(&self) -> String {
+        self.terminus.to_string()
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "{}{}",
+            self.base_url.trim_end_matches('/'),
+            self.terminus.path()
+        )
+    }
+
+    fn apply_url(&self) -> String {
+        match self.apply_url.is_empty() {
+            true => self.url(),
+            false => self.apply_url.clone(),
+        }
+    }
+
+    /// The direct API falls back to the HTML page when it fails, unless it was blocked: the
+    /// page would only be asked from the same identity right after. See `scrape_html` for how
+    /// the engine applies.
+    async fn fetch(&self, session: &Session, proxy: Option<&str>) -> anyhow::Result<Scraped> {
+        match self.source {
+            Source::Html => self.fetch_page(session, proxy).await,
+            Source::Api => match self.fetch_api(session).await {
+                Err(e) if retry::classify(&e) != retry::Failure::Blocked => {
+                    crate::info!("API scrape failed ({:#}), falling back to the HTML page", e);
+                    self.fetch_page(session, proxy).await
+                }
+                scraped => scraped,
+            },
+        }
+    }
+
+    fn key(&self) -> String {
+        let terminus = self.terminus.to_possible_value().unwrap();
+        format!("pcta-{}", terminus.get_name())
+    }
+
+    /// `html` for the availability page, `json` for the API's answer
+    fn parse(&self, kind: &str, body: &str) -> anyhow::Result<Vec<(NaiveDate, u64)>> {
+        let data = match kind {
+            "html" => {
+                if let Some(kind) = detect::detect(StatusCode::OK, body) {
+                    return Err(ScrapeError::from(Blocked::new(kind, "Saved page")).into());
+                }
+                parser::from_objects(&parser::objects(body, self.scan), body)?
+            }
+            "json" => serde_json::from_str::<Data>(body)
+                .map_err(ScrapeError::json("Invalid saved API JSON"))?,
+            _ => anyhow::bail!("The PCTA portal saves no `{}` bodies", kind),
+        };
+        Ok(parser::remaining(&calendar(data, self.limit)?))
+    }
+}
+
+/// The availability page as it came back, before the calendar is pulled out of it
+enum Page {
+    /// 304, the cached copy is still current
+    NotModified,
+    Body {
+        text: String,
+        head: Head,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+impl Pcta {
+    /// The API's answer, which isn't parsed again when it is byte-identical to the last one
+    async fn fetch_api(&self, session: &Session) -> anyhow::Result<Scraped> {
+        let (url, key) = (self.api_url.as_str(), self.key());
+        let cached = session.cache.get(url);
+        let (text, head) = fetch_api(session, url, &key).await?;
+        let body_hash = cache::hash(&text);
+        if let Some(entry) = cached.filter(|entry| entry.body_hash == body_hash) {
+            crate::debug!("No change in the API's answer at {}", url);
+            return Ok(Scraped::unchanged(entry.days));
+        }
+        let days = timing::parse(|| -> anyhow::Result<_> {
+            let data = serde_json::from_str::<Data>(&text).map_err(ScrapeError::json(format!(
+                "Invalid JSON from the PCTA API at {}",
+                url
+            )))?;
+            Ok(parser::remaining(&calendar(data, self.limit)?))
+        })
+        .map_err(|e| session.failed_parse(&key, &head, &text, e))?;
+        session.cache.put(
+            url,
+            Entry {
+                etag: None,
+                last_modified: None,
+                body_hash,
+                data_hash: body_hash,
+                days: days.clone(),
+            },
+        );
+        Ok(Scraped::fresh(days))
+    }
+
+    /// The HTML page, conditional on what we saw last time. Neither a 304 nor a byte-identical
+    /// body is parsed again, both come back as unchanged.
+    async fn fetch_page(&self, session: &Session, proxy: Option<&str>) -> anyhow::Result<Scraped> {
+        let url = self.url();
+        let cached = session.cache.get(&url);
+        let (text, head, etag, last_modified) =
+            match scrape_html(self, session, proxy, cached.as_ref()).await? {
+                Page::NotModified => {
+                    let entry = cached.context("304 Not Modified for a page we never cached")?;
+                    return Ok(Scraped::unchanged(entry.days));
+                }
+                Page::Body {
+                    text,
+                    head,
+                    etag,
+                    last_modified,
+                } => (text, head, etag, last_modified),
+            };
+        session.save_body(&self.key(), "html", &text);
+
+        let body_hash = cache::hash(&text);
+        if let Some(entry) = cached.clone().filter(|entry| entry.body_hash == body_hash) {
+            let days = entry.days.clone();
+            session.cache.put(
+                &url,
+                Entry {
+                    etag,
+                    last_modified,
+                    ..entry
+                },
+            );
+            return Ok(Scraped::unchanged(days));
+        }
+
+        // Pages carry tokens and timestamps that change every load, so compare the calendar
+        // itself before parsing it
+        let objects = timing::parse(|| parser::objects(&text, self.scan));
+        let data_hash = cache::hash(&objects.concat());
+        if let Some(entry) = cached.filter(|entry| entry.data_hash == data_hash) {
+            crate::debug!("No change in the calendar JSON at {}", url);
+            let days = entry.days.clone();
+            session.cache.put(
+                &url,
+                Entry {
+                    etag,
+                    last_modified,
+                    body_hash,
+                    ..entry
+                },
+            );
+            return Ok(Scraped::unchanged(days));
+        }
+
+        let days = timing::parse(|| -> anyhow::Result<_> {
+            let data = parser::from_objects(&objects, &text)?;
+            Ok(parser::remaining(&calendar(data, self.limit)?))
+        })
+        .map_err(|e| session.failed_parse(&self.key(), &head, &text, e))?;
+        session.cache.put(
+            &url,
+            Entry {
+                etag,
+                last_modified,
+                body_hash,
+                data_hash,
+                days: days.clone(),
+            },
+        );
+        Ok(Scraped::fresh(days))
+    }
+}
+
+/// Fetches the availability page with `engine`. In `Engine::Auto` a blocked plain HTTP attempt
+/// is repeated once through the browser before giving up. Only plain HTTP makes conditional
+/// requests, the browser always loads the page in full.
+async fn scrape_html(
+    pcta: &Pcta,
+    session: &Session,
+    proxy: Option<&str>,
+    cached: Option<&Entry>,
+) -> anyhow::Result<Page> {
+    let url = pcta.url();
+    let url = url.as_str();
+    let via_browser = || async {
+        session.record_request(url).await?;
+        let started = Instant::now();
+        let text = browser::fetch(&pcta.browser_binary, url, &session.user_agent, proxy).await?;
+        // No headers to tell apart from the body, the whole page load is the first byte
+        timing::first_byte(started);
+        timing::done(started);
+        if let Some(kind) = detect::detect(StatusCode::OK, &text) {
+            let blocked = Blocked::new(kind, format!("Headless browser on {}", url));
+            return Err(ScrapeError::from(blocked).into());
+        }
+        // No headers either, the page is kept as the browser rendered it
+        let head = Head::default();
+        if let Err(e) = extract(&text) {
+            return Err(session.failed_parse(&pcta.key(), &head, &text, e));
+        }
+        Ok(Page::Body {
+            text,
+            head,
+            etag: None,
+            last_modified: None,
+        })
+    };
+
+    match pcta.engine {
+        Engine::Http => fetch_http(session, url, cached).await,
+        Engine::Browser => via_browser().await,
+        Engine::Auto => {
+            let page = fetch_http(session, url, cached).await.and_then(|page| {
+                if let Page::Body { text, head, .. } = &page {
+                    extract(text).map_err(|e| session.failed_parse(&pcta.key(), head, text, e))?;
+                }
+                Ok(page)
+            });
+            match page {
+                Err(e) if retry::classify(&e) == retry::Failure::Blocked => {
+                    crate::info!(
+                        "Plain HTTP scrape blocked ({}), falling back to the browser",
+                        e
+                    );
+                    via_browser().await
+                }
+                res => res,
+            }
+        }
+    }
+}
+
+/// Asks the endpoint behind the calendar for its JSON directly, the way the page's own XHR
+/// would, skipping the HTML and the script-tag regex entirely
+/// The API's body once it is neither blocked nor an error
+async fn fetch_api(session: &Session, url: &str, key: &str) -> anyhow::Result<(String, Head)> {
+    if url.is_empty() {
+        anyhow::bail!("`--source api` needs `api_url` set on the target in the config");
+    }
+    session.record_request(url).await?;
+    let request = session
+        .client
+        .get(url)
+        .headers(session.headers.clone())
+        .header(ACCEPT, "application/json, text/javascript, */*; q=0.01")
+        .header("X-Requested-With", "XMLHttpRequest")
+        .header(PRAGMA, "no-cache")
+        .header(CACHE_CONTROL, "no-cache");
+    let started = Instant::now();
+    let response = request.send().await.map_err(ScrapeError::Network)?;
+    timing::first_byte(started);
+    let head = Head {
+        status: response.status(),
+        headers: response.headers().clone(),
+    };
+    let status = head.status;
+    let checked = response.error_for_status_ref().map(|_| ());
+    let text = session.body(response, http::JSON, url).await?;
+    timing::done(started);
+    session.save_body(key, "json", &text);
+    if let Some(kind) = detect::detect(status, &text) {
+        let blocked = Blocked::new(kind, format!("HTTP {} from {}", status, url));
+        return Err(ScrapeError::from(blocked).into());
+    }
+    checked.map_err(ScrapeError::Network)?;
+    Ok((text, head))
+}
+
+async fn fetch_http(session: &Session, url: &str, cached: Option<&Entry>) -> anyhow::Result<Page> {
+    session.record_request(url).await?;
+    let mut request = session.client.get(url).headers(session.headers.clone());
+    // A revalidating browser drops the no-cache pair and sends its validators instead
+    match cached {
+        Some(entry) if entry.etag.is_some() || entry.last_modified.is_some() => {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        _ => {
+            request = request
+                .header(PRAGMA, "no-cache")
+                .header(CACHE_CONTROL, "no-cache");
+        }
+    }
+    let started = Instant::now();
+    let response = request.send().await.map_err(ScrapeError::Network)?;
+    timing::first_byte(started);
+    let status = response.status();
+    if status == StatusCode::NOT_MODIFIED {
+        return Ok(Page::NotModified);
+    }
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+    let head = Head {
+        status,
+        headers: response.headers().clone(),
+    };
+    // Keep the response around for `error_for_status`, the body is consumed below
+    let checked = response.error_for_status_ref().map(|_| ());
+    let text = session.body(response, http::HTML, url).await?;
+    timing::done(started);
+    if let Some(kind) = detect::detect(status, &text) {
+        let blocked = Blocked::new(kind, format!("HTTP {} from {}", status, url));
+        return Err(ScrapeError::from(blocked).into());
+    }
+    checked.map_err(ScrapeError::Network)?;
+    Ok(Page::Body {
+        text,
+        head,
+        etag,
+        last_modified,
+    })
+}
@@ -0,0 +1,242 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{Datelike, NaiveDate};
+use reqwest::header::ACCEPT;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Instant;
+
+use super::{PermitSource, Scraped};
+use crate::detect::{self, Blocked};
+use crate::error::ScrapeError;
+use crate::forensics::Head;
+use crate::http;
+use crate::session::Session;
+use crate::timing;
+
+/// A permit on recreation.gov, read from the public JSON API their availability calendar uses.
+/// That API answers one month at a time, so a range is fetched month by month.
+pub struct RecreationGov {
+    pub base_url: String,
+    pub apply_url: String,
+    pub permit_id: String,
+    pub division: String,
+    pub name: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    payload: Payload,
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    #[serde(default)]
+    availability: HashMap<String, Division>,
+}
+
+#[derive(Deserialize)]
+struct Division {
+    #[serde(default)]
+    date_availability: HashMap<String, Day>,
+}
+
+#[derive(Deserialize)]
+struct Day {
+    remaining: i64,
+}
+
+#[async_trait]
+impl PermitSource for RecreationGov {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "{}/permits/{}",
+            self.base_url.trim_end_matches('/'),
+            self.permit_id
+        )
+    }
+
+    fn apply_url(&self) -> String {
+        match self.apply_url.is_empty() {
+            true => self.url(),
+            false => self.apply_url.clone(),
+        }
+    }
+
+    async fn fetch(&self, session: &Session, _proxy: Option<&str>) -> anyhow::Result<Scraped> {
+        let mut days = vec![];
+        let mut month = self.start.with_day(1).unwrap();
+        while month <= self.end {
+            days.extend(self.fetch_month(session, month).await?);
+            month = next_month(month);
+        }
+        days.sort();
+        Ok(Scraped::fresh(days))
+    }
+
+    fn key(&self) -> String {
+        format!("recreation-gov-{}-{}", self.permit_id, self.division)
+    }
+
+    /// `json`, one month of the availability API
+    fn parse(&self, kind: &str, body: &str) -> anyhow::Result<Vec<(NaiveDate, u64)>> {
+        if kind != "json" {
+            anyhow::bail!("recreation.gov saves no `{}` bodies", kind);
+        }
+        let mut days = self.parse_month(body, "a saved body")?;
+        days.sort();
+        Ok(days)
+    }
+}
+
+impl RecreationGov {
+    async fn fetch_month(
+        &self,
+        session: &Session,
+        month: NaiveDate,
+    ) -> anyhow::Result<Vec<(NaiveDate, u64)>> {
+        let url = format!(
+            "{}/api/permits/{}/availability/month",
+            self.base_url.trim_end_matches('/'),
+            self.permit_id
+        );
+        session.record_request(&url).await?;
+        let started = Instant::now();
+        let response = session
+            .client
+            .get(&url)
+            .headers(session.headers.clone())
+            .header(ACCEPT, "application/json, text/plain, */*")
+            .query(&[(
+                "start_date",
+                format!("{}T00:00:00.000Z", month.format("%Y-%m-%d")),
+            )])
+            .send()
+            .await
+            .map_err(ScrapeError::Network)?;
+        timing::first_byte(started);
+        let head = Head {
+            status: response.status(),
+            headers: response.headers().clone(),
+        };
+        let status = head.status;
+        let checked = response.error_for_status_ref().map(|_| ());
+        let text = session.body(response, http::JSON, &url).await?;
+        timing::done(started);
+        session.save_body(&self.key(), "json", &text);
+        if let Some(kind) = detect::detect(status, &text) {
+            let blocked = Blocked::new(kind, format!("HTTP {} from {}", status, url));
+            return Err(ScrapeError::from(blocked).into());
+        }
+        checked.map_err(ScrapeError::Network)?;
+        timing::parse(|| self.parse_month(&text, &url))
+            .map_err(|e| session.failed_parse(&self.key(), &head, &text, e))
+    }
+
+    /// One month's answer, `from` saying where it came from in errors
+    fn parse_month(&self, text: &str, from: &str) -> anyhow::Result<Vec<(NaiveDate, u64)>> {
+        let response = serde_json::from_str::<Response>(text).map_err(ScrapeError::json(
+            format!("Invalid JSON from recreation.gov at {}", from),
+        ))?;
+        let division = response
+            .payload
+            .availability
+            .get(&self.division)
+            .with_context(|| {
+                format!(
+                    "recreation.gov permit {} has no division '{}'",
+                    self.permit_id, self.division
+                )
+            })?;
+
+        division
+            .date_availability
+            .iter()
+            .map(|(date, day)| {
+                // Keys look like "2023-04-14T00:00:00Z"
+                let date = NaiveDate::parse_from_str(&date[..date.len().min(10)], "%Y-%m-%d")
+                    .map_err(|_| ScrapeError::InvalidEntry {
+                        date: date.clone(),
+                        field: "date",
+                        value: date.clone(),
+                        from: "recreation.gov",
+                    })?;
+                Ok((date, day.remaining.max(0) as u64))
+            })
+            .collect()
+    }
+}
+
+fn next_month(month: NaiveDate) -> NaiveDate {
+    if month.month() == 12 {
+        NaiveDate::from_ymd_opt(month.year() + 1, 1, 1).unwrap()
+    } else {
+        NaiveDate::from_ymd_opt(month.year(), month.month() + 1, 1).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_division_across_months() {
+        let server = MockServer::start().await;
+        for (start, body) in [
+            (
+                "2023-04-01T00:00:00.000Z",
+                r#"{"payload": {"availability": {"1": {"date_availability": {
+                    "2023-04-29T00:00:00Z": {"total": 30, "remaining": 0},
+                    "2023-04-30T00:00:00Z": {"total": 30, "remaining": 4}
+                }}, "2": {"date_availability": {
+                    "2023-04-30T00:00:00Z": {"total": 30, "remaining": 30}
+                }}}}}"#,
+            ),
+            (
+                "2023-05-01T00:00:00.000Z",
+                r#"{"payload": {"availability": {"1": {"date_availability": {
+                    "2023-05-01T00:00:00Z": {"total": 30, "remaining": 12}
+                }}}}}"#,
+            ),
+        ] {
+            Mock::given(method("GET"))
+                .and(path("/api/permits/233262/availability/month"))
+                .and(query_param("start_date", start))
+                .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+                .mount(&server)
+                .await;
+        }
+
+        let source = RecreationGov {
+            base_url: server.uri(),
+            apply_url: String::new(),
+            permit_id: "233262".to_string(),
+            division: "1".to_string(),
+            name: "Mt. Whitney".to_string(),
+            start: date("2023-04-20"),
+            end: date("2023-05-02"),
+        };
+        let session = Session::new(Client::builder(), None).unwrap();
+        assert_eq!(
+            source.fetch(&session, None).await.unwrap().days,
+            vec![
+                (date("2023-04-29"), 0),
+                (date("2023-04-30"), 4),
+                (date("2023-05-01"), 12),
+            ]
+        );
+    }
+}
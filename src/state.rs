@@ -0,0 +1,34 @@
+use anyhow::Context;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::timekeeping::Stamp;
+use crate::timing::Timings;
+
+/// What the last scrape saw, written after every pass so `pcta once` runs and outside tools can
+/// pick it up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct State {
+    pub scraped_at: Stamp,
+    pub targets: Vec<TargetState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetState {
+    pub label: String,
+    /// Open dates in the target's range with their remaining permits
+    pub open: Vec<(NaiveDate, u64)>,
+    pub error: Option<String>,
+    /// How long the scrape took, `None` when it failed
+    #[serde(default)]
+    pub timings: Option<Timings>,
+}
+
+impl State {
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write state file '{}'", path.display()))
+    }
+}
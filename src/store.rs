@@ -0,0 +1,227 @@
+#[cfg(feature = "redis")]
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::ha::{Beat, Heartbeat};
+use crate::history::Snapshot;
+use crate::secret::Secret;
+
+/// Every command gives up after this long, a slow Redis must not hold up a pass
+#[cfg(feature = "redis")]
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// ```toml
+/// [redis]
+/// url = "env:PCTA_REDIS_URL"   # redis://:password@10.0.0.5/0
+/// prefix = "pcta"
+/// ```
+///
+/// Shares what instances on different hosts need to agree on: the history in place of the
+/// `[state] history` file, the HA heartbeat in place of its file, and which alerts went out,
+/// so one date opening is only alerted once however many instances saw it. Keys start with
+/// `prefix`. The history keeps as many snapshots as `[memory]` holds, and a standby reads it
+/// again when it takes over so it alerts on what the primary last saw.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedisConfig {
+    /// Unset to not share anything
+    pub url: Option<Secret>,
+    pub prefix: String,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        RedisConfig {
+            url: None,
+            prefix: "pcta".to_string(),
+        }
+    }
+}
+
+/// The instances' shared Redis, over one connection that is opened again after it breaks
+#[cfg(feature = "redis")]
+pub struct Store {
+    client: redis::Client,
+    prefix: String,
+    con: tokio::sync::Mutex<Option<redis::aio::MultiplexedConnection>>,
+}
+
+#[cfg(feature = "redis")]
+impl Store {
+    /// `None` without a `url`. Nothing is sent until the first command.
+    pub fn open(config: &RedisConfig) -> anyhow::Result<Option<Store>> {
+        let Some(url) = &config.url else {
+            return Ok(None);
+        };
+        let client = redis::Client::open(url.expose()?).context("Invalid Redis URL")?;
+        Ok(Some(Store {
+            client,
+            prefix: config.prefix.clone(),
+            con: tokio::sync::Mutex::new(None),
+        }))
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}:{}", self.prefix, name)
+    }
+
+    async fn connection(&self) -> anyhow::Result<redis::aio::MultiplexedConnection> {
+        let mut con = self.con.lock().await;
+        if let Some(con) = &*con {
+            return Ok(con.clone());
+        }
+        let opened = tokio::time::timeout(TIMEOUT, self.client.get_multiplexed_tokio_connection())
+            .await
+            .context("Timed out connecting to Redis")?
+            .context("Failed to connect to Redis")?;
+        *con = Some(opened.clone());
+        Ok(opened)
+    }
+
+    async fn query<T: redis::FromRedisValue>(&self, cmd: &mut redis::Cmd) -> anyhow::Result<T> {
+        let mut con = self.connection().await?;
+        match tokio::time::timeout(TIMEOUT, cmd.query_async(&mut con)).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(e)) => {
+                if e.is_io_error() || e.is_connection_dropped() || e.is_unrecoverable_error() {
+                    self.con.lock().await.take();
+                }
+                Err(e).context("Redis command failed")
+            }
+            Err(_) => {
+                self.con.lock().await.take();
+                anyhow::bail!("Redis command timed out")
+            }
+        }
+    }
+
+    /// Every snapshot any instance recorded, oldest first
+    pub async fn snapshots(&self) -> anyhow::Result<Vec<Snapshot>> {
+        let lines: Vec<String> = self
+            .query(redis::cmd("LRANGE").arg(self.key("history")).arg(0).arg(-1))
+            .await?;
+        lines
+            .iter()
+            .map(|line| serde_json::from_str(line).context("Invalid snapshot in Redis history"))
+            .collect()
+    }
+
+    /// Adds `snapshot` to the history, dropping the oldest over `limit`
+    pub async fn append(&self, snapshot: &Snapshot, limit: Option<usize>) -> anyhow::Result<()> {
+        let (key, line) = (self.key("history"), serde_json::to_string(snapshot)?);
+        self.query::<i64>(redis::cmd("RPUSH").arg(&key).arg(line))
+            .await?;
+        if let Some(limit) = limit {
+            self.query::<()>(redis::cmd("LTRIM").arg(&key).arg(-(limit as i64)).arg(-1))
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn alert_key(&self, watch: &str, date: NaiveDate, remaining: u64) -> String {
+        self.key(&format!("alerted:{}:{}:{}", watch, date, remaining))
+    }
+
+    /// Whether this instance gets to alert on `date` with `remaining` left, `false` when
+    /// another did in the last `cooldown_secs`
+    pub async fn claim(
+        &self,
+        watch: &str,
+        date: NaiveDate,
+        remaining: u64,
+        cooldown_secs: u64,
+    ) -> anyhow::Result<bool> {
+        let set: Option<String> = self
+            .query(
+                redis::cmd("SET")
+                    .arg(self.alert_key(watch, date, remaining))
+                    .arg(std::process::id())
+                    .arg("NX")
+                    .arg("EX")
+                    .arg(cooldown_secs.max(1)),
+            )
+            .await?;
+        Ok(set.is_some())
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl Heartbeat for Store {
+    async fn beat(&self, beat: &Beat) -> anyhow::Result<()> {
+        let json = serde_json::to_string(beat)?;
+        self.query::<()>(redis::cmd("SET").arg(self.key("heartbeat")).arg(json))
+            .await
+    }
+
+    async fn last(&self) -> anyhow::Result<Option<Beat>> {
+        let json: Option<String> = self
+            .query(redis::cmd("GET").arg(self.key("heartbeat")))
+            .await?;
+        json.map(|json| serde_json::from_str(&json).context("Invalid heartbeat in Redis"))
+            .transpose()
+    }
+}
+
+/// Built without the `redis` feature there is never a store, a `url` is refused
+#[cfg(not(feature = "redis"))]
+pub enum Store {}
+
+#[cfg(not(feature = "redis"))]
+impl Store {
+    pub fn open(config: &RedisConfig) -> anyhow::Result<Option<Store>> {
+        match &config.url {
+            None => Ok(None),
+            Some(_) => anyhow::bail!(
+                "[redis] has a url, but pcta was built without the `redis` feature. \
+                 Rebuild with `--features redis`."
+            ),
+        }
+    }
+
+    pub async fn snapshots(&self) -> anyhow::Result<Vec<Snapshot>> {
+        match *self {}
+    }
+
+    pub async fn append(&self, _: &Snapshot, _: Option<usize>) -> anyhow::Result<()> {
+        match *self {}
+    }
+
+    pub async fn claim(&self, _: &str, _: NaiveDate, _: u64, _: u64) -> anyhow::Result<bool> {
+        match *self {}
+    }
+}
+
+#[cfg(not(feature = "redis"))]
+#[async_trait]
+impl Heartbeat for Store {
+    async fn beat(&self, _: &Beat) -> anyhow::Result<()> {
+        match *self {}
+    }
+
+    async fn last(&self) -> anyhow::Result<Option<Beat>> {
+        match *self {}
+    }
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_live_under_the_prefix() {
+        assert!(Store::open(&RedisConfig::default()).unwrap().is_none());
+        let config = RedisConfig {
+            url: Some(Secret::from("redis://127.0.0.1/".to_string())),
+            prefix: "pcta-test".to_string(),
+        };
+        let store = Store::open(&config).unwrap().unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 4, 20).unwrap();
+        assert_eq!(
+            store.alert_key("Early", date, 3),
+            "pcta-test:alerted:Early:2024-04-20:3"
+        );
+    }
+}
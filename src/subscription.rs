@@ -0,0 +1,94 @@
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+/// Someone who wants to be @-mentioned about open dates in their own window
+///
+/// ```toml
+/// [[subscribers]]
+/// user = "jacobyoung"
+///
+/// [[subscribers]]
+/// user = "trailfriend"
+/// start = "2023-04-10"
+/// end = "2023-04-20"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Subscriber {
+    /// Keybase username, without the `@`
+    pub user: String,
+    /// Inclusive, open-ended when missing
+    pub start: Option<NaiveDate>,
+    /// Inclusive, open-ended when missing
+    pub end: Option<NaiveDate>,
+}
+
+impl Subscriber {
+    /// Who got pinged before subscriptions existed
+    pub fn defaults() -> Vec<Subscriber> {
+        vec![Subscriber {
+            user: "jacobyoung".to_string(),
+            start: None,
+            end: None,
+        }]
+    }
+
+    pub fn wants(&self, date: NaiveDate) -> bool {
+        self.start.is_none_or(|start| date >= start) && self.end.is_none_or(|end| date <= end)
+    }
+}
+
+/// `@user` for everyone whose window covers `date`, space separated
+pub fn mentions(subscribers: &[Subscriber], date: NaiveDate) -> String {
+    subscribers
+        .iter()
+        .filter(|s| s.wants(date))
+        .map(|s| format!("@{}", s.user))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `@user` for everyone who wants at least one of `dates`, each user once
+pub fn mentions_any(subscribers: &[Subscriber], dates: &[NaiveDate]) -> String {
+    subscribers
+        .iter()
+        .filter(|s| dates.iter().any(|date| s.wants(*date)))
+        .map(|s| format!("@{}", s.user))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn only_subscribers_in_window_are_mentioned() {
+        let subscribers = vec![
+            Subscriber {
+                user: "everything".to_string(),
+                start: None,
+                end: None,
+            },
+            Subscriber {
+                user: "april".to_string(),
+                start: Some(date("2023-04-10")),
+                end: Some(date("2023-04-20")),
+            },
+        ];
+        assert_eq!(
+            mentions(&subscribers, date("2023-04-20")),
+            "@everything @april"
+        );
+        assert_eq!(mentions(&subscribers, date("2023-04-21")), "@everything");
+        assert_eq!(
+            mentions_any(&subscribers, &[date("2023-05-01"), date("2023-04-12")]),
+            "@everything @april"
+        );
+        assert_eq!(mentions_any(&subscribers[1..], &[date("2023-05-01")]), "");
+    }
+}
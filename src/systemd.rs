@@ -0,0 +1,115 @@
+use anyhow::Context;
+use std::path::Path;
+use std::time::Duration;
+
+/// Tells systemd about our state over `$NOTIFY_SOCKET`, a no-op when not run as a
+/// `Type=notify` unit
+pub fn notify(state: &str) {
+    let Ok(path) = std::env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(e) = send(&path, state) {
+        crate::info!("sd_notify {} failed: {:#}", state, e);
+    }
+}
+
+#[cfg(unix)]
+fn send(path: &str, state: &str) -> anyhow::Result<()> {
+    let socket = std::os::unix::net::UnixDatagram::unbound()?;
+    match path.strip_prefix('@') {
+        // Abstract namespace socket
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        _ => {
+            socket.send_to(state.as_bytes(), path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Nothing sets `$NOTIFY_SOCKET` without systemd
+#[cfg(not(unix))]
+fn send(_: &str, _: &str) -> anyhow::Result<()> {
+    anyhow::bail!("sd_notify needs a Unix socket")
+}
+
+/// How often systemd wants a `WATCHDOG=1`, from `$WATCHDOG_USEC`
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    Some(Duration::from_micros(usec))
+}
+
+/// Sleeps for `duration`, petting the watchdog at half its interval meanwhile so a long wait
+/// for business hours doesn't look like a hang
+pub async fn sleep(duration: Duration) {
+    notify("WATCHDOG=1");
+    let Some(interval) = watchdog_interval() else {
+        return tokio::time::sleep(duration).await;
+    };
+    let step = interval / 2;
+    let mut left = duration;
+    while left > step {
+        tokio::time::sleep(step).await;
+        notify("WATCHDOG=1");
+        left -= step;
+    }
+    tokio::time::sleep(left).await;
+}
+
+/// A hardened user unit running `exec` from `working_dir`, where it also keeps its cookie and
+/// state files
+pub fn unit(exec: &str, working_dir: &Path, config: &Path) -> String {
+    format!(
+        "[Unit]
+Description=PCTA permit availability watcher
+Wants=network-online.target
+After=network-online.target
+
+[Service]
+Type=notify
+ExecStart={exec}
+WorkingDirectory={dir}
+Environment=PCTA_CONFIG={config}
+Restart=on-failure
+RestartSec=30
+WatchdogSec=300
+
+NoNewPrivileges=yes
+PrivateTmp=yes
+ProtectSystem=strict
+ProtectHome=read-only
+ReadWritePaths={dir}
+ProtectKernelTunables=yes
+ProtectControlGroups=yes
+RestrictAddressFamilies=AF_UNIX AF_INET AF_INET6 AF_NETLINK
+RestrictNamespaces=yes
+LockPersonality=yes
+SystemCallArchitectures=native
+
+[Install]
+WantedBy=default.target
+",
+        exec = exec,
+        dir = working_dir.display(),
+        config = config.display(),
+    )
+}
+
+/// Writes `unit` to `path`, refusing to replace an existing file unless `force`
+pub fn install(path: &Path, unit: &str, force: bool) -> anyhow::Result<()> {
+    if path.exists() && !force {
+        anyhow::bail!(
+            "'{}' already exists, pass --force to replace it",
+            path.display()
+        );
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    }
+    std::fs::write(path, unit).with_context(|| format!("Failed to write '{}'", path.display()))
+}
@@ -0,0 +1,786 @@
+use anyhow::Context;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::anomaly::Season;
+use crate::dns::Resolver;
+
+/// Which end of the trail a permit calendar is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Terminus {
+    /// Northbound starts from Campo
+    MexicanBorder,
+    /// Southbound starts from Manning Park / the monument
+    CanadianBorder,
+}
+
+impl Terminus {
+    /// Availability page path on the portal
+    pub fn path(&self) -> &'static str {
+        match self {
+            Terminus::MexicanBorder => "/availability/mexican-border.php",
+            Terminus::CanadianBorder => "/availability/canadian-border.php",
+        }
+    }
+}
+
+impl fmt::Display for Terminus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Terminus::MexicanBorder => "Mexican border",
+            Terminus::CanadianBorder => "Canadian border",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// One permit calendar and the start dates we care about on it
+///
+/// ```toml
+/// [[targets]]
+/// terminus = "canadian-border"
+/// start = "2023-06-20"
+/// end = "2023-07-10"
+///
+/// [[targets]]
+/// permit_id = "233262"
+/// division = "406"
+/// name = "Mt. Whitney day use"
+/// start = "2023-06-01"
+/// end = "2023-06-30"
+///
+/// [[targets]]
+/// terminus = "mexican-border"
+/// ```
+///
+/// Without `start` or `end` a PCTA target watches its whole calendar from the first date or to
+/// the last, alerting on any date that comes open. recreation.gov targets need both, their
+/// calendar is fetched a month at a time.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Target {
+    #[serde(flatten)]
+    pub source: SourceConfig,
+    /// Inclusive, like `end`
+    #[serde(default)]
+    pub start: Option<NaiveDate>,
+    #[serde(default)]
+    pub end: Option<NaiveDate>,
+    /// Narrower windows inside `start..end` that alert on their own terms. Without any the whole
+    /// range is one watch alerting on any open permit.
+    #[serde(default)]
+    pub watches: Vec<Watch>,
+    /// Overrides `[dns]` for this target's site, see `DnsConfig`
+    #[serde(default)]
+    pub dns: Option<Resolver>,
+}
+
+/// A named window on a target with its own threshold and alert channel
+///
+/// ```toml
+/// [[targets]]
+/// terminus = "mexican-border"
+/// start = "2023-04-01"
+/// end = "2023-05-15"
+///
+/// [[targets.watches]]
+/// name = "Early April"
+/// start = "2023-04-09"
+/// end = "2023-04-20"
+///
+/// [[targets.watches]]
+/// name = "Early May"
+/// start = "2023-04-30"
+/// end = "2023-05-15"
+/// # fewer than 35 of 50 taken
+/// min_remaining = 16
+/// close_below = 12
+/// channel = "pcta-may"
+///
+/// [[targets.watches]]
+/// name = "A month out or later"
+/// start = "today+30d"
+///
+/// [[targets.watches]]
+/// name = "First in April"
+/// start = "2023-04-01"
+/// end = "2023-04-30"
+/// earliest = true
+///
+/// [[targets.watches]]
+/// name = "Weekend starts"
+/// weekdays = ["fri", "sat"]
+///
+/// [targets.watches.rank]
+/// ideal = "2023-04-15"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Watch {
+    pub name: String,
+    /// Inclusive, like `end`. Open-ended when missing, the target's range still applies.
+    #[serde(default)]
+    pub start: Option<Bound>,
+    #[serde(default)]
+    pub end: Option<Bound>,
+    /// Only the earliest open date in the window counts, for when any start will do
+    #[serde(default)]
+    pub earliest: bool,
+    /// Only start dates on these days, `"fri"` or `"friday"`. Every day when empty.
+    #[serde(default)]
+    pub weekdays: Vec<Weekday>,
+    /// Which of several open dates the alert lists first, chronological without one
+    #[serde(default)]
+    pub rank: Option<Rank>,
+    /// Only alert on dates with at least this many permits left
+    #[serde(default = "Watch::default_min_remaining")]
+    pub min_remaining: u64,
+    /// An alerted date stays open while it has at least this many left, `min_remaining` without
+    /// one. Set lower, a date going 50, 49, 50 with `min_remaining = 50` alerts only once.
+    #[serde(default)]
+    pub close_below: Option<u64>,
+    /// Keybase topic the alerts go to
+    #[serde(default = "Watch::default_channel")]
+    pub channel: String,
+}
+
+impl Watch {
+    fn default_min_remaining() -> u64 {
+        1
+    }
+
+    fn default_channel() -> String {
+        "pcta-alerts".to_string()
+    }
+
+    /// `dates` of this watch best first as of `today`, see `Rank`
+    pub fn ranked(
+        &self,
+        mut dates: Vec<(NaiveDate, u64)>,
+        today: NaiveDate,
+    ) -> Vec<(NaiveDate, u64)> {
+        if let Some(rank) = &self.rank {
+            rank.sort(&mut dates, today);
+        }
+        dates
+    }
+
+    /// Whether `date` is in this window as of `today`, open or not
+    pub fn covers(&self, date: NaiveDate, today: NaiveDate) -> bool {
+        self.start.is_none_or(|start| date >= start.resolve(today))
+            && self.end.is_none_or(|end| date <= end.resolve(today))
+    }
+
+    /// The dates in this window as of `today` with enough permits left
+    pub fn open_dates(&self, days: &[(NaiveDate, u64)], today: NaiveDate) -> Vec<(NaiveDate, u64)> {
+        let open = self.with_at_least(days, today, self.min_remaining);
+        match self.earliest {
+            true => open
+                .min_by_key(|(date, _)| *date)
+                .copied()
+                .into_iter()
+                .collect(),
+            false => open.copied().collect(),
+        }
+    }
+
+    /// The dates in this window as of `today` that stay open once alerted, see `close_below`
+    pub fn holding(&self, days: &[(NaiveDate, u64)], today: NaiveDate) -> Vec<(NaiveDate, u64)> {
+        let least = self.close_below.unwrap_or(self.min_remaining);
+        self.with_at_least(days, today, least).copied().collect()
+    }
+
+    fn with_at_least<'a>(
+        &'a self,
+        days: &'a [(NaiveDate, u64)],
+        today: NaiveDate,
+        least: u64,
+    ) -> impl Iterator<Item = &'a (NaiveDate, u64)> {
+        days.iter().filter(move |(date, remaining)| {
+            self.covers(*date, today)
+                && (self.weekdays.is_empty() || self.weekdays.contains(&date.weekday()))
+                && *remaining >= least
+        })
+    }
+}
+
+/// How a watch orders the dates of its alerts, the one to grab first on top
+///
+/// ```toml
+/// [targets.watches.rank]
+/// by = ["weekday", "ideal", "remaining"]   # the default, ties go to the next
+/// ideal = "2023-04-15"                      # or "today+30d"
+/// weekdays = ["sat"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rank {
+    pub by: Vec<RankBy>,
+    /// The start date wanted most, `RankBy::Ideal` puts the closest first
+    pub ideal: Option<Bound>,
+    /// `RankBy::Weekday` puts these days first, in this order
+    pub weekdays: Vec<Weekday>,
+}
+
+impl Default for Rank {
+    fn default() -> Self {
+        Rank {
+            by: vec![RankBy::Weekday, RankBy::Ideal, RankBy::Remaining],
+            ideal: None,
+            weekdays: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RankBy {
+    /// Closest to `ideal` first, a no-op without one
+    Ideal,
+    /// Most permits left first
+    Remaining,
+    /// Earliest of the preferred `weekdays` first, the rest after
+    Weekday,
+}
+
+impl Rank {
+    /// Sorts `dates` best first as of `today`. Dates nothing tells apart stay chronological.
+    pub fn sort(&self, dates: &mut [(NaiveDate, u64)], today: NaiveDate) {
+        let ideal = self.ideal.map(|bound| bound.resolve(today));
+        let weekday = |date: NaiveDate| {
+            let at = self.weekdays.iter().position(|day| *day == date.weekday());
+            at.unwrap_or(self.weekdays.len())
+        };
+        dates.sort_by(|(a, a_left), (b, b_left)| {
+            self.by
+                .iter()
+                .map(|by| match by {
+                    RankBy::Ideal => match ideal {
+                        Some(ideal) => (*a - ideal)
+                            .num_days()
+                            .abs()
+                            .cmp(&(*b - ideal).num_days().abs()),
+                        None => std::cmp::Ordering::Equal,
+                    },
+                    RankBy::Remaining => b_left.cmp(a_left),
+                    RankBy::Weekday => weekday(*a).cmp(&weekday(*b)),
+                })
+                .fold(std::cmp::Ordering::Equal, std::cmp::Ordering::then)
+                .then(a.cmp(b))
+        });
+    }
+}
+
+/// One end of a watch's window: a date, or a number of days from the day of the scrape
+///
+/// ```toml
+/// start = "2023-04-01"
+/// start = "today+30d"   # or "today", "today-1w", "today+2w"
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Bound {
+    Date(NaiveDate),
+    /// Days after today, before it when negative
+    Today(i64),
+}
+
+impl Bound {
+    pub fn resolve(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            Bound::Date(date) => date,
+            Bound::Today(days) => today + Duration::days(days),
+        }
+    }
+
+    /// Whether `self..=end` is empty whatever day it is. Mixed bounds depend on the day.
+    fn after(self, end: Bound) -> bool {
+        match (self, end) {
+            (Bound::Date(start), Bound::Date(end)) => start > end,
+            (Bound::Today(start), Bound::Today(end)) => start > end,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Bound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bound::Date(date) => write!(f, "{}", date),
+            Bound::Today(0) => write!(f, "today"),
+            Bound::Today(days) => write!(f, "today{:+}d", days),
+        }
+    }
+}
+
+impl FromStr for Bound {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let invalid = || {
+            format!(
+                "Invalid date '{}', expected e.g. '2023-04-01' or 'today+30d'",
+                s
+            )
+        };
+        let Some(offset) = s.trim().strip_prefix("today") else {
+            return Ok(Bound::Date(
+                NaiveDate::from_str(s.trim()).with_context(invalid)?,
+            ));
+        };
+        if offset.is_empty() {
+            return Ok(Bound::Today(0));
+        }
+        let (sign, rest) = match offset.split_at(1) {
+            ("+", rest) => (1, rest),
+            ("-", rest) => (-1, rest),
+            _ => anyhow::bail!(invalid()),
+        };
+        let (count, per) = match rest.strip_suffix('w') {
+            Some(weeks) => (weeks, 7),
+            None => (rest.strip_suffix('d').with_context(invalid)?, 1),
+        };
+        let count: i64 = count.parse().with_context(invalid)?;
+        Ok(Bound::Today(sign * count * per))
+    }
+}
+
+impl TryFrom<String> for Bound {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+/// Which permit system a target is scraped from, told apart by the keys present
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum SourceConfig {
+    /// The PCTA long-distance permit portal
+    Pcta {
+        terminus: Terminus,
+        /// JSON endpoint behind this calendar, only used with `--source api`
+        #[serde(default)]
+        api_url: String,
+    },
+    /// A permit on recreation.gov (JMT via Inyo, Wonderland Trail, ...)
+    RecreationGov {
+        permit_id: String,
+        /// Trailhead / zone, the key under `availability` in their API
+        division: String,
+        /// What alerts call it, defaults to the permit and division IDs
+        #[serde(default)]
+        name: String,
+    },
+}
+
+impl Target {
+    /// What the scraper watched before targets were configurable, in the coming season
+    pub fn default_targets() -> Vec<Target> {
+        Target::default_targets_from(chrono::Local::now().date_naive())
+    }
+
+    /// The default targets for the first season not over by `today`
+    pub(crate) fn default_targets_from(today: NaiveDate) -> Vec<Target> {
+        let end = |year| NaiveDate::from_ymd_opt(year, 5, 5).unwrap();
+        let year = match end(today.year()) < today {
+            true => today.year() + 1,
+            false => today.year(),
+        };
+        vec![Target {
+            source: SourceConfig::Pcta {
+                terminus: Terminus::MexicanBorder,
+                api_url: String::new(),
+            },
+            // I want to find dates which start after April 1st and up to May 5th
+            start: NaiveDate::from_ymd_opt(year, 4, 2),
+            end: Some(end(year)),
+            watches: vec![],
+            dns: None,
+        }]
+    }
+
+    /// Refuses a window that ends before it starts, and for PCTA calendars one that misses the
+    /// permit `season` entirely, since neither could ever alert
+    pub fn validate(&self, season: &Season) -> anyhow::Result<()> {
+        let label = self.label();
+        if self.terminus().is_none() && (self.start.is_none() || self.end.is_none()) {
+            anyhow::bail!(
+                "`{}` needs a `start` and an `end`, recreation.gov is fetched month by month",
+                label
+            );
+        }
+        let range = self.start.zip(self.end);
+        let windows = range
+            .map(|(start, end)| (label.as_str(), Bound::Date(start), Bound::Date(end)))
+            .into_iter()
+            .chain(
+                self.watches
+                    .iter()
+                    .filter_map(|watch| Some((watch.name.as_str(), watch.start?, watch.end?))),
+            );
+        for (name, start, end) in windows {
+            if start.after(end) {
+                anyhow::bail!(
+                    "`{}` starts on {} but ends before that on {}",
+                    name,
+                    start,
+                    end
+                );
+            }
+        }
+        // A year of days is every day of the season, no need to look further. An open end
+        // always reaches into one.
+        let Some((start, end)) = range else {
+            return Ok(());
+        };
+        let in_season = start
+            .iter_days()
+            .take_while(|day| *day <= end)
+            .take(366)
+            .any(|day| season.contains(day));
+        if self.terminus().is_some() && !in_season {
+            anyhow::bail!(
+                "`{}` runs {} to {}, outside the permit season {} ([anomaly] season)",
+                label,
+                start,
+                end,
+                season
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether the whole window is behind `today`, so that nothing in it can open again
+    pub fn over(&self, today: NaiveDate) -> bool {
+        self.end.is_some_and(|end| end < today)
+    }
+
+    /// Whether `date` is in the range, every date is without one
+    pub fn covers(&self, date: NaiveDate) -> bool {
+        self.start.is_none_or(|start| date >= start) && self.end.is_none_or(|end| date <= end)
+    }
+
+    /// The first and last date of the range, the earliest or latest of `listed` standing in for
+    /// an end left open. `None` when it is open and nothing is listed.
+    pub fn span(
+        &self,
+        listed: impl IntoIterator<Item = NaiveDate>,
+    ) -> Option<(NaiveDate, NaiveDate)> {
+        let listed: Vec<NaiveDate> = listed.into_iter().filter(|d| self.covers(*d)).collect();
+        Some((
+            self.start.or_else(|| listed.iter().min().copied())?,
+            self.end.or_else(|| listed.iter().max().copied())?,
+        ))
+    }
+
+    /// `start..end` with either left out when open
+    pub fn window(&self) -> String {
+        let or_open = |date: Option<NaiveDate>| date.map(|d| d.to_string()).unwrap_or_default();
+        format!("{}..{}", or_open(self.start), or_open(self.end))
+    }
+
+    /// How alerts refer to this target
+    pub fn label(&self) -> String {
+        match &self.source {
+            SourceConfig::Pcta { terminus, .. } => terminus.to_string(),
+            SourceConfig::RecreationGov {
+                permit_id,
+                division,
+                name,
+            } => match name.is_empty() {
+                true => format!("recreation.gov permit {} / {}", permit_id, division),
+                false => name.clone(),
+            },
+        }
+    }
+
+    /// The PCTA terminus, `None` for targets on other permit systems
+    pub fn terminus(&self) -> Option<Terminus> {
+        match &self.source {
+            SourceConfig::Pcta { terminus, .. } => Some(*terminus),
+            SourceConfig::RecreationGov { .. } => None,
+        }
+    }
+
+    /// The configured watches, or the whole range as a single one
+    pub fn watches(&self) -> Vec<Watch> {
+        match self.watches.is_empty() {
+            true => vec![Watch {
+                name: self.label(),
+                start: self.start.map(Bound::Date),
+                end: self.end.map(Bound::Date),
+                earliest: false,
+                weekdays: vec![],
+                rank: None,
+                min_remaining: Watch::default_min_remaining(),
+                close_below: None,
+                channel: Watch::default_channel(),
+            }],
+            false => self.watches.clone(),
+        }
+    }
+
+    /// Whether the calendar has a date for any of the watches as of `today`, full or not. Until
+    /// the season's calendar is up it has none.
+    pub fn lists(&self, days: &[(NaiveDate, u64)], today: NaiveDate) -> bool {
+        let watches = self.watches();
+        days.iter().any(|(date, _)| {
+            self.covers(*date) && watches.iter().any(|watch| watch.covers(*date, today))
+        })
+    }
+
+    /// Keeps the dates in range that still have permits left. `days` pairs each date with its
+    /// remaining permits, whatever source it came from.
+    pub fn open_dates(&self, days: Vec<(NaiveDate, u64)>) -> Vec<(NaiveDate, u64)> {
+        days.into_iter()
+            .filter(|(date, remaining)| self.covers(*date) && *remaining > 0)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn filters_to_range_and_remaining() {
+        let target = Target::default_targets_from(date("2023-03-01")).remove(0);
+        let days = vec![
+            // both ends of the range are inclusive
+            (date("2023-04-01"), 40),
+            (date("2023-04-02"), 40),
+            (date("2023-05-05"), 40),
+            (date("2023-05-06"), 40),
+            // full
+            (date("2023-04-20"), 0),
+        ];
+        assert_eq!(
+            target.open_dates(days),
+            vec![(date("2023-04-02"), 40), (date("2023-05-05"), 40)]
+        );
+    }
+
+    #[test]
+    fn watches_apply_their_own_threshold() {
+        let watch = Watch {
+            name: "Early May".to_string(),
+            start: Some(Bound::Date(date("2023-04-30"))),
+            end: Some(Bound::Date(date("2023-05-15"))),
+            earliest: false,
+            weekdays: vec![],
+            rank: None,
+            min_remaining: 16,
+            close_below: None,
+            channel: Watch::default_channel(),
+        };
+        let days = vec![
+            (date("2023-04-20"), 40),
+            (date("2023-05-01"), 15),
+            (date("2023-05-02"), 16),
+        ];
+        assert_eq!(
+            watch.open_dates(&days, date("2023-03-01")),
+            vec![(date("2023-05-02"), 16)]
+        );
+    }
+
+    #[test]
+    fn relative_and_open_ended_windows() {
+        assert_eq!("today".parse::<Bound>().unwrap(), Bound::Today(0));
+        assert_eq!("today+30d".parse::<Bound>().unwrap(), Bound::Today(30));
+        assert_eq!("today-2w".parse::<Bound>().unwrap(), Bound::Today(-14));
+        assert!("today+30".parse::<Bound>().is_err());
+        assert!("tomorrow".parse::<Bound>().is_err());
+
+        let watch = Watch {
+            name: "A month out".to_string(),
+            start: Some("today+30d".parse().unwrap()),
+            end: None,
+            earliest: false,
+            weekdays: vec![],
+            rank: None,
+            min_remaining: 1,
+            close_below: None,
+            channel: Watch::default_channel(),
+        };
+        let days = vec![
+            (date("2023-04-20"), 40),
+            (date("2023-05-01"), 15),
+            (date("2023-09-02"), 16),
+        ];
+        assert_eq!(
+            watch.open_dates(&days, date("2023-04-01")),
+            vec![(date("2023-05-01"), 15), (date("2023-09-02"), 16)]
+        );
+
+        let earliest = Watch {
+            start: None,
+            earliest: true,
+            ..watch
+        };
+        assert_eq!(
+            earliest.open_dates(&days, date("2023-04-01")),
+            vec![(date("2023-04-20"), 40)]
+        );
+    }
+
+    #[test]
+    fn windows_must_run_forwards_and_in_season() {
+        let season = "03-01..09-30".parse::<Season>().unwrap();
+        let mut target = Target::default_targets_from(date("2023-03-01")).remove(0);
+        assert!(target.validate(&season).is_ok());
+
+        target.end = Some(date("2023-04-01"));
+        let err = target.validate(&season).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`Mexican border` starts on 2023-04-02 but ends before that on 2023-04-01"
+        );
+
+        target.start = Some(date("2023-11-01"));
+        target.end = Some(date("2023-12-31"));
+        let err = target.validate(&season).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("outside the permit season 03-01..09-30"));
+    }
+
+    #[test]
+    fn weekday_filter_keeps_only_those_days() {
+        let watch: Watch = toml::from_str(
+            r#"
+            name = "Weekend starts"
+            weekdays = ["fri", "Saturday"]
+            "#,
+        )
+        .unwrap();
+        let days = vec![
+            // Thursday, Friday, Saturday, Sunday
+            (date("2023-04-13"), 5),
+            (date("2023-04-14"), 5),
+            (date("2023-04-15"), 5),
+            (date("2023-04-16"), 5),
+        ];
+        assert_eq!(
+            watch.open_dates(&days, date("2023-04-01")),
+            vec![(date("2023-04-14"), 5), (date("2023-04-15"), 5)]
+        );
+    }
+
+    #[test]
+    fn ranked_best_first() {
+        let days = vec![
+            (date("2023-04-13"), 5),
+            (date("2023-04-14"), 9),
+            (date("2023-04-15"), 2),
+            (date("2023-04-23"), 9),
+        ];
+        let rank = |toml: &str| {
+            let mut watch = Target::default_targets_from(date("2023-03-01"))
+                .remove(0)
+                .watches()
+                .remove(0);
+            watch.rank = Some(toml::from_str(toml).unwrap());
+            watch.ranked(days.clone(), date("2023-04-01"))
+        };
+        let first = |ranked: Vec<(NaiveDate, u64)>| ranked[0].0;
+
+        // No preference beyond the defaults' own: most remaining, then chronological
+        assert_eq!(rank(""), vec![days[1], days[3], days[0], days[2]]);
+        assert_eq!(first(rank(r#"ideal = "2023-04-21""#)), date("2023-04-23"));
+        assert_eq!(first(rank(r#"weekdays = ["sat"]"#)), date("2023-04-15"));
+        assert_eq!(
+            first(rank(
+                r#"by = ["ideal"]
+                ideal = "today+12d""#
+            )),
+            date("2023-04-13")
+        );
+    }
+
+    #[test]
+    fn defaults_roll_over_to_the_next_season() {
+        let target = |today| Target::default_targets_from(date(today)).remove(0);
+        assert_eq!(target("2024-05-05").start, Some(date("2024-04-02")));
+        assert_eq!(target("2024-05-06").start, Some(date("2025-04-02")));
+        assert!(target("2024-05-06").over(date("2025-05-06")));
+
+        // A season over New Year
+        let season = "11-01..02-28".parse::<Season>().unwrap();
+        assert!(season.contains(date("2024-12-31")) && season.contains(date("2025-01-15")));
+        assert!(!season.contains(date("2025-03-01")));
+        let mut target = target("2024-05-06");
+        target.start = Some(date("2024-12-20"));
+        target.end = Some(date("2025-01-10"));
+        assert!(target.validate(&season).is_ok());
+    }
+
+    #[test]
+    fn no_watches_means_the_whole_range() {
+        let target = Target::default_targets_from(date("2023-03-01")).remove(0);
+        let watches = target.watches();
+        assert_eq!(watches.len(), 1);
+        assert_eq!(watches[0].name, "Mexican border");
+        assert_eq!(watches[0].channel, "pcta-alerts");
+    }
+
+    #[test]
+    fn only_a_calendar_with_dates_for_a_watch_is_in_season() {
+        let target = Target::default_targets_from(date("2023-03-01")).remove(0);
+        let today = date("2023-03-01");
+        // Full dates still mean the season is up
+        assert!(target.lists(&[(date("2023-04-14"), 0)], today));
+        assert!(!target.lists(&[(date("2023-12-01"), 5)], today));
+        assert!(!target.lists(&[], today));
+    }
+
+    #[test]
+    fn without_a_range_the_whole_calendar_is_watched() {
+        let season = "03-01..09-30".parse::<Season>().unwrap();
+        let target: Target = toml::from_str(r#"terminus = "mexican-border""#).unwrap();
+        assert!(target.validate(&season).is_ok());
+        assert!(!target.over(date("2030-01-01")));
+        let days = vec![(date("2023-03-01"), 0), (date("2023-06-30"), 2)];
+        assert_eq!(
+            target.open_dates(days.clone()),
+            vec![(date("2023-06-30"), 2)]
+        );
+        assert_eq!(
+            target.watches()[0].open_dates(&days, date("2023-03-01")),
+            vec![(date("2023-06-30"), 2)]
+        );
+        assert_eq!(
+            target.span(days.iter().map(|(day, _)| *day)),
+            Some((date("2023-03-01"), date("2023-06-30")))
+        );
+        assert_eq!(target.span([]), None);
+
+        let narrowed: Target = toml::from_str(
+            r#"
+            terminus = "mexican-border"
+            start = "2023-04-01"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(narrowed.window(), "2023-04-01..");
+        assert_eq!(narrowed.open_dates(days), vec![(date("2023-06-30"), 2)]);
+
+        let recreation: Target = toml::from_str(
+            r#"
+            permit_id = "233262"
+            division = "406"
+            "#,
+        )
+        .unwrap();
+        assert!(recreation.validate(&season).is_err());
+    }
+}
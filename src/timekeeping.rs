@@ -0,0 +1,79 @@
+use chrono::{DateTime, Local, NaiveDate, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// The one source of time for the scraper. Everything internal is `DateTime<Utc>`, only
+/// `format` turns a time into the human-readable display zone, and durations (uptime, timers)
+/// come from the monotonic clock so an NTP step or a DST change can't bend them.
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    /// `None` for the machine's local zone
+    display: Option<Tz>,
+    started: Instant,
+}
+
+/// A moment in records, in UTC for machines and in the display zone for people
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub utc: DateTime<Utc>,
+    pub local: String,
+}
+
+impl Clock {
+    pub fn new(display: Option<Tz>) -> Self {
+        Clock {
+            display,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    /// RFC 3339 in the display zone, what every message and log line shows
+    pub fn format(&self, at: DateTime<Utc>) -> String {
+        match self.display {
+            Some(tz) => at
+                .with_timezone(&tz)
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            None => at
+                .with_timezone(&Local)
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+
+    /// The day `at` falls on in the display zone, what `today` in a watch means
+    pub fn today(&self, at: DateTime<Utc>) -> NaiveDate {
+        match self.display {
+            Some(tz) => at.with_timezone(&tz).date_naive(),
+            None => at.with_timezone(&Local).date_naive(),
+        }
+    }
+
+    pub fn stamp(&self, at: DateTime<Utc>) -> Stamp {
+        Stamp {
+            utc: at,
+            local: self.format(at),
+        }
+    }
+
+    /// How long this process has been running
+    pub fn uptime(&self) -> std::time::Duration {
+        self.started.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_in_the_display_zone() {
+        let clock = Clock::new(Some(chrono_tz::America::Los_Angeles));
+        let at: DateTime<Utc> = "2023-04-17T17:30:00Z".parse().unwrap();
+        assert_eq!(clock.format(at), "2023-04-17T10:30:00-07:00");
+        assert_eq!(clock.stamp(at).utc, at);
+    }
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::time::Instant;
+
+/// Where the time of one scrape went, summed over the requests it took. A slowly growing time
+/// to first byte is the site throttling us before it blocks outright. Connecting isn't timed
+/// on its own, reqwest 0.11 has no hook between the lookup and the first byte, so a new
+/// connection's TCP and TLS handshakes are part of `ttfb_ms` and say so wherever it is shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timings {
+    pub requests: u32,
+    /// Lookups for new connections, a pooled one needs none and SOCKS5 proxies do their own
+    pub dns_ms: u64,
+    /// Sending the requests until their headers are in, connecting included
+    pub ttfb_ms: u64,
+    /// Sending the requests through reading their bodies
+    pub total_ms: u64,
+    pub parse_ms: u64,
+}
+
+impl Timings {
+    /// Average time to first byte per request
+    pub fn ttfb_avg_ms(&self) -> u64 {
+        self.ttfb_ms / self.requests.max(1) as u64
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}ms over {} requests (dns {}ms, connect + first byte {}ms, parse {}ms)",
+            self.total_ms, self.requests, self.dns_ms, self.ttfb_ms, self.parse_ms
+        )
+    }
+}
+
+tokio::task_local! {
+    /// The scrape being timed in this task. Task-local so concurrent scrapes keep their own.
+    static CURRENT: RefCell<Timings>;
+}
+
+/// Runs `f`, returning its output along with what the fetch code recorded while it ran
+pub async fn measure<F: Future>(f: F) -> (F::Output, Timings) {
+    CURRENT
+        .scope(RefCell::new(Timings::default()), async move {
+            let out = f.await;
+            (out, CURRENT.with(|t| *t.borrow()))
+        })
+        .await
+}
+
+fn record(f: impl FnOnce(&mut Timings)) {
+    // Outside `measure` nobody is asking
+    let _ = CURRENT.try_with(|t| f(&mut t.borrow_mut()));
+}
+
+fn ms(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
+/// A lookup started at `started` is done
+pub fn dns(started: Instant) {
+    record(|t| t.dns_ms += ms(started));
+}
+
+/// The headers of a request sent at `started` are in
+pub fn first_byte(started: Instant) {
+    record(|t| {
+        t.requests += 1;
+        t.ttfb_ms += ms(started);
+    });
+}
+
+/// The body of a request sent at `started` is in
+pub fn done(started: Instant) {
+    record(|t| t.total_ms += ms(started));
+}
+
+pub fn parse<T>(f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let out = f();
+    record(|t| t.parse_ms += ms(started));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn concurrent_scrapes_keep_their_own_timings() {
+        let scrape = |n: u32| async move {
+            for _ in 0..n {
+                let started = Instant::now();
+                tokio::task::yield_now().await;
+                first_byte(started);
+            }
+        };
+        let ((_, a), (_, b)) = tokio::join!(measure(scrape(1)), measure(scrape(3)));
+        assert_eq!((a.requests, b.requests), (1, 3));
+        // Nothing to record into, and no panic either
+        first_byte(Instant::now());
+    }
+}
@@ -0,0 +1,349 @@
+mod mullvad;
+mod none;
+mod wireguard;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::config::{KillSwitchMode, VpnConfig, VpnProviderConfig};
+
+pub use mullvad::Mullvad;
+pub use none::NoVpn;
+pub use wireguard::WireGuard;
+
+/// IP echo that also geolocates, answers `{"ip": "...", "country": "US", ...}`
+const IP_ECHO_URL: &str = "https://ipinfo.io/json";
+
+/// Reconnects tried by `rotate_verified` before reporting the rotation as failed
+const ROTATE_ATTEMPTS: u32 = 3;
+
+/// How long `connect`/`reconnect` wait for the tunnel to report up before giving up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const STATUS_POLL: Duration = Duration::from_secs(1);
+
+/// Where our traffic currently leaves the tunnel
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExitIp {
+    pub ip: IpAddr,
+    /// ISO country code, if the echo service knew it
+    pub country: Option<String>,
+}
+
+impl ExitIp {
+    /// Whether the exit geolocates to `country`, any country matches `""`
+    pub fn in_country(&self, country: &str) -> bool {
+        country.is_empty()
+            || self
+                .country
+                .as_deref()
+                .is_some_and(|c| c.eq_ignore_ascii_case(country))
+    }
+}
+
+impl fmt::Display for ExitIp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.country {
+            Some(country) => write!(f, "{} ({})", self.ip, country),
+            None => write!(f, "{}", self.ip),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Connected,
+    Connecting,
+    Disconnected,
+}
+
+/// Something that can hide our home IP from the portal and hand us a new exit on demand
+#[async_trait]
+pub trait VpnProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Brings the tunnel up and only returns once `status` reports `Connected`
+    async fn connect(&self) -> anyhow::Result<()>;
+
+    /// Tears down the current tunnel in favour of a new exit, waiting for it to come up
+    async fn reconnect(&self) -> anyhow::Result<()>;
+
+    async fn status(&self) -> anyhow::Result<Status>;
+
+    /// Whether `reconnect` can hand us a new exit, `false` leaves rotating to the proxies
+    fn can_rotate(&self) -> bool {
+        true
+    }
+
+    /// The address the portal sees us coming from
+    async fn current_exit_ip(&self, client: &Client) -> anyhow::Result<ExitIp> {
+        let text = client.get(IP_ECHO_URL).send().await?.text().await?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("IP echo service returned '{}'", text.trim()))
+    }
+}
+
+pub fn from_config(config: &VpnConfig) -> Box<dyn VpnProvider> {
+    match &config.provider {
+        VpnProviderConfig::Mullvad => Box::new(Mullvad),
+        VpnProviderConfig::Wireguard { configs } => Box::new(WireGuard::new(configs.clone())),
+        VpnProviderConfig::None => Box::new(NoVpn),
+    }
+}
+
+/// Reconnects and checks that the tunnel really moved: the exit IP has to differ from the one we
+/// had going in and geolocate to `expected_country`. `mullvad reconnect` happily lands on the
+/// same relay, so a rotation that didn't rotate is retried up to `ROTATE_ATTEMPTS` times.
+pub async fn rotate_verified(
+    provider: &dyn VpnProvider,
+    client: &Client,
+    expected_country: &str,
+) -> anyhow::Result<ExitIp> {
+    // The portal may be blocking us but the echo service isn't, so this still answers
+    let before = provider.current_exit_ip(client).await.ok();
+
+    let mut problem = String::new();
+    for attempt in 1..=ROTATE_ATTEMPTS {
+        provider.reconnect().await?;
+        let after = provider.current_exit_ip(client).await?;
+
+        problem = if before.as_ref().map(|b| b.ip) == Some(after.ip) {
+            format!("exit IP is still {}", after.ip)
+        } else if !after.in_country(expected_country) {
+            format!(
+                "exit IP {} is in {} instead of {}",
+                after.ip,
+                after.country.as_deref().unwrap_or("an unknown country"),
+                expected_country
+            )
+        } else {
+            return Ok(after);
+        };
+        crate::info!(
+            "{} rotation attempt {}/{} failed, {}",
+            provider.name(),
+            attempt,
+            ROTATE_ATTEMPTS,
+            problem
+        );
+    }
+    bail!(
+        "{} rotation failed after {} attempts, {}",
+        provider.name(),
+        ROTATE_ATTEMPTS,
+        problem
+    )
+}
+
+/// Checks the tunnel before each pass, see `KillSwitchMode`
+pub struct KillSwitch {
+    mode: KillSwitchMode,
+    /// Where we came from before the tunnel, if the echo service said
+    home: Option<IpAddr>,
+    expected_country: String,
+}
+
+impl KillSwitch {
+    /// Notes the home IP for `KillSwitchMode::ExitIp`, so it has to be armed before connecting.
+    /// `home_ip` is taken as is, otherwise the exit only counts as home while the provider says
+    /// it is disconnected. A tunnel already up, like after a restart, would be its own exit.
+    pub async fn arm(config: &VpnConfig, provider: &dyn VpnProvider, client: &Client) -> Self {
+        let mode = match config.provider {
+            // Nothing to protect, scraping from home is the point
+            VpnProviderConfig::None => KillSwitchMode::Off,
+            _ => config.kill_switch,
+        };
+        let home = match (mode, config.home_ip) {
+            (KillSwitchMode::ExitIp, Some(ip)) => Some(ip),
+            (KillSwitchMode::ExitIp, None) => match provider.status().await {
+                Ok(Status::Disconnected) => {
+                    provider.current_exit_ip(client).await.ok().map(|e| e.ip)
+                }
+                Ok(status) => {
+                    crate::info!(
+                        "{} VPN is {:?} already, the exit IP can't be checked against home. \
+                         Set `home_ip` in [vpn] to check it.",
+                        provider.name(),
+                        status
+                    );
+                    None
+                }
+                Err(e) => {
+                    crate::info!(
+                        "{} VPN status unknown, no home IP: {:#}",
+                        provider.name(),
+                        e
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
+        KillSwitch {
+            mode,
+            home,
+            expected_country: config.expected_country.clone(),
+        }
+    }
+
+    pub fn off() -> Self {
+        KillSwitch {
+            mode: KillSwitchMode::Off,
+            home: None,
+            expected_country: String::new(),
+        }
+    }
+
+    /// Errors with why a pass now would not be safe
+    pub async fn check(&self, provider: &dyn VpnProvider, client: &Client) -> anyhow::Result<()> {
+        if self.mode == KillSwitchMode::Off {
+            return Ok(());
+        }
+        let status = provider.status().await?;
+        if status != Status::Connected {
+            bail!("{} VPN is {:?}", provider.name(), status);
+        }
+        if self.mode == KillSwitchMode::ExitIp {
+            let exit = provider.current_exit_ip(client).await?;
+            if Some(exit.ip) == self.home {
+                bail!("exit IP {} is the home IP", exit.ip);
+            }
+            if !exit.in_country(&self.expected_country) {
+                bail!("exit IP {} is not in {}", exit, self.expected_country);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Polls `provider.status()` until it reports `Connected` or `CONNECT_TIMEOUT` runs out
+async fn wait_connected(provider: &dyn VpnProvider) -> anyhow::Result<()> {
+    let deadline = tokio::time::Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        let status = provider.status().await?;
+        if status == Status::Connected {
+            return Ok(());
+        }
+        if tokio::time::Instant::now() >= deadline {
+            bail!(
+                "{} tunnel not up after {}s, last status = {:?}",
+                provider.name(),
+                CONNECT_TIMEOUT.as_secs(),
+                status
+            );
+        }
+        tokio::time::sleep(STATUS_POLL).await;
+    }
+}
+
+/// Runs `program <args>` and returns stdout, turning a missing binary or a failing exit status
+/// into an error that says so rather than a bare exit code. `hint` is appended to exit failures.
+async fn run(program: &str, args: &[&str], hint: &str) -> anyhow::Result<String> {
+    let output = match crate::exec::command(program).args(args).output().await {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            bail!("`{}` not found on PATH. {}", program, hint)
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to call {} (err)", program)),
+    };
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "`{} {}` failed with {}. {} stderr = '{}'",
+            program,
+            args.join(" "),
+            output.status,
+            hint,
+            stderr.trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Down;
+
+    #[async_trait]
+    impl VpnProvider for Down {
+        fn name(&self) -> &'static str {
+            "down"
+        }
+
+        async fn connect(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn reconnect(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn status(&self) -> anyhow::Result<Status> {
+            Ok(Status::Disconnected)
+        }
+    }
+
+    /// Already connected at startup, exiting from `0`
+    struct Up(IpAddr);
+
+    #[async_trait]
+    impl VpnProvider for Up {
+        fn name(&self) -> &'static str {
+            "up"
+        }
+
+        async fn connect(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn reconnect(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn status(&self) -> anyhow::Result<Status> {
+            Ok(Status::Connected)
+        }
+
+        async fn current_exit_ip(&self, _: &Client) -> anyhow::Result<ExitIp> {
+            Ok(ExitIp {
+                ip: self.0,
+                country: Some("US".to_string()),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn a_tunnel_up_at_startup_isnt_home() {
+        let client = Client::new();
+        let exit: IpAddr = "198.51.100.4".parse().unwrap();
+        let mut config = VpnConfig {
+            kill_switch: KillSwitchMode::ExitIp,
+            ..VpnConfig::default()
+        };
+        let kill_switch = KillSwitch::arm(&config, &Up(exit), &client).await;
+        assert_eq!(kill_switch.home, None);
+        assert!(kill_switch.check(&Up(exit), &client).await.is_ok());
+
+        config.home_ip = Some(exit);
+        let kill_switch = KillSwitch::arm(&config, &Up(exit), &client).await;
+        let err = kill_switch.check(&Up(exit), &client).await.unwrap_err();
+        assert_eq!(err.to_string(), "exit IP 198.51.100.4 is the home IP");
+    }
+
+    #[tokio::test]
+    async fn kill_switch_refuses_a_dropped_tunnel() {
+        let client = Client::new();
+        let kill_switch = KillSwitch::arm(&VpnConfig::default(), &Down, &client).await;
+        let err = kill_switch.check(&Down, &client).await.unwrap_err();
+        assert_eq!(err.to_string(), "down VPN is Disconnected");
+        assert!(KillSwitch::off().check(&Down, &client).await.is_ok());
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::bail;
+use async_trait::async_trait;
+
+use super::{run, wait_connected, Status, VpnProvider};
+
+const HINT: &str = "Is the Mullvad VPN app installed and its daemon running?";
+
+// `mullvad relay set location us`                          - Allows for selection of relays in the United States
+// `mullvad relay set tunnel wireguard --entry-location us` - Uses the WireGuard protocol through US relays
+// `mullvad relay get`                                      - Returns the relay configuration
+//
+// `mullvad connect`                                        - Connects using the relay config
+// `mullvad reconnect`                                      - Forces a reconnection at a new location
+// `mullvad status`                                         - 'Connected' or 'Disconnected' appear in output
+// `mullvad disconnect`                                     - Disconnects from the relay
+pub struct Mullvad;
+
+#[async_trait]
+impl VpnProvider for Mullvad {
+    fn name(&self) -> &'static str {
+        "mullvad"
+    }
+
+    /// Configures US WireGuard relays before connecting
+    async fn connect(&self) -> anyhow::Result<()> {
+        mullvad(&["relay", "set", "location", "us"]).await?;
+        mullvad(&[
+            "relay",
+            "set",
+            "tunnel",
+            "wireguard",
+            "--entry-location",
+            "us",
+        ])
+        .await?;
+        let relay = mullvad(&["relay", "get"]).await?;
+        crate::info!("Mullvad relay configuration: {}", relay.trim());
+
+        mullvad(&["connect"]).await?;
+        wait_connected(self).await
+    }
+
+    async fn reconnect(&self) -> anyhow::Result<()> {
+        mullvad(&["reconnect"]).await?;
+        wait_connected(self).await
+    }
+
+    async fn status(&self) -> anyhow::Result<Status> {
+        let out = mullvad(&["status"]).await?;
+        let status = if out.contains("Disconnected") {
+            Status::Disconnected
+        } else if out.contains("Connecting") {
+            Status::Connecting
+        } else if out.contains("Connected") {
+            Status::Connected
+        } else {
+            bail!("Unrecognized `mullvad status` output = '{}'", out.trim());
+        };
+        Ok(status)
+    }
+}
+
+async fn mullvad(args: &[&str]) -> anyhow::Result<String> {
+    run("mullvad", args, HINT).await
+}
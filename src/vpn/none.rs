@@ -0,0 +1,31 @@
+use async_trait::async_trait;
+
+use super::{Status, VpnProvider};
+
+/// No tunnel at all, requests leave from the machine's own IP. Reconnecting is a no-op, so
+/// blocks are ridden out by waiting or rotating the proxies.
+pub struct NoVpn;
+
+#[async_trait]
+impl VpnProvider for NoVpn {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    async fn connect(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn reconnect(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn can_rotate(&self) -> bool {
+        false
+    }
+
+    /// There is no tunnel to wait for, so we are always as connected as we'll get
+    async fn status(&self) -> anyhow::Result<Status> {
+        Ok(Status::Connected)
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{run, wait_connected, Status, VpnProvider};
+
+const HINT: &str = "Is wireguard-tools installed and are we allowed to manage interfaces?";
+
+/// Plain WireGuard via `wg-quick`. Each config file is one exit, reconnecting brings the current
+/// one down and moves on to the next file in the list.
+pub struct WireGuard {
+    configs: Vec<PathBuf>,
+    current: AtomicUsize,
+}
+
+impl WireGuard {
+    pub fn new(configs: Vec<PathBuf>) -> Self {
+        WireGuard {
+            configs,
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    fn config(&self) -> anyhow::Result<&Path> {
+        if self.configs.is_empty() {
+            bail!("WireGuard VPN provider selected but no `configs` were given");
+        }
+        let i = self.current.load(Ordering::Relaxed) % self.configs.len();
+        Ok(&self.configs[i])
+    }
+
+    /// `wg-quick` names the interface after the config file
+    fn interface(&self) -> anyhow::Result<String> {
+        let config = self.config()?;
+        config
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .with_context(|| format!("WireGuard config '{}' has no file name", config.display()))
+    }
+
+    async fn up(&self) -> anyhow::Result<()> {
+        let config = self.config()?.to_string_lossy().into_owned();
+        run("wg-quick", &["up", &config], HINT).await?;
+        Ok(())
+    }
+
+    async fn down(&self) -> anyhow::Result<()> {
+        let config = self.config()?.to_string_lossy().into_owned();
+        run("wg-quick", &["down", &config], HINT).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VpnProvider for WireGuard {
+    fn name(&self) -> &'static str {
+        "wireguard"
+    }
+
+    async fn connect(&self) -> anyhow::Result<()> {
+        // `wg-quick up` refuses to bring up an interface that already exists
+        if self.status().await? != Status::Connected {
+            self.up().await?;
+        }
+        wait_connected(self).await
+    }
+
+    async fn reconnect(&self) -> anyhow::Result<()> {
+        if self.status().await? == Status::Connected {
+            self.down().await?;
+        }
+        self.current.fetch_add(1, Ordering::Relaxed);
+        self.up().await?;
+        wait_connected(self).await
+    }
+
+    async fn status(&self) -> anyhow::Result<Status> {
+        let interface = self.interface()?;
+        // `wg show` exits non-zero when the interface doesn't exist
+        match run("wg", &["show", &interface], HINT).await {
+            Ok(out) if out.contains("latest handshake") => Ok(Status::Connected),
+            Ok(_) => Ok(Status::Connecting),
+            Err(_) => Ok(Status::Disconnected),
+        }
+    }
+}
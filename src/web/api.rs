@@ -0,0 +1,100 @@
+use axum::extract::{Query, State};
+use axum::http::header;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::Dashboard;
+use crate::bot::FailedScrape;
+use crate::history::Snapshot;
+use crate::ics;
+use crate::state;
+
+/// `GET /api/availability`: the last pass as written to the state file, `null` before the first
+pub(super) async fn availability(State(dashboard): State<Dashboard>) -> Json<Option<state::State>> {
+    Json(dashboard.control.last())
+}
+
+/// RFC 3339 bounds, either can be left out
+#[derive(Debug, Deserialize)]
+pub(super) struct Range {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+/// `GET /api/history?from=&to=`: every calendar change in the range, oldest first
+pub(super) async fn history(
+    State(dashboard): State<Dashboard>,
+    Query(range): Query<Range>,
+) -> Json<Vec<Snapshot>> {
+    Json(dashboard.history.between(range.from, range.to))
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct Status {
+    paused: bool,
+    /// `!status`'s summary of the last pass
+    summary: String,
+    next_scrape: Option<DateTime<Utc>>,
+    /// Newest first
+    errors: Vec<FailedScrape>,
+}
+
+/// `GET /api/status`
+pub(super) async fn status(State(dashboard): State<Dashboard>) -> Json<Status> {
+    let control = &dashboard.control;
+    Json(Status {
+        paused: control.paused(),
+        summary: control.status(),
+        next_scrape: control.next(),
+        errors: control.errors(),
+    })
+}
+
+/// `GET /calendar.ics`: the open start dates of the last pass, for subscribing to from a
+/// calendar app
+pub(super) async fn calendar(
+    State(dashboard): State<Dashboard>,
+) -> ([(header::HeaderName, &'static str); 1], String) {
+    let body = ics::calendar(dashboard.control.last().as_ref());
+    (
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        body,
+    )
+}
+
+/// `GET /api/events`: a Server-Sent Events stream with a `change` event, a `history::Change` as
+/// JSON, whenever a target's calendar changes, and every `event::Event` under its own kind
+pub(super) async fn events(
+    State(dashboard): State<Dashboard>,
+) -> Sse<impl Stream<Item = Result<Event, serde_json::Error>>> {
+    let changes = stream::unfold(dashboard.history.subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(change) => {
+                    let event = Event::default().event("change").json_data(change);
+                    return Some((event, rx));
+                }
+                // Missed some, the next one carries the whole calendar anyway
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = stream::unfold(dashboard.events.subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let sse = Event::default().event(event.kind()).json_data(event);
+                    return Some((sse, rx));
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream::select(changes, events)).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,362 @@
+use anyhow::Context;
+use axum::extract::State;
+use axum::response::{Html, Redirect};
+use axum::routing::{get, post};
+use axum::Router;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::bot::Control;
+use crate::event::Events;
+use crate::history::History;
+use crate::timekeeping::Clock;
+
+mod api;
+
+/// How far back the history chart goes
+const CHART_DAYS: i64 = 7;
+const CHART_WIDTH: f64 = 720.0;
+const CHART_HEIGHT: f64 = 180.0;
+/// Line colors of the chart, one per target in turn
+const COLORS: [&str; 4] = ["#2b7a3d", "#1f5fa8", "#b35c00", "#8a2f8a"];
+
+/// What the web UI and its JSON API read from the running scraper
+#[derive(Clone)]
+pub struct Dashboard {
+    pub control: Arc<Control>,
+    pub history: Arc<History>,
+    pub events: Arc<Events>,
+    pub clock: Clock,
+}
+
+/// `--web` addresses: `host:port`, or just `:port` for localhost. There is no login, so
+/// listening beyond localhost has to be asked for.
+pub fn parse_addr(s: &str) -> anyhow::Result<SocketAddr> {
+    let addr = match s.starts_with(':') {
+        true => format!("127.0.0.1{}", s),
+        false => s.to_string(),
+    };
+    addr.parse()
+        .with_context(|| format!("Invalid address '{}', expected ':8080' or 'host:port'", s))
+}
+
+pub fn router(dashboard: Dashboard) -> Router {
+    Router::new()
+        .route("/", get(index))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/scrape", post(scrape))
+        .route("/api/availability", get(api::availability))
+        .route("/api/history", get(api::history))
+        .route("/api/status", get(api::status))
+        .route("/api/events", get(api::events))
+        .route("/calendar.ics", get(api::calendar))
+        .with_state(dashboard)
+}
+
+/// Serves the web UI until the server fails
+pub async fn serve(addr: SocketAddr, dashboard: Dashboard) -> anyhow::Result<()> {
+    let server =
+        axum::Server::try_bind(&addr).with_context(|| format!("Failed to listen on {}", addr))?;
+    crate::info!("Web UI on http://{}", addr);
+    server
+        .serve(router(dashboard).into_make_service())
+        .await
+        .context("Web server failed")
+}
+
+async fn index(State(dashboard): State<Dashboard>) -> Html<String> {
+    Html(page(&dashboard, dashboard.clock.now()))
+}
+
+async fn pause(State(dashboard): State<Dashboard>) -> Redirect {
+    crate::info!("Paused from the web UI");
+    dashboard.control.pause();
+    Redirect::to("/")
+}
+
+async fn resume(State(dashboard): State<Dashboard>) -> Redirect {
+    crate::info!("Resumed from the web UI");
+    dashboard.control.resume();
+    Redirect::to("/")
+}
+
+async fn scrape(State(dashboard): State<Dashboard>) -> Redirect {
+    dashboard.control.scrape_now();
+    Redirect::to("/")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The whole page, rendered on the server so it works without JavaScript
+fn page(dashboard: &Dashboard, now: DateTime<Utc>) -> String {
+    let (control, clock) = (&dashboard.control, dashboard.clock);
+    let mut html = String::from(
+        "<!doctype html><html><head><meta charset=\"utf-8\">\
+         <meta http-equiv=\"refresh\" content=\"30\"><title>pcta</title>\
+         <style>body{font-family:sans-serif;max-width:760px;margin:2em auto}\
+         td,th{padding:2px 8px;text-align:left}form{display:inline}</style></head><body>\
+         <h1>PCTA permits</h1>",
+    );
+
+    let state = match control.paused() {
+        true => "Paused",
+        false => "Running",
+    };
+    let next = match control.next() {
+        Some(at) if !control.paused() => format!(", next scrape {}", clock.format(at)),
+        _ => String::new(),
+    };
+    let _ = write!(html, "<p>{}{}</p>", state, next);
+    let toggle = match control.paused() {
+        true => "<form method=\"post\" action=\"/resume\"><button>Resume</button></form>",
+        false => "<form method=\"post\" action=\"/pause\"><button>Pause</button></form>",
+    };
+    html += toggle;
+    html += "<form method=\"post\" action=\"/scrape\"><button>Scrape now</button></form>";
+    html += " <a href=\"/calendar.ics\">Calendar feed</a>";
+
+    html += "<h2>Available</h2>";
+    match control.last() {
+        None => html += "<p>No scrape yet</p>",
+        Some(last) => {
+            let _ = write!(
+                html,
+                "<p>As of {}</p><table>",
+                escape(&last.scraped_at.local)
+            );
+            for target in &last.targets {
+                let open = match (&target.error, target.open.is_empty()) {
+                    (Some(e), _) => format!("<em>failed: {}</em>", escape(e)),
+                    (None, true) => "Nothing open".to_string(),
+                    (None, false) => target
+                        .open
+                        .iter()
+                        .map(|(date, left)| format!("{} ({})", date, left))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                };
+                let _ = write!(
+                    html,
+                    "<tr><th>{}</th><td>{}</td></tr>",
+                    escape(&target.label),
+                    open
+                );
+            }
+            html += "</table>";
+        }
+    }
+
+    let _ = write!(
+        html,
+        "<h2>Permits left, last {} days</h2>{}",
+        CHART_DAYS,
+        chart(&dashboard.history, now)
+    );
+
+    html += "<h2>Last errors</h2>";
+    let errors = control.errors();
+    if errors.is_empty() {
+        html += "<p>None</p>";
+    } else {
+        html += "<table>";
+        for error in errors {
+            let _ = write!(
+                html,
+                "<tr><td>{}</td><th>{}</th><td>{}</td></tr>",
+                clock.format(error.at),
+                escape(&error.label),
+                escape(&error.error)
+            );
+        }
+        html += "</table>";
+    }
+    html += "</body></html>";
+    html
+}
+
+/// Every target's open permits summed over its range, as an inline SVG step chart
+fn chart(history: &History, now: DateTime<Utc>) -> String {
+    let from = now - Duration::days(CHART_DAYS);
+    let mut series: BTreeMap<String, Vec<(DateTime<Utc>, u64)>> = BTreeMap::new();
+    for snapshot in history.between(Some(from), None) {
+        let left = snapshot.days.iter().map(|(_, left)| left).sum();
+        series
+            .entry(snapshot.label)
+            .or_default()
+            .push((snapshot.at, left));
+    }
+    if series.is_empty() {
+        return "<p>Nothing recorded yet</p>".to_string();
+    }
+    let max = series
+        .values()
+        .flatten()
+        .map(|(_, left)| *left)
+        .max()
+        .unwrap_or_default()
+        .max(1) as f64;
+    let span = (now - from).num_seconds() as f64;
+    let x = |at: DateTime<Utc>| (at - from).num_seconds() as f64 / span * CHART_WIDTH;
+    let y = |left: u64| CHART_HEIGHT - left as f64 / max * CHART_HEIGHT;
+
+    let mut svg = format!(
+        "<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+         style=\"border:1px solid #ccc\">",
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT
+    );
+    let mut legend = String::new();
+    for ((label, points), color) in series.iter().zip(COLORS.iter().cycle()) {
+        // Each count holds until the next change, the last one until now
+        let mut path = vec![];
+        for (i, (at, left)) in points.iter().enumerate() {
+            let until = points.get(i + 1).map(|(next, _)| *next).unwrap_or(now);
+            path.push(format!("{:.1},{:.1}", x(*at), y(*left)));
+            path.push(format!("{:.1},{:.1}", x(until), y(*left)));
+        }
+        let _ = write!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>",
+            color,
+            path.join(" ")
+        );
+        let _ = write!(
+            legend,
+            "<span style=\"color:{}\">&#9632; {}</span> ",
+            color,
+            escape(label)
+        );
+    }
+    svg += "</svg>";
+    format!("{}<p>{} max {}</p>", svg, legend, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Snapshot;
+    use crate::state::{State, TargetState};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn bare_ports_listen_on_localhost() {
+        assert_eq!(parse_addr(":8080").unwrap().to_string(), "127.0.0.1:8080");
+        assert_eq!(parse_addr("0.0.0.0:80").unwrap().to_string(), "0.0.0.0:80");
+        assert!(parse_addr("8080").is_err());
+    }
+
+    #[test]
+    fn page_shows_availability_history_and_errors() {
+        let clock = Clock::new(Some(chrono_tz::America::Los_Angeles));
+        let at: DateTime<Utc> = "2023-04-17T17:00:00Z".parse().unwrap();
+        let day = NaiveDate::from_ymd_opt(2023, 4, 14).unwrap();
+        let dashboard = Dashboard {
+            control: Control::new(vec![]),
+            history: Arc::new(History::open(None).unwrap()),
+            events: Arc::new(Events::default()),
+            clock,
+        };
+        dashboard
+            .history
+            .record(Snapshot {
+                at,
+                label: "Mexican border".to_string(),
+                days: vec![(day, 3)],
+            })
+            .unwrap();
+        dashboard.control.scraped(State {
+            scraped_at: clock.stamp(at),
+            targets: vec![
+                TargetState {
+                    label: "Mexican border".to_string(),
+                    open: vec![(day, 3)],
+                    error: None,
+                    timings: None,
+                },
+                TargetState {
+                    label: "<Canada>".to_string(),
+                    open: vec![],
+                    error: Some("Blocked".to_string()),
+                    timings: None,
+                },
+            ],
+        });
+
+        let html = page(&dashboard, at + Duration::hours(1));
+        assert!(html.contains("2023-04-14 (3)"));
+        assert!(html.contains("<polyline"));
+        assert!(html.contains("&lt;Canada&gt;"));
+        assert!(html.contains("action=\"/pause\""));
+    }
+
+    #[tokio::test]
+    async fn api_serves_history_ranges() {
+        let dashboard = Dashboard {
+            control: Control::new(vec![]),
+            history: Arc::new(History::open(None).unwrap()),
+            events: Arc::new(Events::default()),
+            clock: Clock::new(None),
+        };
+        for at in ["2023-04-17T17:00:00Z", "2023-04-18T17:00:00Z"] {
+            dashboard
+                .history
+                .record(Snapshot {
+                    at: at.parse().unwrap(),
+                    label: "Mexican border".to_string(),
+                    days: vec![],
+                })
+                .unwrap();
+        }
+        let recorded = dashboard.history.clone();
+        let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap())
+            .serve(router(dashboard).into_make_service());
+        let base = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        let history: Vec<Snapshot> =
+            reqwest::get(format!("{}/api/history?from=2023-04-18T00:00:00Z", base))
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+        assert_eq!(history.len(), 1);
+        let status: serde_json::Value = reqwest::get(format!("{}/api/status", base))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(status["paused"], false);
+        let bad = reqwest::get(format!("{}/api/history?from=yesterday", base))
+            .await
+            .unwrap();
+        assert_eq!(bad.status(), 400);
+
+        let mut events = reqwest::get(format!("{}/api/events", base)).await.unwrap();
+        recorded
+            .record(Snapshot {
+                at: "2023-04-19T17:00:00Z".parse().unwrap(),
+                label: "Mexican border".to_string(),
+                days: vec![(NaiveDate::from_ymd_opt(2023, 4, 20).unwrap(), 2)],
+            })
+            .unwrap();
+        let event = events.chunk().await.unwrap().unwrap();
+        let event = String::from_utf8_lossy(&event);
+        assert!(event.starts_with("event:change\n"), "{}", event);
+        assert!(
+            event.contains(r#""opened":[["2023-04-20",2]]"#),
+            "{}",
+            event
+        );
+    }
+}